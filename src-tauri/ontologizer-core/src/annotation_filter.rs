@@ -0,0 +1,174 @@
+//! Declarative filters over the loaded annotations.
+//!
+//! An [`AnnotationFilter`] is applied to the complete records of the loaded annotation file, so that
+//! an analysis can be rerun with different subsets of the annotations without reloading the file.
+//! Empty lists and missing bounds do not restrict the annotations.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::evidence::{AllowedEvidence, EvidenceFilter};
+use crate::gaf::{AnnotationDate, GafRecord};
+use crate::goannot::{Aspect, GoTermRelation};
+use crate::gontology::GeneOntology;
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct AnnotationFilter {
+    pub evidence: EvidenceFilter,
+    pub aspects: Vec<Aspect>,
+    pub relations: Vec<GoTermRelation>,
+    /// e.g., UniProt or MGI
+    pub assigned_by: Vec<String>,
+    /// NCBI taxon ids of the annotated gene product, e.g., 9606, taxon:9606 or NCBITaxon:9606
+    pub taxa: Vec<String>,
+    /// First and last day (inclusive) on which the annotations were made
+    pub date_from: Option<AnnotationDate>,
+    pub date_to: Option<AnnotationDate>,
+    /// e.g., protein or ncRNA
+    pub db_object_types: Vec<String>,
+    /// Annotations to these GO terms are removed (not to their descendants)
+    pub excluded_go_ids: Vec<String>,
+}
+
+/// The numeric part of a taxon id
+fn taxon_number(taxon: &str) -> &str {
+    taxon.rsplit(':').next().unwrap_or(taxon)
+}
+
+/// An [`AnnotationFilter`] with the evidence resolved to codes and the lists turned into sets
+pub struct CompiledFilter<'a> {
+    filter: &'a AnnotationFilter,
+    evidence: AllowedEvidence,
+    taxa: HashSet<&'a str>,
+    excluded_go_ids: HashSet<&'a str>,
+}
+
+impl AnnotationFilter {
+    /// `eco` is needed if the evidence filter selects ECO terms (see [`EvidenceFilter::allowed_codes`]).
+    /// The filter is compiled for `records`, whose precise ECO ids are matched.
+    pub fn compile(
+        &self,
+        eco: Option<&GeneOntology>,
+        records: &[GafRecord],
+    ) -> Result<CompiledFilter<'_>, String> {
+        let eco_ids = records
+            .iter()
+            .filter_map(|r| Some((r.evidence_code, r.eco_id.as_deref()?)));
+        Ok(CompiledFilter {
+            filter: self,
+            evidence: self.evidence.allowed(eco, eco_ids)?,
+            taxa: self.taxa.iter().map(|t| taxon_number(t)).collect(),
+            excluded_go_ids: self.excluded_go_ids.iter().map(String::as_str).collect(),
+        })
+    }
+}
+
+impl CompiledFilter<'_> {
+    pub fn matches(&self, record: &GafRecord) -> bool {
+        let f = self.filter;
+        let any_of =
+            |values: &[String], value: &str| values.is_empty() || values.iter().any(|v| v == value);
+        self.evidence
+            .contains(record.evidence_code, record.eco_id.as_deref())
+            && (f.aspects.is_empty() || f.aspects.contains(&record.aspect))
+            && (f.relations.is_empty() || f.relations.contains(&record.qualifier.relation))
+            && any_of(&f.assigned_by, &record.assigned_by)
+            && any_of(&f.db_object_types, &record.db_object_type)
+            // the first taxon is the one of the gene product, others are interacting organisms
+            && (self.taxa.is_empty()
+                || record
+                    .taxa
+                    .first()
+                    .is_some_and(|t| self.taxa.contains(taxon_number(&t.value))))
+            && f.date_from.is_none_or(|from| record.date >= from)
+            && f.date_to.is_none_or(|to| record.date <= to)
+            && !self.excluded_go_ids.contains(record.go_id.value.as_str())
+    }
+
+    pub fn apply<'r>(&self, records: &'r [GafRecord]) -> Vec<&'r GafRecord> {
+        records.iter().filter(|r| self.matches(r)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::evidence::{EvidenceCode, EvidenceSelector};
+    use crate::goannot::EviCode;
+
+    const LINES: [&str; 4] = [
+        "UniProtKB\tP04637\tTP53\tinvolved_in\tGO:0006915\tPMID:1\tIMP\t\tP\t\t\tprotein\ttaxon:9606\t20201231\tMGI\t\t",
+        "UniProtKB\tP04637\tTP53\tenables\tGO:0003700\tPMID:2\tIEA\t\tF\t\t\tprotein\ttaxon:9606\t20240114\tUniProt\t\t",
+        "UniProtKB\tP02340\tTrp53\tlocated_in\tGO:0005634\tPMID:3\tIDA\t\tC\t\t\tprotein\ttaxon:10090\t20150301\tMGI\t\t",
+        "RNAcentral\tURS0000\tMIR21\tinvolved_in\tGO:0035195\tPMID:4\tIEP\t\tP\t\t\tmiRNA\ttaxon:9606|taxon:10090\t20220615\tRNAcentral\t\t",
+    ];
+
+    fn records() -> Vec<GafRecord> {
+        LINES
+            .iter()
+            .map(|l| GafRecord::from_str(l).unwrap())
+            .collect()
+    }
+
+    fn symbols(filter: &AnnotationFilter) -> Vec<String> {
+        let records = records();
+        filter
+            .compile(None, &records)
+            .unwrap()
+            .apply(&records)
+            .iter()
+            .map(|r| r.db_object_symbol.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_filter() {
+        assert_eq!(4, symbols(&AnnotationFilter::default()).len());
+    }
+
+    #[test]
+    fn test_filters() {
+        let experimental_except_iep = AnnotationFilter {
+            evidence: EvidenceFilter {
+                include: vec![EvidenceSelector::Group(EviCode::EXP)],
+                exclude: vec![EvidenceSelector::Code(EvidenceCode::IEP)],
+            },
+            ..Default::default()
+        };
+        assert_eq!(vec!["TP53", "Trp53"], symbols(&experimental_except_iep));
+        let human_processes = AnnotationFilter {
+            aspects: vec![Aspect::P],
+            taxa: vec!["NCBITaxon:9606".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(vec!["TP53", "MIR21"], symbols(&human_processes));
+        let mgi_since_2016 = AnnotationFilter {
+            assigned_by: vec!["MGI".to_string()],
+            date_from: Some(AnnotationDate::new(2016, 1, 1).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(vec!["TP53"], symbols(&mgi_since_2016));
+        let proteins = AnnotationFilter {
+            db_object_types: vec!["protein".to_string()],
+            relations: vec![GoTermRelation::InvolvedIn, GoTermRelation::Enables],
+            excluded_go_ids: vec!["GO:0006915".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(vec!["TP53"], symbols(&proteins));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let filter: AnnotationFilter = serde_json::from_str(
+            r#"{"aspects": ["F", "C"], "date_to": "2020-01-01", "evidence": {"exclude": [{"Code": "IEA"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(vec!["Trp53"], symbols(&filter));
+        let json = serde_json::to_string(&filter).unwrap();
+        assert_eq!(filter, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -0,0 +1,385 @@
+//! Index of the annotations by gene and by term.
+//!
+//! Genes are interned as consecutive indices (in the order of their symbols) and terms are referred
+//! to by their index in the [`GeneOntology`]. Sets of genes are bitsets ([`GeneSet`]), so that the
+//! counts of the enrichment analysis are intersections of two bitsets.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::goannot::GoAnnot;
+use crate::gontology::GeneOntology;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A set of gene indices stored as a bitset.
+///
+/// Removing genes or intersecting can leave trailing zero words, so sets are compared by their genes.
+#[derive(Clone, Debug, Default, Eq)]
+pub struct GeneSet {
+    words: Vec<u64>,
+}
+
+impl GeneSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, gene: usize) -> bool {
+        let (word, bit) = (gene / WORD_BITS, gene % WORD_BITS);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let inserted = self.words[word] & (1 << bit) == 0;
+        self.words[word] |= 1 << bit;
+        inserted
+    }
+
+    pub fn remove(&mut self, gene: usize) {
+        if let Some(word) = self.words.get_mut(gene / WORD_BITS) {
+            *word &= !(1 << (gene % WORD_BITS));
+        }
+    }
+
+    pub fn contains(&self, gene: usize) -> bool {
+        self.words
+            .get(gene / WORD_BITS)
+            .is_some_and(|w| w & (1 << (gene % WORD_BITS)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    /// The number of genes in both sets, without building the intersection
+    pub fn intersection_len(&self, other: &GeneSet) -> usize {
+        self.words
+            .iter()
+            .zip(&other.words)
+            .map(|(a, b)| (a & b).count_ones() as usize)
+            .sum()
+    }
+
+    pub fn intersection(&self, other: &GeneSet) -> GeneSet {
+        GeneSet {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| a & b)
+                .collect(),
+        }
+    }
+
+    pub fn union_with(&mut self, other: &GeneSet) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a |= b;
+        }
+    }
+
+    pub fn intersect_with(&mut self, other: &GeneSet) {
+        self.words.truncate(other.words.len());
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= b;
+        }
+    }
+
+    pub fn difference_with(&mut self, other: &GeneSet) {
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= !b;
+        }
+    }
+
+    /// The words without the trailing zero words
+    fn significant_words(&self) -> &[u64] {
+        let len = self
+            .words
+            .iter()
+            .rposition(|w| *w != 0)
+            .map_or(0, |i| i + 1);
+        &self.words[..len]
+    }
+
+    /// The genes in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            (0..WORD_BITS)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| i * WORD_BITS + bit)
+        })
+    }
+}
+
+impl PartialEq for GeneSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.significant_words() == other.significant_words()
+    }
+}
+
+impl FromIterator<usize> for GeneSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = GeneSet::new();
+        for gene in iter {
+            set.insert(gene);
+        }
+        set
+    }
+}
+
+/// Gene-to-term and term-to-gene maps of a set of annotations.
+///
+/// According to the true path rule, a gene annotated to a term is implicitly annotated to all of its
+/// ancestors along is_a and part_of, so the index keeps both the direct and the propagated terms of
+/// each gene, and both the directly annotated and all (direct and inherited) genes of each term.
+/// Genes are keyed by their symbol; gene product ids (e.g. UniProtKB:P04637) are accepted as aliases.
+pub struct AnnotationIndex {
+    symbols: Vec<String>,
+    gene_ids: HashMap<String, usize>,
+    direct_terms: Vec<Vec<usize>>,
+    propagated_terms: Vec<Vec<usize>>,
+    direct_genes: HashMap<usize, GeneSet>,
+    term_genes: HashMap<usize, GeneSet>,
+}
+
+impl AnnotationIndex {
+    /// Annotations to GO ids that are not in the ontology (e.g., from an older release) are skipped
+    pub fn new(go: &GeneOntology, annotations: &[GoAnnot]) -> Self {
+        let mut symbols: Vec<String> = annotations
+            .iter()
+            .filter(|a| go.index_of(&a.go_id).is_some())
+            .map(|a| a.gene_product_symbol.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        symbols.sort();
+        let mut gene_ids: HashMap<String, usize> = symbols
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.clone(), i))
+            .collect();
+        let mut direct_terms: Vec<Vec<usize>> = vec![vec![]; symbols.len()];
+        for annot in annotations {
+            if let Some(term) = go.index_of(&annot.go_id) {
+                let gene = gene_ids[&annot.gene_product_symbol];
+                direct_terms[gene].push(term);
+                gene_ids
+                    .entry(annot.gene_product_id.value.clone())
+                    .or_insert(gene);
+            }
+        }
+        for terms in direct_terms.iter_mut() {
+            terms.sort();
+            terms.dedup();
+        }
+        let mut ancestors: HashMap<usize, HashSet<usize>> = HashMap::new();
+        let propagated_terms = direct_terms
+            .iter()
+            .map(|terms| {
+                let mut propagated: HashSet<usize> = terms.iter().copied().collect();
+                for term in terms {
+                    propagated.extend(
+                        ancestors
+                            .entry(*term)
+                            .or_insert_with(|| go.ancestor_indices(*term))
+                            .iter()
+                            .copied(),
+                    );
+                }
+                let mut propagated: Vec<usize> = propagated.into_iter().collect();
+                propagated.sort();
+                propagated
+            })
+            .collect();
+        let mut index = AnnotationIndex {
+            symbols,
+            gene_ids,
+            direct_terms,
+            propagated_terms,
+            direct_genes: HashMap::new(),
+            term_genes: HashMap::new(),
+        };
+        index.index_terms();
+        index
+    }
+
+    fn index_terms(&mut self) {
+        self.direct_genes.clear();
+        self.term_genes.clear();
+        for (gene, terms) in self.direct_terms.iter().enumerate() {
+            for term in terms {
+                self.direct_genes.entry(*term).or_default().insert(gene);
+            }
+        }
+        for (gene, terms) in self.propagated_terms.iter().enumerate() {
+            for term in terms {
+                self.term_genes.entry(*term).or_default().insert(gene);
+            }
+        }
+    }
+
+    /// Remove the terms of negated (NOT) annotations from the annotated genes.
+    ///
+    /// A gene that is explicitly not annotated to a term is not annotated to any of its descendants
+    /// either, so the negated term and its descendants are removed, even if there is a conflicting
+    /// annotation without NOT. The gene is thus no longer counted in the population of these terms.
+    pub fn exclude_negated(&mut self, go: &GeneOntology, negated: &[GoAnnot]) {
+        for annot in negated {
+            let Some(idx) = go.index_of(&annot.go_id) else {
+                continue;
+            };
+            let gene = self
+                .resolve(&annot.gene_product_symbol)
+                .or_else(|| self.resolve(&annot.gene_product_id.value));
+            if let Some(gene) = gene {
+                let mut excluded = go.descendant_indices(idx);
+                excluded.insert(idx);
+                self.direct_terms[gene].retain(|t| !excluded.contains(t));
+                self.propagated_terms[gene].retain(|t| !excluded.contains(t));
+            }
+        }
+        self.index_terms();
+    }
+
+    /// The index of a gene given by symbol or gene product id
+    pub fn resolve(&self, gene: &str) -> Option<usize> {
+        self.gene_ids.get(gene).copied()
+    }
+
+    pub fn symbol(&self, gene: usize) -> &str {
+        &self.symbols[gene]
+    }
+
+    pub fn gene_count(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// All annotated genes
+    pub fn all_genes(&self) -> GeneSet {
+        (0..self.gene_count()).collect()
+    }
+
+    /// The terms a gene is directly annotated to, sorted
+    pub fn direct_terms(&self, gene: usize) -> &[usize] {
+        &self.direct_terms[gene]
+    }
+
+    /// The terms a gene is annotated to, including the ancestors of its direct terms, sorted
+    pub fn terms(&self, gene: usize) -> &[usize] {
+        &self.propagated_terms[gene]
+    }
+
+    /// The genes directly annotated to a term
+    pub fn direct_genes(&self, term: usize) -> Option<&GeneSet> {
+        self.direct_genes.get(&term)
+    }
+
+    /// The genes annotated to a term or one of its descendants
+    pub fn genes(&self, term: usize) -> Option<&GeneSet> {
+        self.term_genes.get(&term)
+    }
+
+    /// The genes of every term that is annotated to at least one gene
+    pub fn term_genes(&self) -> &HashMap<usize, GeneSet> {
+        &self.term_genes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+
+    use super::*;
+    use crate::gontology::TermRelation;
+    use crate::test_fixtures::{annot, ontology, tid, toy_annotations, toy_ontology};
+
+    #[test]
+    fn test_gene_set() {
+        let mut a: GeneSet = [1, 64, 130].into_iter().collect();
+        let b: GeneSet = [1, 2, 130].into_iter().collect();
+        assert_eq!(3, a.len());
+        assert!(a.contains(64) && !a.contains(63) && !a.contains(1000));
+        assert_eq!(2, a.intersection_len(&b));
+        assert_eq!(vec![1, 130], a.intersection(&b).iter().collect::<Vec<_>>());
+        a.union_with(&b);
+        assert_eq!(vec![1, 2, 64, 130], a.iter().collect::<Vec<_>>());
+        a.difference_with(&b);
+        assert_eq!(vec![64], a.iter().collect::<Vec<_>>());
+        a.remove(64);
+        assert!(a.is_empty());
+        assert!(!a.insert(3) || a.contains(3));
+    }
+
+    #[test]
+    fn test_gene_set_eq() {
+        let mut a: GeneSet = [1, 130].into_iter().collect();
+        let b: GeneSet = [1].into_iter().collect();
+        assert_ne!(a, b);
+        a.remove(130);
+        assert_eq!(a, b);
+        a.remove(1);
+        assert_eq!(GeneSet::new(), a);
+        let mut c: GeneSet = [1, 130].into_iter().collect();
+        c.intersect_with(&[1, 140].into_iter().collect());
+        assert_eq!(b, c);
+    }
+
+    /// root <- A <- B (part_of), root <- C; A regulates C
+    #[test]
+    fn test_index() {
+        let go = ontology(
+            &[
+                ("GO:0000001", "root"),
+                ("GO:0000002", "A"),
+                ("GO:0000003", "B"),
+                ("GO:0000004", "C"),
+            ],
+            &[
+                ("GO:0000002", TermRelation::IsA, "GO:0000001"),
+                ("GO:0000003", TermRelation::PartOf, "GO:0000002"),
+                ("GO:0000004", TermRelation::IsA, "GO:0000001"),
+                ("GO:0000002", TermRelation::Regulates, "GO:0000004"),
+            ],
+        );
+        let annotations = vec![
+            annot("g2", "GO:0000003"),
+            annot("g1", "GO:0000003"),
+            annot("g1", "GO:0000004"),
+            annot("g3", "GO:0000002"),
+            annot("g4", "GO:9999999"),
+        ];
+        let index = AnnotationIndex::new(&go, &annotations);
+        let idx = |id: &str| go.index_of(&tid(id)).unwrap();
+        assert_eq!(3, index.gene_count());
+        let g1 = index.resolve("g1").unwrap();
+        assert_eq!(0, g1);
+        assert_eq!(Some(g1), index.resolve("UniProtKB:Pg1"));
+        assert_eq!("g1", index.symbol(g1));
+        assert_eq!(None, index.resolve("g4"));
+        assert_eq!(2, index.direct_terms(g1).len());
+        // regulates does not propagate, so A is only reached via part_of
+        assert_eq!(4, index.terms(g1).len());
+        assert_eq!(1, index.direct_genes(idx("GO:0000002")).unwrap().len());
+        assert_eq!(3, index.genes(idx("GO:0000002")).unwrap().len());
+        assert_eq!(1, index.genes(idx("GO:0000004")).unwrap().len());
+        assert_eq!(3, index.genes(idx("GO:0000001")).unwrap().len());
+    }
+
+    #[test]
+    fn test_propagation() {
+        let go = toy_ontology();
+        let annotations = AnnotationIndex::new(&go, &toy_annotations());
+        assert_eq!(10, annotations.gene_count());
+        let terms = annotations.terms(annotations.resolve("g1").unwrap());
+        // B, A (via part_of) and root
+        assert_eq!(3, terms.len());
+        // gene product ids are accepted as aliases
+        let g5 = annotations.resolve("UniProtKB:Pg5").unwrap();
+        assert_eq!("g5", annotations.symbol(g5));
+    }
+}
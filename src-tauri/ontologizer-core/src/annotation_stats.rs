@@ -0,0 +1,269 @@
+//! Descriptive statistics of a set of annotations, shown on the setup page.
+//!
+//! The rows are returned in a fixed order (aspects as F, P, C, evidence codes in the order of
+//! [`EvidenceCode`], everything else sorted by name or year), so the table does not change between
+//! loads of the same file. Distributions are returned as histograms with bins that double in width.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::evidence::EvidenceCode;
+use crate::gaf::GafRecord;
+use crate::goannot::{negation_conflicts, AnnotationStat, Aspect, GoAnnot};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HistogramBin {
+    /// Inclusive bounds of the bin
+    pub lower: usize,
+    pub upper: usize,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Histogram {
+    pub name: String,
+    pub bins: Vec<HistogramBin>,
+}
+
+impl Histogram {
+    /// Bins 1, 2, 3-4, 5-8, 9-16, ... up to the largest value. Values of 0 are not counted.
+    pub fn new(name: &str, values: &[usize]) -> Self {
+        let max = values.iter().copied().max().unwrap_or(0);
+        let mut bins = vec![];
+        let (mut lower, mut upper) = (1, 1);
+        while lower <= max {
+            bins.push(HistogramBin {
+                lower,
+                upper,
+                count: values
+                    .iter()
+                    .filter(|v| (lower..=upper).contains(*v))
+                    .count(),
+            });
+            lower = upper + 1;
+            upper *= 2;
+        }
+        Histogram {
+            name: name.to_string(),
+            bins,
+        }
+    }
+}
+
+/// The table and the histograms of the setup page
+#[derive(Debug, Serialize)]
+pub struct AnnotationSummary {
+    pub stats: Vec<AnnotationStat>,
+    pub histograms: Vec<Histogram>,
+}
+
+/// Median, mean and maximum of a distribution as rows of the table
+fn distribution_stats(name: &str, values: &mut [usize]) -> Vec<AnnotationStat> {
+    if values.is_empty() {
+        return vec![];
+    }
+    values.sort_unstable();
+    let n = values.len();
+    let median = if n % 2 == 1 {
+        values[n / 2] as f64
+    } else {
+        (values[n / 2 - 1] + values[n / 2]) as f64 / 2.0
+    };
+    let mean = values.iter().sum::<usize>() as f64 / n as f64;
+    vec![
+        AnnotationStat::from_string(&format!("{} (median)", name), &format!("{}", median)),
+        AnnotationStat::from_string(&format!("{} (mean)", name), &format!("{:.2}", mean)),
+        AnnotationStat::from_int(&format!("{} (max)", name), values[n - 1]),
+    ]
+}
+
+/// Counts of the annotations used for the analysis, and of the records they come from for the
+/// columns that the compact annotations do not keep (assigned by, date)
+pub fn annotation_descriptive_stats(
+    records: &[GafRecord],
+    go_annots: &[GoAnnot],
+) -> AnnotationSummary {
+    let mut annots = Vec::new();
+    annots.push(AnnotationStat::from_int(
+        "Total annotations",
+        go_annots.len(),
+    ));
+    let unique_symbols: HashSet<_> = go_annots
+        .iter()
+        .map(|annot| &annot.gene_product_symbol)
+        .collect();
+    annots.push(AnnotationStat::from_int("genes", unique_symbols.len()));
+    for aspect in [Aspect::F, Aspect::P, Aspect::C] {
+        let aspect_annots: Vec<&GoAnnot> =
+            go_annots.iter().filter(|a| a.aspect == aspect).collect();
+        let genes: HashSet<_> = aspect_annots
+            .iter()
+            .map(|a| &a.gene_product_symbol)
+            .collect();
+        annots.push(AnnotationStat::from_int(
+            &format!("Annotations ({})", aspect),
+            aspect_annots.len(),
+        ));
+        annots.push(AnnotationStat::from_int(
+            &format!("Genes ({})", aspect),
+            genes.len(),
+        ));
+    }
+    // Count relation types, sorted by name
+    let mut relation_counts = BTreeMap::new();
+    let mut evidence_counts: BTreeMap<EvidenceCode, usize> = BTreeMap::new();
+    for annot in go_annots {
+        *relation_counts
+            .entry(annot.relation.to_string())
+            .or_insert(0) += 1;
+        *evidence_counts.entry(annot.evidence_code).or_default() += 1;
+    }
+    for (relation, count) in &relation_counts {
+        annots.push(AnnotationStat::from_int(relation, *count));
+    }
+    for (code, count) in &evidence_counts {
+        annots.push(AnnotationStat::from_int(
+            &format!("Evidence: {}", code),
+            *count,
+        ));
+    }
+    let mut assigned_by_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut year_counts: BTreeMap<u16, usize> = BTreeMap::new();
+    for record in records.iter().filter(|r| !r.qualifier.negated) {
+        *assigned_by_counts.entry(&record.assigned_by).or_default() += 1;
+        *year_counts.entry(record.date.year).or_default() += 1;
+    }
+    for (source, count) in &assigned_by_counts {
+        annots.push(AnnotationStat::from_int(
+            &format!("Assigned by: {}", source),
+            *count,
+        ));
+    }
+    for (year, count) in &year_counts {
+        annots.push(AnnotationStat::from_int(&format!("Year: {}", year), *count));
+    }
+
+    let mut annotations_per_gene: HashMap<&str, usize> = HashMap::new();
+    let mut genes_per_term: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for annot in go_annots {
+        *annotations_per_gene
+            .entry(&annot.gene_product_symbol)
+            .or_default() += 1;
+        genes_per_term
+            .entry(&annot.go_id.value)
+            .or_default()
+            .insert(&annot.gene_product_symbol);
+    }
+    let mut annotations_per_gene: Vec<usize> = annotations_per_gene.into_values().collect();
+    let mut genes_per_term: Vec<usize> = genes_per_term.values().map(HashSet::len).collect();
+    let histograms = vec![
+        Histogram::new("Annotations per gene", &annotations_per_gene),
+        Histogram::new("Genes per term", &genes_per_term),
+    ];
+    annots.extend(distribution_stats(
+        "Annotations per gene",
+        &mut annotations_per_gene,
+    ));
+    annots.extend(distribution_stats("Genes per term", &mut genes_per_term));
+    AnnotationSummary {
+        stats: annots,
+        histograms,
+    }
+}
+
+/// The statistics of an annotation set that are shown on the setup page
+pub fn annotation_set_stats(
+    records: &[GafRecord],
+    annotations: &[GoAnnot],
+    negated: &[GoAnnot],
+) -> AnnotationSummary {
+    let mut summary = annotation_descriptive_stats(records, annotations);
+    let negation_stats = [
+        AnnotationStat::from_int("Negated annotations", negated.len()),
+        AnnotationStat::from_int(
+            "Conflicting negated annotations",
+            negation_conflicts(annotations, negated).len(),
+        ),
+    ];
+    summary.stats.splice(0..0, negation_stats);
+    summary
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::diagnostics::ParseDiagnostics;
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::new("test", &[1, 1, 2, 3, 4, 5, 9]);
+        let bins: Vec<(usize, usize, usize)> = histogram
+            .bins
+            .iter()
+            .map(|b| (b.lower, b.upper, b.count))
+            .collect();
+        assert_eq!(
+            vec![(1, 1, 2), (2, 2, 1), (3, 4, 2), (5, 8, 1), (9, 16, 1)],
+            bins
+        );
+        assert!(Histogram::new("empty", &[]).bins.is_empty());
+    }
+
+    #[test]
+    fn test_stable_order() {
+        let lines = [
+            "UniProtKB\tP1\tA\tinvolved_in\tGO:0006915\tPMID:1\tIMP\t\tP\t\t\tprotein\ttaxon:9606\t20201231\tMGI\t\t",
+            "UniProtKB\tP1\tA\tenables\tGO:0003700\tPMID:2\tIEA\t\tF\t\t\tprotein\ttaxon:9606\t20240114\tUniProt\t\t",
+            "UniProtKB\tP2\tB\tinvolved_in\tGO:0006915\tPMID:3\tIDA\t\tP\t\t\tprotein\ttaxon:9606\t20150301\tMGI\t\t",
+            "UniProtKB\tP2\tB\tNOT|enables\tGO:0003700\tPMID:4\tIDA\t\tF\t\t\tprotein\ttaxon:9606\t20150301\tMGI\t\t",
+        ];
+        let records: Vec<GafRecord> = lines
+            .iter()
+            .map(|l| GafRecord::from_str(l).unwrap())
+            .collect();
+        let (annotations, negated) =
+            crate::goannot::compact_annotations(&records, &mut ParseDiagnostics::new());
+        let summary = annotation_set_stats(&records, &annotations, &negated);
+        let rows: Vec<String> = summary
+            .stats
+            .iter()
+            .map(|s| format!("{}={}", s.key, s.value))
+            .collect();
+        assert_eq!(
+            vec![
+                "Negated annotations=1",
+                "Conflicting negated annotations=0",
+                "Total annotations=3",
+                "genes=2",
+                "Annotations (F)=1",
+                "Genes (F)=1",
+                "Annotations (P)=2",
+                "Genes (P)=2",
+                "Annotations (C)=0",
+                "Genes (C)=0",
+                "enables=1",
+                "involved_in=2",
+                "Evidence: IDA=1",
+                "Evidence: IMP=1",
+                "Evidence: IEA=1",
+                "Assigned by: MGI=2",
+                "Assigned by: UniProt=1",
+                "Year: 2015=1",
+                "Year: 2020=1",
+                "Year: 2024=1",
+                "Annotations per gene (median)=1.5",
+                "Annotations per gene (mean)=1.50",
+                "Annotations per gene (max)=2",
+                "Genes per term (median)=1.5",
+                "Genes per term (mean)=1.50",
+                "Genes per term (max)=2",
+            ],
+            rows
+        );
+        assert_eq!("Genes per term", summary.histograms[1].name);
+        assert_eq!(1, summary.histograms[1].bins[1].count);
+    }
+}
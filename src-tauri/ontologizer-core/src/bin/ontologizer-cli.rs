@@ -0,0 +1,557 @@
+//! Command-line version of the Ontologizer for batch jobs on machines without a display.
+//!
+//! ```text
+//! ontologizer-cli --go go.json --annotations goa_human.gaf.gz --study study.txt \
+//!     [--gpi mgi.gpi] [--population population.txt] [--method elim] [--mtc benjamini-hochberg] \
+//!     [--resamplings 1000] [--seed 42] [--format tsv] [--output results.tsv]
+//! ```
+//!
+//! The method `mgsa` runs Model-based Gene Set Analysis with the default parameters of the desktop
+//! application instead of a test of each term, and cannot be combined with `--mtc`.
+//!
+//! Annotations are read from GAF files, or from GPAD files (ending in `.gpad` or `.gpad.gz`) together
+//! with the optional GPI file `--gpi`. The analysis uses the same code as the desktop application.
+//! The result is written in the format of the export of the desktop application (see
+//! [`ontologizer_core::export`]) to `--output` or to standard output, and a summary of the input to
+//! standard error.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::process::ExitCode;
+
+use ontologizer_core::annotation_index::AnnotationIndex;
+use ontologizer_core::diagnostics::ParseDiagnostics;
+use ontologizer_core::enrichment::{analyze, parse_gene_list, CalculationMethod};
+use ontologizer_core::export::{save_report, write_report, ExportFormat, Report};
+use ontologizer_core::goannot::AnnotationStat;
+use ontologizer_core::goannot::{annotation_set, open_annotation_file, read_gaf};
+use ontologizer_core::gontology::{load_go, GeneOntology};
+use ontologizer_core::gpad::{join, parse_gpad, parse_gpi};
+use ontologizer_core::jobs::Job;
+use ontologizer_core::mgsa::{mgsa, MgsaConfig};
+use ontologizer_core::mtc::MultipleTestingCorrection;
+use ontologizer_core::resampling::ResamplingConfig;
+use ontologizer_core::session::{file_name, AnalysisOutput, AnnotationSet, InputFile};
+
+const USAGE: &str =
+    "Usage: ontologizer-cli --go <go.json|go.obo> --annotations <file.gaf[.gz]|file.gpad[.gz]>
+    --study <genes.txt>
+    [--gpi <file.gpi[.gz]>]      gene product information of a GPAD file
+    [--population <genes.txt>]   all annotated genes if omitted
+    [--method <method>]          term-for-term (default), parent-child-union,
+                                 parent-child-intersection, elim, weight or mgsa
+    [--mtc <correction>]         none (default), bonferroni, holm, benjamini-hochberg,
+                                 benjamini-yekutieli, storey-q-value,
+                                 westfall-young-single-step or westfall-young-step-down
+    [--resamplings <n>]          resamplings of the Westfall-Young corrections (1000)
+    [--seed <n>]                 seed of the Westfall-Young corrections and MGSA (42)
+    [--format <format>]          tsv (default), json or html
+    [--output <results.tsv>]     standard output if omitted";
+
+const OPTIONS: [&str; 11] = [
+    "go",
+    "annotations",
+    "gpi",
+    "study",
+    "population",
+    "method",
+    "mtc",
+    "resamplings",
+    "seed",
+    "format",
+    "output",
+];
+
+/// A test of each term, or MGSA, which models all terms at once
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Method {
+    Enrichment(CalculationMethod),
+    Mgsa,
+}
+
+#[derive(Debug)]
+struct Options {
+    go: String,
+    annotations: String,
+    gpi: Option<String>,
+    study: String,
+    population: Option<String>,
+    method: Method,
+    correction: MultipleTestingCorrection,
+    mgsa: MgsaConfig,
+    format: ExportFormat,
+    output: Option<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut values: HashMap<&str, String> = HashMap::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let name = arg
+            .strip_prefix("--")
+            .and_then(|name| OPTIONS.iter().find(|o| **o == name))
+            .ok_or_else(|| format!("Unknown argument: {}", arg))?;
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value of --{}", name))?;
+        values.insert(name, value);
+    }
+    let required = |name: &str| {
+        values
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("--{} is required", name))
+    };
+    let number = |name: &str, default: u64| match values.get(name) {
+        Some(value) => value
+            .parse::<u64>()
+            .map_err(|_| format!("--{} must be a number: {}", name, value)),
+        None => Ok(default),
+    };
+    let defaults = ResamplingConfig::default();
+    let config = ResamplingConfig {
+        resamplings: number("resamplings", defaults.resamplings as u64)? as usize,
+        seed: number("seed", defaults.seed)?,
+    };
+    let method = match values.get("method") {
+        Some(method) if method.eq_ignore_ascii_case("mgsa") => Method::Mgsa,
+        Some(method) => Method::Enrichment(method.parse()?),
+        None => Method::Enrichment(CalculationMethod::TermForTerm),
+    };
+    if method == Method::Mgsa && values.contains_key("mtc") {
+        return Err("--mtc cannot be used with MGSA".to_string());
+    }
+    Ok(Options {
+        go: required("go")?,
+        annotations: required("annotations")?,
+        gpi: values.get("gpi").cloned(),
+        study: required("study")?,
+        population: values.get("population").cloned(),
+        method,
+        correction: match values.get("mtc") {
+            Some(mtc) => MultipleTestingCorrection::from_name(mtc, config)?,
+            None => MultipleTestingCorrection::None,
+        },
+        mgsa: MgsaConfig {
+            seed: config.seed,
+            ..MgsaConfig::default()
+        },
+        format: match values.get("format") {
+            Some(format) => format.parse()?,
+            None => ExportFormat::Tsv,
+        },
+        output: values.get("output").cloned(),
+    })
+}
+
+/// Read the gene list `path` and add the description of the file to `inputs`
+fn read_gene_list(path: &str, inputs: &mut Vec<InputFile>) -> Result<Vec<String>, String> {
+    let content = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    inputs.push(InputFile::from_bytes(path, &content));
+    Ok(parse_gene_list(&String::from_utf8_lossy(&content)))
+}
+
+fn is_gpad(path: &str) -> bool {
+    path.ends_with(".gpad") || path.ends_with(".gpad.gz")
+}
+
+/// Read the annotations of a GAF file, or of a GPAD file joined with the GPI file, if there is one
+fn read_annotations(
+    options: &Options,
+    go: &GeneOntology,
+    diagnostics: &mut ParseDiagnostics,
+    job: &Job,
+) -> Result<AnnotationSet, String> {
+    if !is_gpad(&options.annotations) {
+        return read_gaf(&options.annotations, diagnostics, job)
+            .map(|(annotations, _)| annotations);
+    }
+    let reader = open_annotation_file(&options.annotations, job)?;
+    let (gpad, _) = parse_gpad(reader, diagnostics, job)?;
+    let gpi = match &options.gpi {
+        Some(path) => parse_gpi(open_annotation_file(path, job)?, diagnostics, job)?,
+        None => vec![],
+    };
+    let records = join(&gpad, &gpi, go, None, diagnostics);
+    Ok(annotation_set(records, diagnostics))
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    // the job computes the CRC-32 of the ontology and annotation files while they are parsed
+    let job = Job::default();
+    let go = load_go(&options.go, &job).map_err(|e| e.to_string())?;
+    let mut diagnostics = ParseDiagnostics::new();
+    let annotations = read_annotations(options, &go, &mut diagnostics, &job)?.annotations;
+    let mut inputs = job.input_files()?;
+    eprintln!(
+        "{} terms, {} annotations ({} of {} lines skipped)",
+        go.term_count(),
+        annotations.len(),
+        diagnostics.errors,
+        diagnostics.lines
+    );
+    let index = AnnotationIndex::new(&go, &annotations);
+    let study = read_gene_list(&options.study, &mut inputs)?;
+    let population = match &options.population {
+        Some(path) => read_gene_list(path, &mut inputs)?,
+        None => vec![],
+    };
+    let mut parameters = vec![
+        AnnotationStat::from_string("Ontology", &file_name(&options.go)),
+        AnnotationStat::from_string("Annotations", &file_name(&options.annotations)),
+        AnnotationStat::from_string("Study set", &file_name(&options.study)),
+    ];
+    let population_name = match &options.population {
+        Some(path) => file_name(path),
+        None => "all annotated genes".to_string(),
+    };
+    parameters.push(AnnotationStat::from_string("Population", &population_name));
+    let (title, results) = match options.method {
+        Method::Enrichment(method) => {
+            let result = analyze(&go, &index, &study, &population, method, options.correction)?;
+            eprintln!(
+                "{} study genes ({} without annotations), {} population genes, {} terms tested",
+                study.len(),
+                result.unannotated_study_genes.len(),
+                result.population_total,
+                result.results.len()
+            );
+            let title = format!("{:?} ({} genes)", method, study.len());
+            (title, AnalysisOutput::Enrichment(vec![result]))
+        }
+        Method::Mgsa => {
+            let result = mgsa(
+                &go,
+                &index,
+                &study,
+                &population,
+                &options.mgsa,
+                &|_, _, _| Ok(()),
+            )?;
+            eprintln!(
+                "{} study genes, {} population genes, {} terms in the model",
+                result.study_total,
+                result.population_total,
+                result.results.len()
+            );
+            parameters.push(AnnotationStat::from_int("Seed", options.mgsa.seed));
+            let title = format!("MGSA ({} genes)", study.len());
+            (title, AnalysisOutput::Mgsa(result))
+        }
+    };
+    let report = Report {
+        title,
+        parameters,
+        inputs,
+        results: &results,
+    };
+    match &options.output {
+        Some(path) => save_report(path, options.format, &report).map(|_| ()),
+        None => write_report(&mut io::stdout().lock(), options.format, &report),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let result = parse_args(args).and_then(|options| run(&options));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+    use std::path::PathBuf;
+
+    use ontologizer_core::export::{ENRICHMENT_COLUMNS, MGSA_COLUMNS};
+
+    use super::*;
+
+    const GO_OBO: &str = "format-version: 1.2
+data-version: releases/2024-01-17
+
+[Term]
+id: GO:0008150
+name: biological_process
+namespace: biological_process
+
+[Term]
+id: GO:0009987
+name: cellular process
+namespace: biological_process
+is_a: GO:0008150 ! biological_process
+
+[Term]
+id: GO:0007049
+name: cell cycle
+namespace: biological_process
+is_a: GO:0009987 ! cellular process
+";
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn gaf_line(symbol: &str, term: &str) -> String {
+        format!(
+            "UniProtKB\t{}_ID\t{}\tinvolved_in\t{}\tPMID:1\tIDA\t\tP\t\t\tprotein\ttaxon:9606\t20240114\tUniProt\t\t\n",
+            symbol, symbol, term
+        )
+    }
+
+    /// A directory of its own for the files of a test, removed when it is dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "ontologizer_cli_{}_{}",
+                std::process::id(),
+                test
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.0.join(name).to_string_lossy().to_string()
+        }
+
+        fn write(&self, name: &str, content: &str) -> String {
+            let path = self.path(name);
+            fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(args(&[
+            "--go",
+            "go.json",
+            "--annotations",
+            "a.gaf",
+            "--study",
+            "s.txt",
+        ]))
+        .unwrap();
+        assert_eq!("go.json", options.go);
+        assert_eq!(None, options.population);
+        assert_eq!(
+            Method::Enrichment(CalculationMethod::TermForTerm),
+            options.method
+        );
+        assert_eq!(MultipleTestingCorrection::None, options.correction);
+        assert_eq!(ExportFormat::Tsv, options.format);
+        let options = parse_args(args(&[
+            "--go",
+            "go.obo",
+            "--annotations",
+            "a.gaf",
+            "--study",
+            "s.txt",
+            "--population",
+            "p.txt",
+            "--method",
+            "elim",
+            "--mtc",
+            "bonferroni",
+            "--format",
+            "json",
+            "--output",
+            "out.json",
+        ]))
+        .unwrap();
+        assert_eq!(Some("p.txt".to_string()), options.population);
+        assert_eq!(Method::Enrichment(CalculationMethod::Elim), options.method);
+        assert_eq!(MultipleTestingCorrection::Bonferroni, options.correction);
+        assert_eq!(ExportFormat::Json, options.format);
+        assert_eq!(Some("out.json".to_string()), options.output);
+        let options = parse_args(args(&[
+            "--go",
+            "go.json",
+            "--annotations",
+            "a.gaf",
+            "--study",
+            "s.txt",
+            "--method",
+            "MGSA",
+            "--seed",
+            "7",
+        ]))
+        .unwrap();
+        assert_eq!(Method::Mgsa, options.method);
+        assert_eq!(7, options.mgsa.seed);
+        assert_eq!(
+            "--mtc cannot be used with MGSA",
+            parse_args(args(&["--method", "mgsa", "--mtc", "holm"])).unwrap_err()
+        );
+        assert_eq!(
+            "--study is required",
+            parse_args(args(&["--go", "go.json", "--annotations", "a.gaf"])).unwrap_err()
+        );
+        assert_eq!(
+            "Unknown argument: --studies",
+            parse_args(args(&["--studies", "s.txt"])).unwrap_err()
+        );
+        assert_eq!(
+            "Missing value of --go",
+            parse_args(args(&["--go"])).unwrap_err()
+        );
+        assert!(parse_args(args(&[
+            "--go",
+            "go.json",
+            "--annotations",
+            "a.gaf",
+            "--study",
+            "s.txt",
+            "--seed",
+            "x"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = TempDir::new("test_run");
+        let go = dir.write("go.obo", GO_OBO);
+        let mut gaf = "!gaf-version: 2.2\n".to_string();
+        for symbol in ["A", "B", "C"] {
+            gaf.push_str(&gaf_line(symbol, "GO:0007049"));
+        }
+        for symbol in ["D", "E", "F"] {
+            gaf.push_str(&gaf_line(symbol, "GO:0009987"));
+        }
+        let annotations = dir.write("annotations.gaf", &gaf);
+        let study = dir.write("study.txt", "A\nB\nC\n");
+        let output = dir.path("results.tsv");
+        let options = parse_args(args(&[
+            "--go",
+            &go,
+            "--annotations",
+            &annotations,
+            "--study",
+            &study,
+            "--output",
+            &output,
+        ]))
+        .unwrap();
+        run(&options).unwrap();
+        let results = fs::read_to_string(&output).unwrap();
+        let mut lines = results.lines();
+        assert_eq!(Some(ENRICHMENT_COLUMNS.join("\t").as_str()), lines.next());
+        let cell_cycle = lines.find(|line| line.starts_with("GO:0007049")).unwrap();
+        // all three genes annotated to cell cycle are in the study set of three of the six genes
+        assert!(cell_cycle.starts_with("GO:0007049\t6\t3\t3\t3\t"));
+        // the classic layout without the odds ratios
+        assert_eq!(ENRICHMENT_COLUMNS.len(), cell_cycle.split('\t').count());
+        let json = dir.path("results.json");
+        let json_options = Options {
+            format: ExportFormat::Json,
+            output: Some(json.clone()),
+            ..parse_args(args(&[
+                "--go",
+                &go,
+                "--annotations",
+                &annotations,
+                "--study",
+                &study,
+            ]))
+            .unwrap()
+        };
+        run(&json_options).unwrap();
+        let report: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&json).unwrap()).unwrap();
+        // the ontology and the annotations are described while they are parsed, then the study set
+        let inputs = report["inputs"].as_array().unwrap();
+        let expected = [
+            InputFile::from_bytes(&go, GO_OBO.as_bytes()),
+            InputFile::from_bytes(&annotations, gaf.as_bytes()),
+            InputFile::from_bytes(&study, b"A\nB\nC\n"),
+        ];
+        assert_eq!(expected.len(), inputs.len());
+        for (expected, input) in expected.iter().zip(inputs) {
+            assert_eq!(expected.path, input["path"]);
+            assert_eq!(expected.bytes, input["bytes"]);
+            assert_eq!(expected.crc32, input["crc32"]);
+        }
+        let mgsa_options = Options {
+            method: Method::Mgsa,
+            mgsa: MgsaConfig {
+                steps: 20_000,
+                burn_in: 2_000,
+                ..MgsaConfig::default()
+            },
+            ..parse_args(args(&[
+                "--go",
+                &go,
+                "--annotations",
+                &annotations,
+                "--study",
+                &study,
+                "--output",
+                &output,
+            ]))
+            .unwrap()
+        };
+        run(&mgsa_options).unwrap();
+        let results = fs::read_to_string(&output).unwrap();
+        let mut lines = results.lines();
+        assert_eq!(Some(MGSA_COLUMNS.join("\t").as_str()), lines.next());
+        let cell_cycle = lines.find(|line| line.starts_with("GO:0007049")).unwrap();
+        assert!(cell_cycle.starts_with("GO:0007049\t6\t3\t3\t3\t"));
+        let gpad = dir.write(
+            "annotations.gpad",
+            &["A", "B", "C", "D"]
+                .iter()
+                .map(|id| format!("MGI:MGI:{}\t\tRO:0002331\tGO:0007049\tPMID:1\tECO:0000315\t\t\t2020-12-31\tMGI\t\t\n", id))
+                .collect::<String>(),
+        );
+        let gpi = dir.write(
+            "annotations.gpi",
+            "MGI:MGI:A\tA\t\t\tSO:0000704\tNCBITaxon:10090\t\t\t\t\t\n",
+        );
+        let options = parse_args(args(&[
+            "--go",
+            &go,
+            "--annotations",
+            &gpad,
+            "--gpi",
+            &gpi,
+            "--study",
+            &study,
+            "--output",
+            &output,
+        ]))
+        .unwrap();
+        run(&options).unwrap();
+        let results = fs::read_to_string(&output).unwrap();
+        // B and C have no GPI entry, so their symbols are the local ids MGI:B and MGI:C
+        let cell_cycle = results
+            .lines()
+            .find(|line| line.starts_with("GO:0007049"))
+            .unwrap();
+        assert!(cell_cycle.starts_with("GO:0007049\t4\t4\t1\t1\t"));
+        let missing = Options {
+            study: "does_not_exist.txt".to_string(),
+            ..options
+        };
+        assert!(run(&missing).is_err());
+    }
+}
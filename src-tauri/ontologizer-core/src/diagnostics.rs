@@ -0,0 +1,171 @@
+//! Diagnostics for lines of annotation files that could not be parsed.
+//!
+//! Instead of skipping bad lines silently, the parsers count them per category and keep a sample of
+//! the offending lines, which is returned to the frontend together with the annotation statistics.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::goannot::InputError;
+
+/// Number of offending lines that are kept as examples
+pub const MAX_SAMPLES: usize = 20;
+/// Offending lines are truncated to this many characters in the samples
+const MAX_SAMPLE_LENGTH: usize = 300;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum DiagnosticCategory {
+    /// Wrong number of tab-separated fields
+    FieldCount,
+    UnknownRelation,
+    UnknownEvidenceCode,
+    InvalidCurie,
+    InvalidAspect,
+    /// e.g., an invalid date or an empty required field
+    Other,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LineDiagnostic {
+    /// One-based line number, None if the problem was found after parsing (e.g., when joining GPAD and GPI)
+    pub line_number: Option<usize>,
+    pub category: DiagnosticCategory,
+    pub message: String,
+    pub line: String,
+}
+
+/// How to deal with lines that cannot be parsed
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum ParseMode {
+    /// Skip bad lines and report them
+    #[default]
+    Lenient,
+    /// Fail the load if more than this fraction of the annotation lines cannot be parsed
+    Strict { max_error_rate: f64 },
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ParseDiagnostics {
+    /// Number of annotation lines (header lines are not counted)
+    pub lines: usize,
+    pub errors: usize,
+    pub counts: BTreeMap<DiagnosticCategory, usize>,
+    /// The first offending lines
+    pub samples: Vec<LineDiagnostic>,
+}
+
+impl ParseDiagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_error(&mut self, line_number: Option<usize>, line: &str, error: &InputError) {
+        let category = error.category();
+        self.errors += 1;
+        *self.counts.entry(category).or_default() += 1;
+        if self.samples.len() < MAX_SAMPLES {
+            self.samples.push(LineDiagnostic {
+                line_number,
+                category,
+                message: error.to_string(),
+                line: line.chars().take(MAX_SAMPLE_LENGTH).collect(),
+            });
+        }
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        if self.lines == 0 {
+            return 0.0;
+        }
+        self.errors as f64 / self.lines as f64
+    }
+
+    /// An error describing the problems if the load should fail in the given mode
+    pub fn check(&self, mode: ParseMode) -> Result<(), String> {
+        match mode {
+            ParseMode::Strict { max_error_rate } if self.error_rate() > max_error_rate => {
+                let counts: Vec<String> = self
+                    .counts
+                    .iter()
+                    .map(|(category, count)| format!("{:?}: {}", category, count))
+                    .collect();
+                let first = self
+                    .samples
+                    .first()
+                    .map(|s| format!(" First error: {}", s.message))
+                    .unwrap_or_default();
+                Err(format!(
+                    "{} of {} annotation lines ({:.2}%) could not be parsed, more than the allowed {:.2}% ({}).{}",
+                    self.errors,
+                    self.lines,
+                    100.0 * self.error_rate(),
+                    100.0 * max_error_rate,
+                    counts.join(", "),
+                    first
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_diagnostics() {
+        let mut diagnostics = ParseDiagnostics::new();
+        diagnostics.lines = 100;
+        for i in 0..25 {
+            diagnostics.add_error(
+                Some(i + 1),
+                "bad\tline",
+                &InputError::MalformedLine("2 fields".to_string()),
+            );
+        }
+        diagnostics.add_error(
+            Some(30),
+            "line",
+            &InputError::UnknownEvidenceCode("XYZ".to_string()),
+        );
+        assert_eq!(26, diagnostics.errors);
+        assert_eq!(25, diagnostics.counts[&DiagnosticCategory::FieldCount]);
+        assert_eq!(
+            1,
+            diagnostics.counts[&DiagnosticCategory::UnknownEvidenceCode]
+        );
+        assert_eq!(MAX_SAMPLES, diagnostics.samples.len());
+        assert_eq!(Some(1), diagnostics.samples[0].line_number);
+        assert!(diagnostics.check(ParseMode::Lenient).is_ok());
+        assert!(diagnostics
+            .check(ParseMode::Strict {
+                max_error_rate: 0.3
+            })
+            .is_ok());
+        let err = diagnostics
+            .check(ParseMode::Strict {
+                max_error_rate: 0.1,
+            })
+            .unwrap_err();
+        assert!(err.starts_with("26 of 100 annotation lines (26.00%) could not be parsed"));
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        let mode: ParseMode =
+            serde_json::from_str(r#"{"Strict": {"max_error_rate": 0.05}}"#).unwrap();
+        assert_eq!(
+            ParseMode::Strict {
+                max_error_rate: 0.05
+            },
+            mode
+        );
+        assert_eq!(
+            ParseMode::Lenient,
+            serde_json::from_str("\"Lenient\"").unwrap()
+        );
+    }
+}
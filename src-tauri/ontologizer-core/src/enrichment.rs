@@ -0,0 +1,630 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::annotation_index::{AnnotationIndex, GeneSet};
+use crate::exact_fisher::{ContingencyTable, FisherExact, Hypergeometric};
+use crate::gontology::{GeneOntology, Namespace};
+use crate::jobs::{Progress, ProgressStage};
+use crate::mtc::MultipleTestingCorrection;
+use crate::resampling;
+use crate::topgo;
+use rayon::prelude::*;
+
+/// Parse a gene list as used by the Ontologizer (one gene per line).
+///
+/// Only the first token of each line is used so that files with additional columns can be read
+/// directly. Empty lines and lines starting with `#` are skipped, duplicates are removed.
+pub fn parse_gene_list(content: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|gene| seen.insert(gene.to_string()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The calculation methods that can be selected for an analysis
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum CalculationMethod {
+    TermForTerm,
+    ParentChildUnion,
+    ParentChildIntersection,
+    Elim,
+    Weight,
+}
+
+impl CalculationMethod {
+    pub const ALL: [CalculationMethod; 5] = [
+        CalculationMethod::TermForTerm,
+        CalculationMethod::ParentChildUnion,
+        CalculationMethod::ParentChildIntersection,
+        CalculationMethod::Elim,
+        CalculationMethod::Weight,
+    ];
+}
+
+impl FromStr for CalculationMethod {
+    type Err = String;
+
+    /// The name of the variant, ignoring case, dashes and underscores (e.g., `term-for-term`)
+    fn from_str(s: &str) -> Result<Self, String> {
+        let name = s.replace(['-', '_'], "").to_lowercase();
+        CalculationMethod::ALL
+            .into_iter()
+            .find(|m| format!("{:?}", m).to_lowercase() == name)
+            .ok_or_else(|| format!("Unknown calculation method: {}", s))
+    }
+}
+
+/// One row of the result table, corresponding to one GO term
+#[derive(Clone, Debug, Serialize)]
+pub struct EnrichmentResult {
+    pub term_id: String,
+    pub label: String,
+    pub namespace: Option<Namespace>,
+    pub population_total: usize,
+    pub population_count: usize,
+    pub study_total: usize,
+    pub study_count: usize,
+    /// p-value of the method, which tests for enrichment
+    pub p_value: f64,
+    /// p-value adjusted for multiple testing with the correction of the analysis
+    pub adjusted_p_value: f64,
+    /// P(X <= study_count) of Fisher's exact test with the counts of the method, small values indicate
+    /// depletion
+    pub lower_p_value: f64,
+    /// Two-sided p-value of Fisher's exact test with the counts of the method
+    pub two_sided_p_value: f64,
+    /// Conditional maximum likelihood estimate of the odds ratio of the term in the study set
+    pub odds_ratio: f64,
+    /// 95% confidence interval of the odds ratio (see [`FisherExact`])
+    pub confidence_interval: (f64, f64),
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnalysisResult {
+    pub method: CalculationMethod,
+    pub correction: MultipleTestingCorrection,
+    pub population_total: usize,
+    pub study_total: usize,
+    /// Study genes for which we did not find any annotation
+    pub unannotated_study_genes: Vec<String>,
+    pub results: Vec<EnrichmentResult>,
+}
+
+/// Study and population restricted to annotated genes, with the genes annotated to each term.
+///
+/// The population side is shared, so that random study sets can be drawn cheaply (see [`TermGenes::with_study`]).
+pub struct TermGenes {
+    pub study: GeneSet,
+    pub population: Arc<GeneSet>,
+    pub study_genes: HashMap<usize, GeneSet>,
+    pub population_genes: Arc<HashMap<usize, GeneSet>>,
+    pub unannotated_study_genes: Vec<String>,
+}
+
+impl TermGenes {
+    /// The study genes are always added to the population. If the population is empty,
+    /// all annotated genes are used.
+    pub fn new(annotations: &AnnotationIndex, study: &[String], population: &[String]) -> Self {
+        let mut unannotated_study_genes = vec![];
+        let mut study_set = GeneSet::new();
+        for gene in study {
+            match annotations.resolve(gene) {
+                Some(g) => {
+                    study_set.insert(g);
+                }
+                None => unannotated_study_genes.push(gene.clone()),
+            }
+        }
+        let (population_set, population_genes) = if population.is_empty() {
+            (annotations.all_genes(), annotations.term_genes().clone())
+        } else {
+            let mut population_set: GeneSet = population
+                .iter()
+                .filter_map(|g| annotations.resolve(g))
+                .collect();
+            population_set.union_with(&study_set);
+            let population_genes = Self::genes_per_term(annotations, &population_set);
+            (population_set, population_genes)
+        };
+        TermGenes {
+            study_genes: Self::genes_per_term(annotations, &study_set),
+            population_genes: Arc::new(population_genes),
+            study: study_set,
+            population: Arc::new(population_set),
+            unannotated_study_genes,
+        }
+    }
+
+    /// The same population with another study set, which must be a subset of the population
+    pub fn with_study(&self, annotations: &AnnotationIndex, study: GeneSet) -> Self {
+        TermGenes {
+            study_genes: Self::genes_per_term(annotations, &study),
+            population_genes: Arc::clone(&self.population_genes),
+            study,
+            population: Arc::clone(&self.population),
+            unannotated_study_genes: vec![],
+        }
+    }
+
+    /// The genes of `genes` annotated to each term, for the terms annotated to any of them
+    fn genes_per_term(annotations: &AnnotationIndex, genes: &GeneSet) -> HashMap<usize, GeneSet> {
+        let mut terms = HashSet::new();
+        for gene in genes.iter() {
+            terms.extend(annotations.terms(gene).iter().copied());
+        }
+        terms
+            .into_iter()
+            .filter_map(|t| annotations.genes(t).map(|g| (t, g.intersection(genes))))
+            .collect()
+    }
+
+    /// Genes annotated to any (union) or to all (intersection) of the parents of `term`.
+    ///
+    /// Returns None for the root, which has no parents.
+    fn parent_genes(
+        go: &GeneOntology,
+        term: usize,
+        term_genes: &HashMap<usize, GeneSet>,
+        intersection: bool,
+    ) -> Option<GeneSet> {
+        let empty = GeneSet::new();
+        let mut parents = go
+            .parent_indices(term)
+            .map(|p| term_genes.get(&p).unwrap_or(&empty));
+        let first = parents.next()?.clone();
+        Some(parents.fold(first, |mut acc, genes| {
+            if intersection {
+                acc.intersect_with(genes);
+            } else {
+                acc.union_with(genes);
+            }
+            acc
+        }))
+    }
+}
+
+/// The counts and the p-value that one of the calculation methods computed for a term
+pub struct TermScore {
+    pub term: usize,
+    pub population_total: usize,
+    pub population_count: usize,
+    pub study_total: usize,
+    pub study_count: usize,
+    pub p_value: f64,
+}
+
+/// Term-for-term and parent-child scores, where each term is tested independently of the others.
+///
+/// For term-for-term, each GO term annotated to a population gene is tested with the one-sided
+/// hypergeometric test P(X >= study_count). The parent-child methods (Grossmann et al., 2007) instead draw the study
+/// genes from the genes annotated to the parents of the term (any parent for union, all parents for
+/// intersection), so that a term is only reported if it is enriched beyond what is expected given
+/// its parents. The root has no parents and is reported with p = 1.
+fn independent_scores(
+    go: &GeneOntology,
+    genes: &TermGenes,
+    method: CalculationMethod,
+    hgeom: &Hypergeometric,
+) -> Result<Vec<TermScore>, String> {
+    let mut scores = vec![];
+    for (&term, population_genes) in genes.population_genes.iter() {
+        let study_count = genes.study_genes.get(&term).map_or(0, |g| g.len());
+        let population_count = population_genes.len();
+        let (population_total, study_total) = match method {
+            CalculationMethod::ParentChildUnion | CalculationMethod::ParentChildIntersection => {
+                let intersection = method == CalculationMethod::ParentChildIntersection;
+                match TermGenes::parent_genes(go, term, &genes.population_genes, intersection) {
+                    Some(population_parents) => {
+                        let study_parents =
+                            TermGenes::parent_genes(go, term, &genes.study_genes, intersection)
+                                .unwrap_or_default();
+                        (population_parents.len(), study_parents.len())
+                    }
+                    None => (population_count, study_count),
+                }
+            }
+            _ => (genes.population.len(), genes.study.len()),
+        };
+        let p_value = upper_tail(
+            hgeom,
+            study_count,
+            population_total,
+            population_count,
+            study_total,
+        )?;
+        scores.push(TermScore {
+            term,
+            population_total,
+            population_count,
+            study_total,
+            study_count,
+            p_value,
+        });
+    }
+    Ok(scores)
+}
+
+/// P(X >= study_count); 1 if no study gene is (left) in the term
+pub(crate) fn upper_tail(
+    hgeom: &Hypergeometric,
+    study_count: usize,
+    population_total: usize,
+    population_count: usize,
+    study_total: usize,
+) -> Result<f64, String> {
+    if study_count == 0 {
+        return Ok(1.0);
+    }
+    // P(X >= k) = P(X > k-1)
+    hgeom.phyper(
+        study_count - 1,
+        population_total,
+        population_count,
+        study_total,
+        false,
+    )
+}
+
+/// Score all terms annotated to at least one population gene with the chosen method. Terms without
+/// study genes are scored as well, so that their depletion can be reported.
+pub fn term_scores(
+    go: &GeneOntology,
+    genes: &TermGenes,
+    method: CalculationMethod,
+    hgeom: &Hypergeometric,
+) -> Result<Vec<TermScore>, String> {
+    match method {
+        CalculationMethod::Elim => topgo::elim(go, genes, hgeom, topgo::ELIM_CUTOFF),
+        CalculationMethod::Weight => topgo::weight(go, genes, hgeom),
+        _ => independent_scores(go, genes, method, hgeom),
+    }
+}
+
+fn analyze_term_genes(
+    go: &GeneOntology,
+    annotations: &AnnotationIndex,
+    genes: &TermGenes,
+    method: CalculationMethod,
+    correction: MultipleTestingCorrection,
+    progress: Progress,
+) -> Result<AnalysisResult, String> {
+    let hgeom = Hypergeometric::new(genes.population.len());
+    let scores = term_scores(go, genes, method, &hgeom)?;
+    progress(ProgressStage::TermsTested, scores.len(), scores.len())?;
+    let p_values: Vec<f64> = scores.iter().map(|s| s.p_value).collect();
+    let adjusted = match correction.adjust(&p_values) {
+        Some(adjusted) => adjusted,
+        // resampling-based corrections need to rerun the method on random study sets
+        None => resampling::westfall_young(
+            go,
+            annotations,
+            genes,
+            method,
+            &scores,
+            correction,
+            progress,
+        )?,
+    };
+    // Fisher's exact test uses the same counts as the method, e.g., the parents' genes for parent-child
+    let fisher = FisherExact::new(genes.population.len());
+    let mut results = scores
+        .into_par_iter()
+        .zip(adjusted)
+        .map(|(score, adjusted_p_value)| {
+            let go_term = go.term_at(score.term);
+            let table = ContingencyTable::from_term_counts(
+                score.population_total,
+                score.population_count,
+                score.study_total,
+                score.study_count,
+            )?;
+            let fisher_result = fisher.test(&table)?;
+            Ok(EnrichmentResult {
+                term_id: go_term.id.value.clone(),
+                label: go_term.label.clone(),
+                namespace: go_term.namespace,
+                population_total: score.population_total,
+                population_count: score.population_count,
+                study_total: score.study_total,
+                study_count: score.study_count,
+                p_value: score.p_value,
+                adjusted_p_value,
+                lower_p_value: fisher_result.less,
+                two_sided_p_value: fisher_result.two_sided,
+                odds_ratio: fisher_result.odds_ratio,
+                confidence_interval: fisher_result.confidence_interval,
+            })
+        })
+        .collect::<Result<Vec<EnrichmentResult>, String>>()?;
+    results.sort_by(|a, b| {
+        a.p_value
+            .total_cmp(&b.p_value)
+            .then_with(|| a.term_id.cmp(&b.term_id))
+    });
+    Ok(AnalysisResult {
+        method,
+        correction,
+        population_total: genes.population.len(),
+        study_total: genes.study.len(),
+        unannotated_study_genes: genes.unannotated_study_genes.clone(),
+        results,
+    })
+}
+
+/// Perform the enrichment analysis with the chosen method. The results are sorted by p-value.
+///
+/// All terms annotated to at least one population gene are tested, including the terms without study
+/// genes, which can only be depleted.
+pub fn analyze(
+    go: &GeneOntology,
+    annotations: &AnnotationIndex,
+    study: &[String],
+    population: &[String],
+    method: CalculationMethod,
+    correction: MultipleTestingCorrection,
+) -> Result<AnalysisResult, String> {
+    let genes = TermGenes::new(annotations, study, population);
+    analyze_term_genes(go, annotations, &genes, method, correction, &|_, _, _| {
+        Ok(())
+    })
+}
+
+/// Run several calculation methods on the same study and population, e.g. to compare elim with term-for-term.
+///
+/// `progress` is called with the number of completed and total resamplings of a resampling-based correction.
+pub fn analyze_methods(
+    go: &GeneOntology,
+    annotations: &AnnotationIndex,
+    study: &[String],
+    population: &[String],
+    methods: &[CalculationMethod],
+    correction: MultipleTestingCorrection,
+    progress: Progress,
+) -> Result<Vec<AnalysisResult>, String> {
+    let genes = TermGenes::new(annotations, study, population);
+    methods
+        .iter()
+        .map(|method| analyze_term_genes(go, annotations, &genes, *method, correction, progress))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use float_eq::float_eq;
+    use std::assert_eq;
+
+    use super::*;
+    use crate::gontology::TermRelation;
+    use crate::test_fixtures::{annot, annotate, ontology, tid, toy_annotations, toy_ontology};
+
+    /// root <- A <- D, root <- C <- D, i.e., D has two parents.
+    /// g1..g3 are annotated to D, g4..g6 to A, g7 and g8 to C and g9..g12 to the root.
+    fn two_parent_fixture() -> (GeneOntology, AnnotationIndex) {
+        let go = ontology(
+            &[
+                ("GO:0000001", "root"),
+                ("GO:0000002", "A"),
+                ("GO:0000004", "C"),
+                ("GO:0000005", "D"),
+            ],
+            &[
+                ("GO:0000002", TermRelation::IsA, "GO:0000001"),
+                ("GO:0000004", TermRelation::IsA, "GO:0000001"),
+                ("GO:0000005", TermRelation::IsA, "GO:0000002"),
+                ("GO:0000005", TermRelation::IsA, "GO:0000004"),
+            ],
+        );
+        let annots = annotate(&[
+            ("GO:0000005", 3),
+            ("GO:0000002", 3),
+            ("GO:0000004", 2),
+            ("GO:0000001", 4),
+        ]);
+        let annotations = AnnotationIndex::new(&go, &annots);
+        (go, annotations)
+    }
+
+    fn two_parent_study() -> Vec<String> {
+        ["g1", "g2", "g3", "g4", "g7"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_gene_list() {
+        let genes = parse_gene_list("# study set\nTP53\n\nBRCA1\textra column\nTP53\n");
+        assert_eq!(vec!["TP53".to_string(), "BRCA1".to_string()], genes);
+    }
+
+    #[test]
+    fn test_method_from_str() {
+        assert_eq!(Ok(CalculationMethod::TermForTerm), "term-for-term".parse());
+        assert_eq!(Ok(CalculationMethod::Elim), "Elim".parse());
+        assert_eq!(
+            Ok(CalculationMethod::ParentChildIntersection),
+            "parent_child_intersection".parse()
+        );
+        assert!("mgsa".parse::<CalculationMethod>().is_err());
+    }
+
+    #[test]
+    fn test_exclude_negated() {
+        let go = toy_ontology();
+        let mut annotations = AnnotationIndex::new(&go, &toy_annotations());
+        let idx = |id: &str| go.index_of(&tid(id)).unwrap();
+        let gene = |symbol: &str| annotations.resolve(symbol).unwrap();
+        // g1 is explicitly not involved in A, so neither in its descendant B; g5 is matched by its id
+        let mut negated = vec![annot("g1", "GO:0000002"), annot("g5", "GO:0000004")];
+        negated[1].gene_product_symbol = "other symbol".to_string();
+        let (g1, g2, g5) = (gene("g1"), gene("g2"), gene("g5"));
+        annotations.exclude_negated(&go, &negated);
+        assert_eq!(&[idx("GO:0000001")], annotations.terms(g1));
+        assert!(!annotations.terms(g5).contains(&idx("GO:0000004")));
+        assert_eq!(3, annotations.terms(g2).len());
+        let genes = TermGenes::new(&annotations, &[], &[]);
+        assert_eq!(3, genes.population_genes[&idx("GO:0000003")].len());
+    }
+
+    #[test]
+    fn test_term_for_term() {
+        let go = toy_ontology();
+        let annotations = AnnotationIndex::new(&go, &toy_annotations());
+        let study: Vec<String> = ["g1", "g2", "g3", "g5", "unknown"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let result = analyze(
+            &go,
+            &annotations,
+            &study,
+            &[],
+            CalculationMethod::TermForTerm,
+            MultipleTestingCorrection::None,
+        )
+        .unwrap();
+        assert_eq!(10, result.population_total);
+        assert_eq!(4, result.study_total);
+        assert_eq!(vec!["unknown".to_string()], result.unannotated_study_genes);
+        // A and B have the same counts (3 of 4 study genes, 4 of 10 population genes)
+        // phyper(2, 4, 6, 4, lower.tail=FALSE) = 0.1190476 in R
+        let best = &result.results[0];
+        assert_eq!("GO:0000002", best.term_id);
+        assert_eq!(3, best.study_count);
+        assert_eq!(4, best.population_count);
+        assert!(float_eq!(0.1190476, best.p_value, rmax <= 1e-6));
+        // fisher.test(matrix(c(3, 1, 1, 5), 2)) in R
+        assert!(float_eq!(10.29406, best.odds_ratio, rmax <= 1e-3));
+        assert!(float_eq!(
+            0.4016104,
+            best.confidence_interval.0,
+            rmax <= 1e-3
+        ));
+        assert!(float_eq!(
+            939.7386,
+            best.confidence_interval.1,
+            rmax <= 1e-3
+        ));
+        // the root is annotated to every gene
+        let root = result.results.iter().find(|r| r.label == "root").unwrap();
+        assert!(float_eq!(1.0, root.p_value, rmax <= 1e-9));
+    }
+
+    #[test]
+    fn test_depletion() {
+        let go = toy_ontology();
+        let annotations = AnnotationIndex::new(&go, &toy_annotations());
+        let study: Vec<String> = (1..=4).map(|i| format!("g{}", i)).collect();
+        for method in [
+            CalculationMethod::TermForTerm,
+            CalculationMethod::Elim,
+            CalculationMethod::Weight,
+        ] {
+            let result = analyze(
+                &go,
+                &annotations,
+                &study,
+                &[],
+                method,
+                MultipleTestingCorrection::None,
+            )
+            .unwrap();
+            // C has no study genes but is tested
+            assert_eq!(4, result.results.len());
+            let c = result.results.iter().find(|r| r.label == "C").unwrap();
+            assert_eq!(0, c.study_count);
+            assert_eq!(6, c.population_count);
+            assert_eq!(1.0, c.p_value);
+            // none of the 6 genes of C among the 4 study genes: C(4,4) / C(10,4) = 1 / 210
+            assert!(float_eq!(1.0 / 210.0, c.lower_p_value, rmax <= 1e-9));
+            assert!(float_eq!(1.0 / 210.0, c.two_sided_p_value, rmax <= 1e-9));
+            assert_eq!(0.0, c.odds_ratio);
+        }
+    }
+
+    #[test]
+    fn test_parent_child_union() {
+        let (go, annotations) = two_parent_fixture();
+        let study = two_parent_study();
+        let result = analyze(
+            &go,
+            &annotations,
+            &study,
+            &[],
+            CalculationMethod::ParentChildUnion,
+            MultipleTestingCorrection::None,
+        )
+        .unwrap();
+        let d = result.results.iter().find(|r| r.label == "D").unwrap();
+        // genes annotated to A or C: 8 in the population, 5 in the study set
+        assert_eq!(8, d.population_total);
+        assert_eq!(5, d.study_total);
+        // phyper(2, 3, 5, 5, lower.tail=FALSE) = 0.1785714 in R
+        assert!(float_eq!(0.1785714, d.p_value, rmax <= 1e-6));
+        let root = result.results.iter().find(|r| r.label == "root").unwrap();
+        assert!(float_eq!(1.0, root.p_value, rmax <= 1e-9));
+    }
+
+    #[test]
+    fn test_parent_child_intersection() {
+        let (go, annotations) = two_parent_fixture();
+        let study = two_parent_study();
+        let result = analyze(
+            &go,
+            &annotations,
+            &study,
+            &[],
+            CalculationMethod::ParentChildIntersection,
+            MultipleTestingCorrection::None,
+        )
+        .unwrap();
+        let d = result.results.iter().find(|r| r.label == "D").unwrap();
+        // only g1..g3 are annotated to both A and C, so D is not surprising given its parents
+        assert_eq!(3, d.population_total);
+        assert_eq!(3, d.study_total);
+        assert!(float_eq!(1.0, d.p_value, rmax <= 1e-9));
+        // term-for-term finds D enriched: phyper(2, 3, 9, 5, lower.tail=FALSE) = 0.04545455 in R
+        let tft = analyze(
+            &go,
+            &annotations,
+            &study,
+            &[],
+            CalculationMethod::TermForTerm,
+            MultipleTestingCorrection::None,
+        )
+        .unwrap();
+        let d = tft.results.iter().find(|r| r.label == "D").unwrap();
+        assert!(float_eq!(0.04545455, d.p_value, rmax <= 1e-6));
+    }
+
+    #[test]
+    fn test_adjusted_p_values() {
+        let (go, annotations) = two_parent_fixture();
+        let study = two_parent_study();
+        let result = analyze(
+            &go,
+            &annotations,
+            &study,
+            &[],
+            CalculationMethod::TermForTerm,
+            MultipleTestingCorrection::Bonferroni,
+        )
+        .unwrap();
+        assert_eq!(MultipleTestingCorrection::Bonferroni, result.correction);
+        let m = result.results.len() as f64;
+        for row in &result.results {
+            assert!(float_eq!(
+                (row.p_value * m).min(1.0),
+                row.adjusted_p_value,
+                rmax <= 1e-9
+            ));
+        }
+    }
+}
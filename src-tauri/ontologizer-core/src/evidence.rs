@@ -0,0 +1,585 @@
+//! Exact GO evidence codes, their Evidence and Conclusion Ontology (ECO) ids, and filters on evidence.
+//!
+//! Annotations keep the exact code (e.g., IDA), while [`EviCode`] is the coarse group used by GO
+//! (experimental, high throughput, ...). An [`EvidenceFilter`] selects codes by code, by group or by
+//! ECO term; selecting an ECO term includes all codes whose ECO id descends from it, which needs
+//! the ECO ontology (eco.obo) to be loaded.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use crate::goannot::{EviCode, InputError, TermId};
+use crate::gontology::GeneOntology;
+
+/// The evidence codes of the GO annotation guidelines
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum EvidenceCode {
+    EXP,
+    IDA,
+    IPI,
+    IMP,
+    IGI,
+    IEP,
+    HTP,
+    HDA,
+    HMP,
+    HGI,
+    HEP,
+    IBA,
+    IBD,
+    IKR,
+    IRD,
+    ISS,
+    ISO,
+    ISA,
+    ISM,
+    IGC,
+    RCA,
+    TAS,
+    NAS,
+    IC,
+    ND,
+    IEA,
+}
+
+/// Each code with its default ECO id (gaf-eco-mapping.txt)
+const ECO_IDS: [(EvidenceCode, &str); 26] = [
+    (EvidenceCode::EXP, "ECO:0000269"),
+    (EvidenceCode::IDA, "ECO:0000314"),
+    (EvidenceCode::IPI, "ECO:0000353"),
+    (EvidenceCode::IMP, "ECO:0000315"),
+    (EvidenceCode::IGI, "ECO:0000316"),
+    (EvidenceCode::IEP, "ECO:0000270"),
+    (EvidenceCode::HTP, "ECO:0006056"),
+    (EvidenceCode::HDA, "ECO:0007005"),
+    (EvidenceCode::HMP, "ECO:0007001"),
+    (EvidenceCode::HGI, "ECO:0007003"),
+    (EvidenceCode::HEP, "ECO:0007007"),
+    (EvidenceCode::IBA, "ECO:0000318"),
+    (EvidenceCode::IBD, "ECO:0000319"),
+    (EvidenceCode::IKR, "ECO:0000320"),
+    (EvidenceCode::IRD, "ECO:0000321"),
+    (EvidenceCode::ISS, "ECO:0000250"),
+    (EvidenceCode::ISO, "ECO:0000266"),
+    (EvidenceCode::ISA, "ECO:0000247"),
+    (EvidenceCode::ISM, "ECO:0000255"),
+    (EvidenceCode::IGC, "ECO:0000317"),
+    (EvidenceCode::RCA, "ECO:0000245"),
+    (EvidenceCode::TAS, "ECO:0000304"),
+    (EvidenceCode::NAS, "ECO:0000303"),
+    (EvidenceCode::IC, "ECO:0000305"),
+    (EvidenceCode::ND, "ECO:0000307"),
+    (EvidenceCode::IEA, "ECO:0000501"),
+];
+
+/// Other ECO ids that GPAD files use for electronic annotations
+const IEA_ECO_IDS: [&str; 3] = ["ECO:0000256", "ECO:0000363", "ECO:0007669"];
+
+impl EvidenceCode {
+    pub fn all() -> impl Iterator<Item = EvidenceCode> {
+        ECO_IDS.iter().map(|(code, _)| *code)
+    }
+
+    /// The default ECO id, e.g., ECO:0000314 for IDA
+    pub fn eco_id(&self) -> &'static str {
+        ECO_IDS
+            .iter()
+            .find(|(code, _)| code == self)
+            .map(|(_, eco)| *eco)
+            .expect("every evidence code has an ECO id")
+    }
+
+    /// The GO evidence code of a default ECO id, e.g. IDA for ECO:0000314. More specific ECO terms need
+    /// the ECO ontology, see [`EcoResolver`].
+    pub fn from_eco(id: &str) -> Result<Self, InputError> {
+        if IEA_ECO_IDS.contains(&id) {
+            return Ok(EvidenceCode::IEA);
+        }
+        ECO_IDS
+            .iter()
+            .find(|(_, eco)| *eco == id)
+            .map(|(code, _)| *code)
+            .ok_or_else(|| {
+                InputError::UnknownEvidenceCode(format!(
+                    "Did not recognize '{}' as an ECO evidence code. Load the Evidence and Conclusion Ontology to map more specific ECO terms.",
+                    id
+                ))
+            })
+    }
+
+    /// The coarse group of the code, e.g., EXP for IDA
+    pub fn group(&self) -> EviCode {
+        use EvidenceCode::*;
+        match self {
+            EXP | IDA | IPI | IMP | IGI | IEP => EviCode::EXP,
+            HTP | HDA | HMP | HGI | HEP => EviCode::HTP,
+            IBA | IBD | IKR | IRD => EviCode::PHYLO,
+            ISS | ISO | ISA | ISM | IGC | RCA => EviCode::COMPUTATIONAL,
+            TAS | NAS => EviCode::AUTHOR,
+            IC => EviCode::IC,
+            ND => EviCode::ND,
+            IEA => EviCode::IEA,
+        }
+    }
+}
+
+/// Maps the ECO ids of GPAD files to GO evidence codes.
+///
+/// Besides the ids of [`EvidenceCode::from_eco`], a more specific ECO term is mapped to the code of
+/// its most specific ancestor among those ids, if the ECO ontology is loaded. This is how GO derives
+/// its complete mapping (gaf-eco-mapping-derived.txt); e.g., ECO:0007634 (mutant phenotype evidence
+/// used in manual assertion) descends from ECO:0000315 and is mapped to IMP.
+pub struct EcoResolver<'a> {
+    eco: Option<&'a GeneOntology>,
+    /// The mapping of the ids seen so far, since a GPAD file uses few distinct ECO ids on many lines
+    resolved: HashMap<String, Result<EvidenceCode, String>>,
+}
+
+impl<'a> EcoResolver<'a> {
+    pub fn new(eco: Option<&'a GeneOntology>) -> Self {
+        EcoResolver {
+            eco,
+            resolved: HashMap::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, id: &str) -> Result<EvidenceCode, InputError> {
+        if let Some(resolved) = self.resolved.get(id) {
+            return resolved.clone().map_err(InputError::UnknownEvidenceCode);
+        }
+        let resolved = match (EvidenceCode::from_eco(id), self.eco) {
+            (Ok(code), _) => Ok(code),
+            (Err(e), None) => Err(e),
+            (Err(_), Some(eco)) => Self::resolve_ancestry(id, eco),
+        };
+        let cached = match &resolved {
+            Ok(code) => Ok(*code),
+            Err(e) => Err(e.to_string()),
+        };
+        self.resolved.insert(id.to_string(), cached);
+        resolved
+    }
+
+    fn resolve_ancestry(id: &str, eco: &GeneOntology) -> Result<EvidenceCode, InputError> {
+        let unknown = || {
+            InputError::UnknownEvidenceCode(format!(
+                "'{}' does not descend from the ECO id of a GO evidence code.",
+                id
+            ))
+        };
+        let term = eco.index_of(&TermId::from_curie(id)?).ok_or_else(unknown)?;
+        let ancestors = eco.ancestor_indices(term);
+        let defaults = ECO_IDS.iter().map(|(code, eco_id)| (*code, *eco_id));
+        let iea = IEA_ECO_IDS
+            .iter()
+            .map(|eco_id| (EvidenceCode::IEA, *eco_id));
+        let candidates: Vec<(EvidenceCode, usize)> = defaults
+            .chain(iea)
+            .filter_map(|(code, eco_id)| {
+                let idx = eco.index_of(&TermId::from_curie(eco_id).ok()?)?;
+                ancestors.contains(&idx).then_some((code, idx))
+            })
+            .collect();
+        // the most specific candidate is not an ancestor of another one
+        candidates
+            .iter()
+            .find(|(_, idx)| {
+                !candidates
+                    .iter()
+                    .any(|(_, other)| other != idx && eco.ancestor_indices(*other).contains(idx))
+            })
+            .map(|(code, _)| *code)
+            .ok_or_else(unknown)
+    }
+}
+
+impl FromStr for EvidenceCode {
+    type Err = InputError;
+
+    fn from_str(s: &str) -> Result<Self, InputError> {
+        EvidenceCode::all()
+            .find(|code| code.to_string() == s)
+            .ok_or_else(|| {
+                InputError::UnknownEvidenceCode(format!(
+                    "Did not recognize '{}' as EvidenceCode.",
+                    s
+                ))
+            })
+    }
+}
+
+impl fmt::Display for EvidenceCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the variants are named after the codes
+        write!(f, "{:?}", self)
+    }
+}
+
+/// A set of evidence codes
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum EvidenceSelector {
+    Code(EvidenceCode),
+    Group(EviCode),
+    /// An ECO term and all of its descendants, e.g., ECO:0000088
+    Eco(String),
+}
+
+impl EvidenceSelector {
+    fn matches(&self, code: EvidenceCode, eco: Option<&GeneOntology>) -> Result<bool, String> {
+        match self {
+            EvidenceSelector::Code(c) => Ok(*c == code),
+            EvidenceSelector::Group(g) => Ok(code.group() == *g),
+            EvidenceSelector::Eco(id) if id == code.eco_id() => Ok(true),
+            EvidenceSelector::Eco(id) => {
+                // without the ontology, the ECO id of an evidence code only selects this code
+                let eco = match (eco, EvidenceCode::from_eco(id)) {
+                    (Some(eco), _) => eco,
+                    (None, Ok(selected)) => return Ok(selected == code),
+                    (None, Err(_)) => {
+                        return Err(format!(
+                            "The Evidence and Conclusion Ontology must be loaded to select evidence by {}",
+                            id
+                        ))
+                    }
+                };
+                let term = TermId::from_curie(id).map_err(|e| e.to_string())?;
+                let ancestor = eco
+                    .index_of(&term)
+                    .ok_or_else(|| format!("{} is not a term of the loaded ECO", id))?;
+                let code_term = TermId::from_curie(code.eco_id()).map_err(|e| e.to_string())?;
+                Ok(eco
+                    .index_of(&code_term)
+                    .is_some_and(|idx| eco.ancestor_indices(idx).contains(&ancestor)))
+            }
+        }
+    }
+
+    /// Like [`EvidenceSelector::matches`] for an annotation with the precise ECO id `eco_id`, which
+    /// is more specific than the default id of `code` in some GPAD files
+    fn matches_eco_id(
+        &self,
+        code: EvidenceCode,
+        eco_id: &str,
+        eco: Option<&GeneOntology>,
+    ) -> Result<bool, String> {
+        if let EvidenceSelector::Eco(id) = self {
+            if id == eco_id {
+                return Ok(true);
+            }
+            if let Some(eco) = eco {
+                let index = |curie: &str| {
+                    TermId::from_curie(curie)
+                        .ok()
+                        .and_then(|t| eco.index_of(&t))
+                };
+                if let (Some(term), Some(selected)) = (index(eco_id), index(id)) {
+                    return Ok(eco.ancestor_indices(term).contains(&selected));
+                }
+            }
+        }
+        self.matches(code, eco)
+    }
+}
+
+/// Which evidence to use: the codes matching any of `include` (all codes if it is empty) that do not
+/// match any of `exclude`. For example, experimental evidence except IEP is
+/// `{"include": [{"Group": "EXP"}], "exclude": [{"Code": "IEP"}]}`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct EvidenceFilter {
+    pub include: Vec<EvidenceSelector>,
+    pub exclude: Vec<EvidenceSelector>,
+}
+
+impl EvidenceFilter {
+    fn passes<F>(&self, matches: F) -> Result<bool, String>
+    where
+        F: Fn(&EvidenceSelector) -> Result<bool, String>,
+    {
+        let any = |selectors: &[EvidenceSelector]| -> Result<bool, String> {
+            for selector in selectors {
+                if matches(selector)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        };
+        Ok((self.include.is_empty() || any(&self.include)?) && !any(&self.exclude)?)
+    }
+
+    /// The evidence codes that pass the filter. `eco` is only needed for ECO selectors that are not
+    /// the ECO id of an evidence code.
+    pub fn allowed_codes(
+        &self,
+        eco: Option<&GeneOntology>,
+    ) -> Result<BTreeSet<EvidenceCode>, String> {
+        let mut allowed = BTreeSet::new();
+        for code in EvidenceCode::all() {
+            if self.passes(|selector| selector.matches(code, eco))? {
+                allowed.insert(code);
+            }
+        }
+        Ok(allowed)
+    }
+
+    /// The evidence that passes the filter. `eco_ids` are the precise ECO ids of the annotations from
+    /// GPAD files together with their codes (see [`crate::gaf::GafRecord::eco_id`]).
+    pub fn allowed<'a, I>(
+        &self,
+        eco: Option<&GeneOntology>,
+        eco_ids: I,
+    ) -> Result<AllowedEvidence, String>
+    where
+        I: IntoIterator<Item = (EvidenceCode, &'a str)>,
+    {
+        let mut checked = HashSet::new();
+        let mut allowed_ids = HashSet::new();
+        for (code, eco_id) in eco_ids {
+            if !checked.insert(eco_id) {
+                continue;
+            }
+            if self.passes(|selector| selector.matches_eco_id(code, eco_id, eco))? {
+                allowed_ids.insert(eco_id.to_string());
+            }
+        }
+        Ok(AllowedEvidence {
+            codes: self.allowed_codes(eco)?,
+            eco_ids: allowed_ids,
+        })
+    }
+}
+
+/// The evidence that passes an [`EvidenceFilter`]. Annotations with a precise ECO id are matched by
+/// that id, all others by their evidence code.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AllowedEvidence {
+    pub codes: BTreeSet<EvidenceCode>,
+    pub eco_ids: HashSet<String>,
+}
+
+impl AllowedEvidence {
+    pub fn contains(&self, code: EvidenceCode, eco_id: Option<&str>) -> bool {
+        match eco_id {
+            Some(eco_id) => self.eco_ids.contains(eco_id),
+            None => self.codes.contains(&code),
+        }
+    }
+}
+
+/// The Evidence and Conclusion Ontology, if the user loaded it
+#[derive(Default)]
+pub struct EvidenceOntologyState(pub Mutex<Option<GeneOntology>>);
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+
+    use super::*;
+    use crate::gontology::{parse_obographs, GeneOntologyBuilder, GoTerm, TermRelation};
+    use crate::obo_parser::parse_obo;
+
+    /// evidence <- experimental evidence <- direct assay evidence <- IDA, experimental evidence <- IMP
+    fn eco_fragment() -> GeneOntology {
+        eco_fragment_builder().build().unwrap()
+    }
+
+    fn eco_fragment_builder() -> GeneOntologyBuilder {
+        let mut builder = GeneOntologyBuilder::new();
+        for (id, label) in [
+            ("ECO:0000000", "evidence"),
+            ("ECO:0000006", "experimental evidence"),
+            ("ECO:0000002", "direct assay evidence"),
+            (
+                "ECO:0000314",
+                "direct assay evidence used in manual assertion",
+            ),
+            (
+                "ECO:0000315",
+                "mutant phenotype evidence used in manual assertion",
+            ),
+        ] {
+            builder.add_term(GoTerm::new(TermId::from_curie(id).unwrap(), label));
+        }
+        builder
+            .add_relation("ECO:0000006", TermRelation::IsA, "ECO:0000000")
+            .add_relation("ECO:0000002", TermRelation::IsA, "ECO:0000006")
+            .add_relation("ECO:0000314", TermRelation::IsA, "ECO:0000002")
+            .add_relation("ECO:0000315", TermRelation::IsA, "ECO:0000006");
+        builder
+    }
+
+    #[test]
+    fn test_codes() {
+        for code in EvidenceCode::all() {
+            assert_eq!(code, EvidenceCode::from_str(&code.to_string()).unwrap());
+            assert_eq!(code, EvidenceCode::from_eco(code.eco_id()).unwrap());
+        }
+        assert_eq!(
+            EvidenceCode::IEA,
+            EvidenceCode::from_eco("ECO:0007669").unwrap()
+        );
+        assert_eq!(EviCode::HTP, EvidenceCode::HDA.group());
+        assert!(EvidenceCode::from_str("XYZ").is_err());
+        assert!(EvidenceCode::from_eco("ECO:0000000").is_err());
+    }
+
+    #[test]
+    fn test_eco_resolver() {
+        let mut eco = eco_fragment_builder();
+        eco.add_term(GoTerm::new(
+            TermId::from_curie("ECO:0007634").unwrap(),
+            "mutant phenotype evidence used in manual assertion (specific)",
+        ));
+        eco.add_relation("ECO:0007634", TermRelation::IsA, "ECO:0000315");
+        let eco = eco.build().unwrap();
+        let mut resolver = EcoResolver::new(Some(&eco));
+        assert_eq!(EvidenceCode::IDA, resolver.resolve("ECO:0000314").unwrap());
+        assert_eq!(EvidenceCode::IMP, resolver.resolve("ECO:0007634").unwrap());
+        // experimental evidence is more general than any GO evidence code
+        assert!(resolver.resolve("ECO:0000006").is_err());
+        assert!(resolver.resolve("ECO:0000006").is_err());
+        let mut without_eco = EcoResolver::new(None);
+        assert!(without_eco.resolve("ECO:0007634").is_err());
+        assert_eq!(
+            EvidenceCode::IEA,
+            without_eco.resolve("ECO:0000256").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_precise_eco_ids() {
+        let mut eco = eco_fragment_builder();
+        eco.add_term(GoTerm::new(
+            TermId::from_curie("ECO:0007634").unwrap(),
+            "mutant phenotype evidence used in manual assertion (specific)",
+        ));
+        eco.add_relation("ECO:0007634", TermRelation::IsA, "ECO:0000315");
+        let eco = eco.build().unwrap();
+        let specific = EvidenceFilter {
+            include: vec![EvidenceSelector::Eco("ECO:0007634".to_string())],
+            exclude: vec![],
+        };
+        let eco_ids = [
+            (EvidenceCode::IMP, "ECO:0007634"),
+            (EvidenceCode::IMP, "ECO:0000315"),
+        ];
+        let allowed = specific.allowed(Some(&eco), eco_ids).unwrap();
+        // only the annotations with the more specific ECO id pass, not all IMP annotations
+        assert!(allowed.contains(EvidenceCode::IMP, Some("ECO:0007634")));
+        assert!(!allowed.contains(EvidenceCode::IMP, Some("ECO:0000315")));
+        assert!(!allowed.contains(EvidenceCode::IMP, None));
+        let imp = EvidenceFilter {
+            include: vec![EvidenceSelector::Eco("ECO:0000315".to_string())],
+            exclude: vec![],
+        };
+        let allowed = imp.allowed(None, eco_ids).unwrap();
+        assert!(allowed.contains(EvidenceCode::IMP, Some("ECO:0007634")));
+        assert!(allowed.contains(EvidenceCode::IMP, None));
+    }
+
+    #[test]
+    fn test_filter() {
+        let experimental_except_iep = EvidenceFilter {
+            include: vec![EvidenceSelector::Group(EviCode::EXP)],
+            exclude: vec![EvidenceSelector::Code(EvidenceCode::IEP)],
+        };
+        let allowed = experimental_except_iep.allowed_codes(None).unwrap();
+        assert_eq!(5, allowed.len());
+        assert!(allowed.contains(&EvidenceCode::IDA));
+        assert!(!allowed.contains(&EvidenceCode::IEP));
+        let no_iea = EvidenceFilter {
+            include: vec![],
+            exclude: vec![EvidenceSelector::Eco("ECO:0000501".to_string())],
+        };
+        assert_eq!(25, no_iea.allowed_codes(None).unwrap().len());
+    }
+
+    const ECO_OBO: &str = "format-version: 1.2
+default-namespace: eco
+
+[Term]
+id: ECO:0000000
+name: evidence
+namespace: eco
+
+[Term]
+id: ECO:0000006
+name: experimental evidence
+namespace: eco
+is_a: ECO:0000000 ! evidence
+
+[Term]
+id: ECO:0000314
+name: direct assay evidence used in manual assertion
+is_a: ECO:0000006 ! experimental evidence
+
+[Term]
+id: ECO:0000315
+name: mutant phenotype evidence used in manual assertion
+is_a: ECO:0000006 ! experimental evidence
+";
+
+    const ECO_JSON: &str = r#"{"graphs": [{
+      "id": "http://purl.obolibrary.org/obo/eco.json",
+      "nodes": [
+        {"id": "http://purl.obolibrary.org/obo/ECO_0000006", "lbl": "experimental evidence", "type": "CLASS",
+         "meta": {"basicPropertyValues": [
+           {"pred": "http://www.geneontology.org/formats/oboInOwl#hasOBONamespace", "val": "eco"}]}},
+        {"id": "http://purl.obolibrary.org/obo/ECO_0000315", "lbl": "mutant phenotype evidence used in manual assertion", "type": "CLASS",
+         "meta": {"basicPropertyValues": [
+           {"pred": "http://www.geneontology.org/formats/oboInOwl#hasOBONamespace", "val": "eco"}]}}
+      ],
+      "edges": [
+        {"sub": "http://purl.obolibrary.org/obo/ECO_0000315", "pred": "is_a", "obj": "http://purl.obolibrary.org/obo/ECO_0000006"}
+      ]}]}"#;
+
+    #[test]
+    fn test_load_eco() {
+        let experimental = EvidenceFilter {
+            include: vec![EvidenceSelector::Eco("ECO:0000006".to_string())],
+            exclude: vec![],
+        };
+        let eco = parse_obo(ECO_OBO.as_bytes()).unwrap();
+        assert_eq!(4, eco.term_count());
+        let allowed = experimental.allowed_codes(Some(&eco)).unwrap();
+        assert_eq!(
+            vec![EvidenceCode::IDA, EvidenceCode::IMP],
+            allowed.into_iter().collect::<Vec<_>>()
+        );
+        let eco = parse_obographs(ECO_JSON.as_bytes()).unwrap();
+        assert_eq!(2, eco.term_count());
+        let allowed = experimental.allowed_codes(Some(&eco)).unwrap();
+        assert_eq!(
+            vec![EvidenceCode::IMP],
+            allowed.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_eco_descendants() {
+        let eco = eco_fragment();
+        let direct_assay = EvidenceFilter {
+            include: vec![EvidenceSelector::Eco("ECO:0000002".to_string())],
+            exclude: vec![],
+        };
+        assert!(direct_assay.allowed_codes(None).is_err());
+        let allowed = direct_assay.allowed_codes(Some(&eco)).unwrap();
+        assert_eq!(
+            vec![EvidenceCode::IDA],
+            allowed.into_iter().collect::<Vec<_>>()
+        );
+        let experimental = EvidenceFilter {
+            include: vec![EvidenceSelector::Eco("ECO:0000006".to_string())],
+            exclude: vec![],
+        };
+        let allowed = experimental.allowed_codes(Some(&eco)).unwrap();
+        assert_eq!(
+            vec![EvidenceCode::IDA, EvidenceCode::IMP],
+            allowed.into_iter().collect::<Vec<_>>()
+        );
+    }
+}
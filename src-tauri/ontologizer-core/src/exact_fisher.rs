@@ -0,0 +1,660 @@
+use std::cmp::{max, min};
+
+/// log(sum(exp(values))) without overflow or underflow; negative infinity for an empty slice
+fn log_sum_exp(values: &[f64]) -> f64 {
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + values.iter().map(|v| (v - max).exp()).sum::<f64>().ln()
+}
+
+/// Hypergeometric distribution based on a table of log factorials.
+///
+/// The table is computed once for the largest urn that will be used (usually the population), so the
+/// calculator is immutable and can be shared between threads.
+pub struct Hypergeometric {
+    // log factorial
+    lfactorial: Vec<f64>,
+}
+
+impl Hypergeometric {
+    /// Precompute the log factorials of 0..=max_n
+    pub fn new(max_n: usize) -> Self {
+        let mut lfactorial = Vec::with_capacity(max_n + 1);
+        // by convention, we let log(0) = 0, because 0! = 1
+        lfactorial.push(0.0);
+        for j in 1..=max_n {
+            lfactorial.push(lfactorial[j - 1] + (j as f64).ln());
+        }
+        Hypergeometric { lfactorial }
+    }
+
+    /// The largest n for which the log factorial is available
+    pub fn max_n(&self) -> usize {
+        self.lfactorial.len() - 1
+    }
+
+    pub fn logfact(&self, i: usize) -> Result<f64, String> {
+        self.lfactorial.get(i).copied().ok_or_else(|| {
+            format!(
+                "Could not calculate log factorial for j={}, the calculator was created for n <= {}",
+                i,
+                self.max_n()
+            )
+        })
+    }
+
+    /**
+     * <P>
+     * For the hypergeometric distribution note the following.
+     * </P>
+     * <UL>
+     * <LI>We set up the problem as sampling a set of genes (study genes, for
+     * instance, the set of upregulated genes in some microarray experiment)
+     * from a larger set of genes (say, the set of all genes of a species). The
+     * sampling is done without replacement. </LI>
+     * <LI>For each GO term, we can conceive of the population as being divided
+     * into genes annotated to this term and those genes that are not annotated
+     * to the term.</LI>
+     * <LI>The probability of having a certain number of terms annotated to the
+     * term in the study set can then be calculated by the hypergeometric
+     * distribution. See GeneMerge by Castillo-Davis et al (Bioinformatics).
+     * </LI>
+     * <LI>To do this, we need to divide the population into two groups, genes
+     * with and without annotation. The arguments to the function supply us with
+     * <B>n</B>, the total number of genes in the population group, and <B>p
+     * </B>, the proportion of genes with annotation to the term in question.
+     * We can then calculate the number of genes in the population annotated to
+     * the term by <B>round(n*p)</B>, and the number of genes not annotated to
+     * the term by <B>round(n*(1-p))</B>.</LI>
+     * </UL>
+     *
+     *
+     * @param n
+     *            Number of population genes
+     * @param p
+     *            Proportion of population genes
+     * @param k
+     *            Number of study genes
+     * @param r
+     *            Number of study genes in group
+     */
+    pub fn phypergeometric(&self, n: usize, p: f64, k: usize, r: usize) -> Result<f64, String> {
+        /*
+         * Study group cannot be larger than population. If this happens there
+         * is probably something wrong with the input data, but returning 1.0
+         * prevents confusing and wrong output.
+         */
+        if k >= n {
+            return Ok(1.0);
+        }
+
+        if r < 1 {
+            return Ok(1.0); // Not valid for r < 2, less than 2 study genes.
+        }
+
+        let q = 1.0 - p;
+        let np = (n as f64 * p).round() as usize; // Round to nearest int
+        let nq = (n as f64 * q).round() as usize;
+
+        // P(X >= r) for r study genes in the group
+        self.phyper(r - 1, np + nq, np, k, false)
+    }
+
+    /// n-choose-k in log space
+    #[allow(non_snake_case)]
+    pub fn lNchooseK(&self, n: usize, k: usize) -> Result<f64, String> {
+        let result = self.logfact(n)? - self.logfact(k)? - self.logfact(n - k)?;
+        Ok(result)
+    }
+
+    /// dhyper: Density for the hypergeometric function
+    ///
+    /// # Parameters
+    /// - `x`: number of white balls drawn without replacement
+    /// - `m`: the number of white balls in the urn.
+    /// - `n`: the number of black balls in the urn.
+    /// - `k`: the number of balls drawn from the urn, hence must be in 0,1,..., m+n.
+    ///
+    /// # Returns
+    /// - Returns the density function as a Result.
+    pub fn dhyper(&self, x: usize, m: usize, n: usize, k: usize) -> Result<f64, String> {
+        Ok(self.ldhyper(x, m, n, k)?.exp())
+    }
+
+    /// Logarithm of [`Hypergeometric::dhyper`]; negative infinity if x is impossible
+    pub fn ldhyper(&self, x: usize, m: usize, n: usize, k: usize) -> Result<f64, String> {
+        // It is not possible to draw more white balls than there are in the urn or than the number of
+        // draws, nor to draw more black balls (k-x) than there are in the urn.
+        if x > m || x > k || k - x > n {
+            return Ok(f64::NEG_INFINITY);
+        }
+        // #ways to choose x white balls + #ways to choose k-x black balls less #ways to choose k balls from total (m+n)
+        Ok(self.lNchooseK(m, x)? + self.lNchooseK(n, k - x)? - self.lNchooseK(m + n, k)?)
+    }
+
+    /// phyper - Calculates P(X &gt; x) where X is the hypergeometric distribution with indices N,M,n.
+    ///
+    /// If lower_tail is set to true, then P(X &lt;= x) is calculated.
+    /// The value is computed with [`Hypergeometric::lphyper`], so upper tails close to zero keep their precision.
+    ///
+    /// # Parameters
+    /// - `x`:  x number of white balls drawn without replacement
+    /// - `N`: number of balls in the urn
+    /// - `M`: number of white balls in the urn
+    /// - `n`: number of balls drawn from the urn
+    /// - `lower_tail`: if true, then P(X &lt;= x) is calculated, otherwise P(X &gt; x) is calculated.
+    ///
+    /// # Returns
+    /// - Returns the tail probability as a Result.
+    #[allow(non_snake_case)]
+    pub fn phyper(
+        &self,
+        x: usize,
+        N: usize,
+        M: usize,
+        n: usize,
+        lower_tail: bool,
+    ) -> Result<f64, String> {
+        Ok(self.lphyper(x, N, M, n, lower_tail)?.exp())
+    }
+
+    /// Logarithm of [`Hypergeometric::phyper`].
+    ///
+    /// The requested tail is summed directly with log-sum-exp instead of subtracting the other tail from
+    /// one, so that p-values far below 1e-300, which underflow in linear space, are still available.
+    #[allow(non_snake_case)]
+    pub fn lphyper(
+        &self,
+        x: usize,
+        N: usize,
+        M: usize,
+        n: usize,
+        lower_tail: bool,
+    ) -> Result<f64, String> {
+        if M > N || n > N {
+            return Err(format!(
+                "Invalid hypergeometric parameters: {} white balls and {} draws from an urn with {} balls",
+                M, n, N
+            ));
+        }
+        // support of the distribution
+        let lo = n.saturating_sub(N - M);
+        let up = min(n, M);
+        let tail = if lower_tail {
+            lo..=min(x, up)
+        } else {
+            max(x + 1, lo)..=up
+        };
+        let mut log_densities = Vec::with_capacity(tail.size_hint().0);
+        for i in tail {
+            log_densities.push(self.ldhyper(i, M, N - M, n)?);
+        }
+        Ok(log_sum_exp(&log_densities).min(0.0))
+    }
+}
+
+/// A 2x2 contingency table
+///
+/// |           | in term | not in term |
+/// |-----------|---------|-------------|
+/// | study     | `a`     | `b`         |
+/// | not study | `c`     | `d`         |
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ContingencyTable {
+    pub a: usize,
+    pub b: usize,
+    pub c: usize,
+    pub d: usize,
+}
+
+impl ContingencyTable {
+    pub fn new(a: usize, b: usize, c: usize, d: usize) -> Self {
+        ContingencyTable { a, b, c, d }
+    }
+
+    /// Table of a GO term from the counts of an enrichment analysis, where the study genes are part of the population
+    pub fn from_term_counts(
+        population_total: usize,
+        population_count: usize,
+        study_total: usize,
+        study_count: usize,
+    ) -> Result<Self, String> {
+        if study_count > study_total
+            || study_count > population_count
+            || population_count > population_total
+            || study_total - study_count > population_total - population_count
+        {
+            return Err(format!(
+                "Inconsistent counts: {}/{} study genes and {}/{} population genes in term",
+                study_count, study_total, population_count, population_total
+            ));
+        }
+        let c = population_count - study_count;
+        Ok(ContingencyTable {
+            a: study_count,
+            b: study_total - study_count,
+            c,
+            d: population_total - study_total - c,
+        })
+    }
+}
+
+/// Result of Fisher's exact test, computed as in R's `fisher.test`
+#[derive(Clone, Debug)]
+pub struct FisherResult {
+    /// Sum of the probabilities of all tables that are at most as likely as the observed one
+    pub two_sided: f64,
+    /// P(X <= a), small values indicate depletion
+    pub less: f64,
+    /// P(X >= a), small values indicate enrichment
+    pub greater: f64,
+    /// Conditional maximum likelihood estimate of the odds ratio
+    pub odds_ratio: f64,
+    /// Exact two-sided confidence interval of the odds ratio
+    pub confidence_interval: (f64, f64),
+}
+
+/// Fisher's exact test on 2x2 tables.
+///
+/// Conditional on the margins, `a` follows Fisher's noncentral hypergeometric distribution with the odds
+/// ratio as noncentrality parameter; for an odds ratio of one this is the hypergeometric distribution.
+/// The odds ratio and the limits of the confidence interval are found by bisection.
+pub struct FisherExact {
+    hgeom: Hypergeometric,
+    confidence_level: f64,
+}
+
+/// Relative tolerance for tables that are as likely as the observed one (same value as R)
+const FISHER_RELATIVE_ERROR: f64 = 1.0 + 1e-7;
+
+/// Support and log densities of the central hypergeometric distribution of `a` given the margins
+struct NoncentralHypergeometric {
+    lo: usize,
+    hi: usize,
+    log_densities: Vec<f64>,
+}
+
+impl NoncentralHypergeometric {
+    /// Densities for the odds ratio `ncp`, which may be zero or infinite
+    fn densities(&self, ncp: f64) -> Vec<f64> {
+        let support = self.lo..=self.hi;
+        if ncp == 0.0 || ncp.is_infinite() {
+            let mode = if ncp == 0.0 { self.lo } else { self.hi };
+            return support.map(|x| if x == mode { 1.0 } else { 0.0 }).collect();
+        }
+        let log_ncp = ncp.ln();
+        let d: Vec<f64> = support
+            .zip(&self.log_densities)
+            .map(|(x, ld)| ld + log_ncp * x as f64)
+            .collect();
+        let max = d.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let d: Vec<f64> = d.into_iter().map(|x| (x - max).exp()).collect();
+        let sum: f64 = d.iter().sum();
+        d.into_iter().map(|x| x / sum).collect()
+    }
+
+    fn mean(&self, ncp: f64) -> f64 {
+        (self.lo..=self.hi)
+            .zip(self.densities(ncp))
+            .map(|(x, d)| x as f64 * d)
+            .sum()
+    }
+
+    /// P(X <= q), or P(X >= q) if `upper_tail`
+    fn cdf(&self, q: usize, ncp: f64, upper_tail: bool) -> f64 {
+        (self.lo..=self.hi)
+            .zip(self.densities(ncp))
+            .filter(|(x, _)| if upper_tail { *x >= q } else { *x <= q })
+            .map(|(_, d)| d)
+            .sum()
+    }
+}
+
+/// Root of the monotone function `f` on [0, 1] by bisection
+fn bisect<F: Fn(f64) -> f64>(f: F) -> f64 {
+    let (mut lo, mut hi) = (0.0, 1.0);
+    let increasing = f(hi) > f(lo);
+    for _ in 0..100 {
+        let mid = 0.5 * (lo + hi);
+        if (f(mid) < 0.0) == increasing {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    0.5 * (lo + hi)
+}
+
+impl FisherExact {
+    /// Fisher's exact test with 95% confidence intervals for tables with a total of at most `max_n`
+    pub fn new(max_n: usize) -> Self {
+        Self::with_confidence_level(max_n, 0.95)
+    }
+
+    pub fn with_confidence_level(max_n: usize, confidence_level: f64) -> Self {
+        FisherExact {
+            hgeom: Hypergeometric::new(max_n),
+            confidence_level,
+        }
+    }
+
+    pub fn test(&self, table: &ContingencyTable) -> Result<FisherResult, String> {
+        if !(0.0..1.0).contains(&self.confidence_level) {
+            return Err(format!(
+                "Confidence level must be in [0, 1), got {}",
+                self.confidence_level
+            ));
+        }
+        let m = table.a + table.c;
+        let n = table.b + table.d;
+        let k = table.a + table.b;
+        let x = table.a;
+        let lo = k.saturating_sub(n);
+        let hi = min(k, m);
+        let mut log_densities = Vec::with_capacity(hi - lo + 1);
+        for i in lo..=hi {
+            log_densities.push(
+                self.hgeom.lNchooseK(m, i)? + self.hgeom.lNchooseK(n, k - i)?
+                    - self.hgeom.lNchooseK(m + n, k)?,
+            );
+        }
+        let dist = NoncentralHypergeometric {
+            lo,
+            hi,
+            log_densities,
+        };
+        let densities = dist.densities(1.0);
+        let observed = densities[x - lo] * FISHER_RELATIVE_ERROR;
+        let two_sided = densities.iter().filter(|&&d| d <= observed).sum::<f64>();
+        let alpha = (1.0 - self.confidence_level) / 2.0;
+        Ok(FisherResult {
+            two_sided: two_sided.min(1.0),
+            less: dist.cdf(x, 1.0, false).min(1.0),
+            greater: dist.cdf(x, 1.0, true).min(1.0),
+            odds_ratio: Self::odds_ratio(&dist, x),
+            confidence_interval: (
+                Self::lower_limit(&dist, x, alpha),
+                Self::upper_limit(&dist, x, alpha),
+            ),
+        })
+    }
+
+    /// The odds ratio for which the mean of the distribution equals the observed count
+    fn odds_ratio(dist: &NoncentralHypergeometric, x: usize) -> f64 {
+        if x == dist.lo {
+            return 0.0;
+        }
+        if x == dist.hi {
+            return f64::INFINITY;
+        }
+        let x = x as f64;
+        let mu = dist.mean(1.0);
+        if mu > x {
+            bisect(|t| dist.mean(t) - x)
+        } else if mu < x {
+            1.0 / bisect(|t| dist.mean(1.0 / t) - x)
+        } else {
+            1.0
+        }
+    }
+
+    /// The largest odds ratio for which P(X <= x) is still at least alpha
+    fn upper_limit(dist: &NoncentralHypergeometric, x: usize, alpha: f64) -> f64 {
+        if x == dist.hi {
+            return f64::INFINITY;
+        }
+        let p = dist.cdf(x, 1.0, false);
+        if p < alpha {
+            bisect(|t| dist.cdf(x, t, false) - alpha)
+        } else if p > alpha {
+            1.0 / bisect(|t| dist.cdf(x, 1.0 / t, false) - alpha)
+        } else {
+            1.0
+        }
+    }
+
+    /// The smallest odds ratio for which P(X >= x) is still at least alpha
+    fn lower_limit(dist: &NoncentralHypergeometric, x: usize, alpha: f64) -> f64 {
+        if x == dist.lo {
+            return 0.0;
+        }
+        let p = dist.cdf(x, 1.0, true);
+        if p > alpha {
+            bisect(|t| dist.cdf(x, t, true) - alpha)
+        } else if p < alpha {
+            1.0 / bisect(|t| dist.cdf(x, 1.0 / t, true) - alpha)
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use float_eq::float_eq;
+    use std::assert_eq;
+
+    #[test]
+    fn test_lfactorial() {
+        let tests: Vec<(usize, i64)> = vec![
+            (1, 1),
+            (2, 2),
+            (3, 6),
+            (4, 24),
+            (5, 120),
+            (6, 720),
+            (7, 5040),
+            (8, 40_320),
+            (9, 362_880),
+            (10, 3_628_800),
+            (11, 39_916_800),
+            (12, 479_001_600),
+            (13, 6_227_020_800),
+            (14, 87_178_291_200),
+            (15, 1_307_674_368_000),
+        ];
+        let hgeom = Hypergeometric::new(15);
+        for test in tests {
+            let lf = hgeom.logfact(test.0);
+            assert!(lf.is_ok());
+            let fact = lf.unwrap().exp();
+            assert!(float_eq!(test.1 as f64, fact, rmax <= 1e-6));
+        }
+    }
+
+    #[test]
+    fn test_l_n_choose_k() {
+        // Test log N-choose-K for K=4, N=20
+        let hgeom = Hypergeometric::new(20);
+        let lf4 = hgeom.logfact(4).unwrap();
+        let lf16 = hgeom.logfact(16).unwrap();
+        let lf20 = hgeom.logfact(20).unwrap();
+        // lfactorial(20) =>  42.33562
+        assert!(float_eq!(42.33562, lf20, rmax <= 1e-6));
+        // lfactorial(16) =>  30.67186
+        assert!(float_eq!(30.67186, lf16, rmax <= 1e-6));
+        // lfactorial(4) =>  3.178054
+        assert!(float_eq!(3.178054, lf4, rmax <= 1e-6));
+        let nck = lf20 - lf4 - lf16; // calculate by hand
+                                     // lchoose(20, 4) =>  8.485703 in R
+        let expected_r = 8.485703;
+        assert!(float_eq!(expected_r, nck, rmax <= 1e-6));
+        let mylck = hgeom.lNchooseK(20, 4).unwrap();
+        assert!(float_eq!(expected_r, mylck, rmax <= 1e-6));
+    }
+
+    #[test]
+    fn test_dhyper() {
+        let hgeom = Hypergeometric::new(65);
+        // Let's first valid log-NchooseK
+        // in R,  dhyper(4,20,45,10) yields 0.2204457
+        // This means we choose 4 white balls from an urn with 20 white and 45 black balls when we take a total of 10 balls
+        let a = hgeom.lNchooseK(20, 4);
+        let b = hgeom.lNchooseK(45, 6);
+        let c = hgeom.lNchooseK(65, 10);
+        let lck = a.unwrap() + b.unwrap() - c.unwrap();
+        // Exponentiate the result to get the result
+        let n_choose_k = lck.exp();
+        let expected = 0.2204457;
+        assert!(float_eq!(expected, n_choose_k, rmax <= 1e-6));
+        // Now test our version
+        let result = hgeom.dhyper(4, 20, 45, 10);
+        assert!(result.is_ok());
+        let our_n_choose_k = result.unwrap();
+        assert!(float_eq!(expected, our_n_choose_k, rmax <= 1e-6));
+    }
+
+    #[test]
+    fn test_dhyper_edge() {
+        // We have ten balls, all white, and we draw 10. All ten must be white
+        let hgeom = Hypergeometric::new(10);
+        let result = hgeom.dhyper(10, 10, 0, 10);
+        assert!(result.is_ok());
+        let our_n_choose_k = result.unwrap();
+        assert!(float_eq!(1.0, our_n_choose_k, rmax <= 1e-6));
+    }
+
+    #[test]
+    fn test_shared_calculator() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Hypergeometric>();
+        assert_send_sync::<FisherExact>();
+        let hgeom = Hypergeometric::new(1526);
+        let p = hgeom.phyper(2, 1526, 4, 190, false).unwrap();
+        // the same calculator is used by several threads at once
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| assert_eq!(p, hgeom.phyper(2, 1526, 4, 190, false).unwrap()));
+            }
+        });
+        assert!(hgeom.logfact(1527).is_err());
+        assert!(hgeom.phyper(2, 2000, 4, 190, false).is_err());
+    }
+
+    #[test]
+    fn test_phyper() {
+        let hgeom = Hypergeometric::new(30000);
+        let result = hgeom.phyper(2, 1526, 4, 190, false);
+        // assertTrue(result > 0.0069 && result < 0.0070); -- from ontologizer code.
+        let p = result.unwrap();
+        assert!(p > 0.0069 && p < 0.0070);
+    }
+
+    /// (x, N, M, n, lower_tail, log p) with exact values of phyper(x, M, N - M, n, lower.tail, log.p = TRUE),
+    /// computed with rational arithmetic
+    const PHYPER_REFERENCE: [(usize, usize, usize, usize, bool, f64); 10] = [
+        (2, 1526, 4, 190, false, -4.974349029982932),
+        (2, 1526, 4, 190, true, -0.006937023246791796),
+        (10, 20000, 150, 400, false, -8.414076432575712),
+        (0, 20000, 150, 400, true, -3.0418668910526776),
+        (40, 15000, 2000, 300, true, -0.6122700155992788),
+        (40, 15000, 2000, 300, false, -0.7811456329179691),
+        (30, 20000, 150, 400, false, -50.69306219994337),
+        (100, 30000, 1000, 1000, false, -52.02364110941744),
+        (5, 30000, 1000, 1000, true, -21.2721976064131),
+        // p = 1.2e-386, far below the smallest f64
+        (299, 20000, 500, 500, false, -889.5457518743542),
+    ];
+
+    #[test]
+    fn test_lphyper_reference() {
+        let hgeom = Hypergeometric::new(30000);
+        for (x, balls, white, drawn, lower_tail, expected) in PHYPER_REFERENCE {
+            let lp = hgeom.lphyper(x, balls, white, drawn, lower_tail).unwrap();
+            assert!(
+                float_eq!(expected, lp, rmax <= 1e-8),
+                "phyper({}, {}, {}, {}, {})",
+                x,
+                balls,
+                white,
+                drawn,
+                lower_tail
+            );
+            let p = hgeom.phyper(x, balls, white, drawn, lower_tail).unwrap();
+            assert!(float_eq!(expected.exp(), p, rmax <= 1e-8));
+        }
+    }
+
+    #[test]
+    fn test_phyper_tails() {
+        let hgeom = Hypergeometric::new(30000);
+        // the upper tail is 9.6e-23, which would be lost in 1 - P(X <= 30)
+        let upper = hgeom.phyper(30, 20000, 150, 400, false).unwrap();
+        assert!(float_eq!(9.644568806399722e-23, upper, rmax <= 1e-8));
+        let lower = hgeom.phyper(30, 20000, 150, 400, true).unwrap();
+        assert!(float_eq!(1.0, lower, rmax <= 1e-12));
+        // tails outside of the support
+        assert_eq!(0.0, hgeom.phyper(4, 1526, 4, 190, false).unwrap());
+        assert_eq!(
+            f64::NEG_INFINITY,
+            hgeom.lphyper(4, 1526, 4, 190, false).unwrap()
+        );
+        assert_eq!(1.0, hgeom.phyper(4, 1526, 4, 190, true).unwrap());
+        // all 10 balls must be white
+        assert_eq!(0.0, hgeom.phyper(9, 10, 10, 10, true).unwrap());
+        assert!(hgeom.phyper(1, 10, 11, 5, true).is_err());
+    }
+
+    #[test]
+    fn test_phypergeometric() {
+        let hgeom = Hypergeometric::new(30000);
+        // three of 190 study genes in a group of 4 of 1526 population genes
+        let p = hgeom.phypergeometric(1526, 4.0 / 1526.0, 190, 3).unwrap();
+        assert!(float_eq!((-4.974349029982932_f64).exp(), p, rmax <= 1e-8));
+        assert_eq!(1.0, hgeom.phypergeometric(1526, 0.1, 190, 0).unwrap());
+    }
+
+    #[test]
+    fn test_fisher_tea_tasting() {
+        // TeaTasting <- matrix(c(3, 1, 1, 3), nrow = 2); fisher.test(TeaTasting) in R
+        let fisher = FisherExact::new(2000);
+        let result = fisher.test(&ContingencyTable::new(3, 1, 1, 3)).unwrap();
+        assert!(float_eq!(0.4857143, result.two_sided, rmax <= 1e-6));
+        assert!(float_eq!(0.2428571, result.greater, rmax <= 1e-6));
+        assert!(float_eq!(0.9857143, result.less, rmax <= 1e-6));
+        assert!(float_eq!(6.408309, result.odds_ratio, rmax <= 1e-3));
+        assert!(float_eq!(
+            0.2117329,
+            result.confidence_interval.0,
+            rmax <= 1e-3
+        ));
+        // R reports 621.9337505 because of the default tolerance of uniroot; at 626.2435 the lower tail
+        // probability is exactly 0.025
+        assert!(float_eq!(
+            626.2435,
+            result.confidence_interval.1,
+            rmax <= 1e-6
+        ));
+    }
+
+    #[test]
+    fn test_fisher_depletion() {
+        // Convictions <- matrix(c(2, 10, 15, 3), nrow = 2); fisher.test(Convictions, alternative = "less")
+        let fisher = FisherExact::new(2000);
+        let result = fisher.test(&ContingencyTable::new(2, 15, 10, 3)).unwrap();
+        assert!(float_eq!(0.0004652, result.less, rmax <= 1e-3));
+        assert!(float_eq!(0.04693661, result.odds_ratio, rmax <= 1e-3));
+        assert!(result.two_sided > result.less);
+        assert!(result.greater > 0.99);
+    }
+
+    #[test]
+    fn test_fisher_term_counts() {
+        // 3 of 190 study genes and 4 of 1526 population genes: P(X > 2) of test_phyper is the p-value
+        let table = ContingencyTable::from_term_counts(1526, 4, 190, 3).unwrap();
+        assert_eq!(ContingencyTable::new(3, 187, 1, 1335), table);
+        let fisher = FisherExact::new(2000);
+        let result = fisher.test(&table).unwrap();
+        let hgeom = Hypergeometric::new(30000);
+        let p = hgeom.phyper(2, 1526, 4, 190, false).unwrap();
+        assert!(float_eq!(p, result.greater, rmax <= 1e-9));
+        assert!(ContingencyTable::from_term_counts(10, 4, 5, 6).is_err());
+        // no study gene in the term: the odds ratio is zero and the interval starts at zero
+        let result = fisher.test(&ContingencyTable::new(0, 5, 5, 5)).unwrap();
+        assert_eq!(0.0, result.odds_ratio);
+        assert_eq!(0.0, result.confidence_interval.0);
+    }
+}
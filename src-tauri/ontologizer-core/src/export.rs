@@ -0,0 +1,490 @@
+//! Export of analysis results.
+//!
+//! A result is saved as a tab-separated table in the layout of the `table-*.txt` files of the classic
+//! Ontologizer, as JSON, or as a self-contained HTML report. The report has sortable tables and lists
+//! the parameters of the run and the checksums of the input files, so it can be shared on its own.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::enrichment::{AnalysisResult, EnrichmentResult};
+use crate::exact_fisher::Hypergeometric;
+use crate::goannot::AnnotationStat;
+use crate::mgsa::MgsaResult;
+use crate::session::{AnalysisOutput, InputFile};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum ExportFormat {
+    Tsv,
+    Json,
+    Html,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tsv" | "txt" => Ok(ExportFormat::Tsv),
+            "json" => Ok(ExportFormat::Json),
+            "html" | "htm" => Ok(ExportFormat::Html),
+            _ => Err(format!("Unknown export format: {}", s)),
+        }
+    }
+}
+
+/// The results of a run together with the description of its inputs
+#[derive(Serialize)]
+pub struct Report<'a> {
+    pub title: String,
+    /// Datasets and settings of the run, e.g., the study set and the evidence filter
+    pub parameters: Vec<AnnotationStat>,
+    pub inputs: Vec<InputFile>,
+    pub results: &'a AnalysisOutput,
+}
+
+/// A result table with formatted values. The last column is the name of the term.
+pub struct Table {
+    /// Short name that is used in file names, e.g., `Elim`
+    pub method: String,
+    pub caption: String,
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Columns of the `table-*.txt` files of the classic Ontologizer
+pub const ENRICHMENT_COLUMNS: [&str; 9] = [
+    "ID",
+    "Pop.total",
+    "Pop.term",
+    "Study.total",
+    "Study.term",
+    "p",
+    "p.adjusted",
+    "p.min",
+    "name",
+];
+
+/// Columns of Fisher's exact test that the HTML report adds before the name. The TSV files keep the
+/// classic layout.
+pub const FISHER_COLUMNS: [&str; 5] = [
+    "p.less",
+    "p.two.sided",
+    "odds.ratio",
+    "odds.ratio.lower",
+    "odds.ratio.upper",
+];
+
+pub const MGSA_COLUMNS: [&str; 7] = [
+    "ID",
+    "Pop.total",
+    "Pop.term",
+    "Study.total",
+    "Study.term",
+    "marg",
+    "name",
+];
+
+/// The smallest p-value that the term can reach with its population counts, i.e., the probability that
+/// as many of its genes as possible are in the study set. Terms whose minimal p-value is above the
+/// significance level cannot become significant, whatever the study set.
+pub fn min_p_value(hgeom: &Hypergeometric, r: &EnrichmentResult) -> Result<f64, String> {
+    let most = r.population_count.min(r.study_total);
+    hgeom.dhyper(
+        most,
+        r.population_count,
+        r.population_total - r.population_count,
+        r.study_total,
+    )
+}
+
+/// The table of one method, in the classic layout or with the [`FISHER_COLUMNS`]
+pub fn enrichment_table(result: &AnalysisResult, classic: bool) -> Result<Table, String> {
+    // the parent-child methods count the genes of the parents, which are at most the population
+    let max_n = result
+        .results
+        .iter()
+        .map(|r| r.population_total)
+        .max()
+        .unwrap_or(0);
+    let hgeom = Hypergeometric::new(max_n);
+    let mut rows = Vec::with_capacity(result.results.len());
+    for r in &result.results {
+        let mut row = vec![
+            r.term_id.clone(),
+            r.population_total.to_string(),
+            r.population_count.to_string(),
+            r.study_total.to_string(),
+            r.study_count.to_string(),
+            format!("{:e}", r.p_value),
+            format!("{:e}", r.adjusted_p_value),
+            format!("{:e}", min_p_value(&hgeom, r)?),
+        ];
+        if !classic {
+            row.extend([
+                format!("{:e}", r.lower_p_value),
+                format!("{:e}", r.two_sided_p_value),
+                r.odds_ratio.to_string(),
+                r.confidence_interval.0.to_string(),
+                r.confidence_interval.1.to_string(),
+            ]);
+        }
+        row.push(r.label.clone());
+        rows.push(row);
+    }
+    let (name, values) = ENRICHMENT_COLUMNS.split_last().unwrap();
+    let mut columns = values.to_vec();
+    if !classic {
+        columns.extend(FISHER_COLUMNS);
+    }
+    columns.push(name);
+    Ok(Table {
+        method: format!("{:?}", result.method),
+        caption: format!("{:?}, {:?}", result.method, result.correction),
+        columns,
+        rows,
+    })
+}
+
+pub fn mgsa_table(result: &MgsaResult) -> Table {
+    let rows = result
+        .results
+        .iter()
+        .map(|r| {
+            vec![
+                r.term_id.clone(),
+                result.population_total.to_string(),
+                r.population_count.to_string(),
+                result.study_total.to_string(),
+                r.study_count.to_string(),
+                r.marginal.to_string(),
+                r.label.clone(),
+            ]
+        })
+        .collect();
+    Table {
+        method: "MGSA".to_string(),
+        caption: "MGSA".to_string(),
+        columns: MGSA_COLUMNS.to_vec(),
+        rows,
+    }
+}
+
+/// One table per method of the run, in the classic layout for the TSV files
+pub fn tables(results: &AnalysisOutput, classic: bool) -> Result<Vec<Table>, String> {
+    match results {
+        AnalysisOutput::Enrichment(results) => results
+            .iter()
+            .map(|r| enrichment_table(r, classic))
+            .collect(),
+        AnalysisOutput::Mgsa(result) => Ok(vec![mgsa_table(result)]),
+    }
+}
+
+fn write_error(e: std::io::Error) -> String {
+    format!("Failed to write the results: {}", e)
+}
+
+/// Write the table as tab-separated values. As in the classic Ontologizer, the names are quoted.
+pub fn write_tsv(out: &mut dyn Write, table: &Table) -> Result<(), String> {
+    writeln!(out, "{}", table.columns.join("\t")).map_err(write_error)?;
+    for row in &table.rows {
+        if let Some((name, values)) = row.split_last() {
+            writeln!(out, "{}\t\"{}\"", values.join("\t"), name).map_err(write_error)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_json(out: &mut dyn Write, report: &Report) -> Result<(), String> {
+    serde_json::to_writer_pretty(&mut *out, report)
+        .map_err(|e| format!("Serialization error: {}", e))?;
+    writeln!(out).map_err(write_error)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; }
+th { background: #eee; }
+table.sortable th { cursor: pointer; }
+th[data-order=asc]::after { content: \" \\25B2\"; }
+th[data-order=desc]::after { content: \" \\25BC\"; }
+td.number { text-align: right; font-family: monospace; }";
+
+/// Sort a table by the column whose header was clicked, numerically if both values are numbers
+const SORT_SCRIPT: &str = "document.querySelectorAll(\"table.sortable\").forEach(function (table) {
+  var headers = table.querySelectorAll(\"th\");
+  headers.forEach(function (th, column) {
+    th.addEventListener(\"click\", function () {
+      var ascending = th.dataset.order !== \"asc\";
+      headers.forEach(function (other) { delete other.dataset.order; });
+      th.dataset.order = ascending ? \"asc\" : \"desc\";
+      var body = table.tBodies[0];
+      var rows = Array.prototype.slice.call(body.rows);
+      rows.sort(function (a, b) {
+        var x = a.cells[column].textContent, y = b.cells[column].textContent;
+        var order = isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+        return ascending ? order : -order;
+      });
+      rows.forEach(function (row) { body.appendChild(row); });
+    });
+  });
+});";
+
+/// Write a self-contained HTML page with the parameters, the input files and one sortable table per method
+pub fn write_html(out: &mut dyn Write, report: &Report) -> Result<(), String> {
+    let tables = tables(report.results, false)?;
+    let title = escape(&report.title);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        title, STYLE, title
+    );
+    html.push_str("<h2>Parameters</h2>\n<table>\n");
+    for parameter in &report.parameters {
+        html.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            escape(&parameter.key),
+            escape(&parameter.value)
+        ));
+    }
+    html.push_str("</table>\n");
+    if !report.inputs.is_empty() {
+        html.push_str(
+            "<h2>Input files</h2>\n<table>\n<tr><th>File</th><th>Bytes</th><th>CRC-32</th></tr>\n",
+        );
+        for input in &report.inputs {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td class=\"number\">{}</td><td>{}</td></tr>\n",
+                escape(&input.path),
+                input.bytes,
+                input.crc32
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    for table in &tables {
+        html.push_str(&format!(
+            "<h2>{}</h2>\n<table class=\"sortable\">\n<thead><tr>",
+            escape(&table.caption)
+        ));
+        for column in &table.columns {
+            html.push_str(&format!("<th>{}</th>", column));
+        }
+        html.push_str("</tr></thead>\n<tbody>\n");
+        for row in &table.rows {
+            html.push_str("<tr>");
+            for (i, value) in row.iter().enumerate() {
+                // the id and the name are text, all other columns are numbers
+                if i == 0 || i + 1 == row.len() {
+                    html.push_str(&format!("<td>{}</td>", escape(value)));
+                } else {
+                    html.push_str(&format!("<td class=\"number\">{}</td>", value));
+                }
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</tbody>\n</table>\n");
+    }
+    html.push_str(&format!(
+        "<script>\n{}\n</script>\n</body>\n</html>\n",
+        SORT_SCRIPT
+    ));
+    out.write_all(html.as_bytes()).map_err(write_error)
+}
+
+/// Write the report to `out`. Several TSV tables are separated by an empty line.
+pub fn write_report(
+    out: &mut dyn Write,
+    format: ExportFormat,
+    report: &Report,
+) -> Result<(), String> {
+    match format {
+        ExportFormat::Tsv => {
+            for (i, table) in tables(report.results, true)?.iter().enumerate() {
+                if i > 0 {
+                    writeln!(out).map_err(write_error)?;
+                }
+                write_tsv(out, table)?;
+            }
+        }
+        ExportFormat::Json => write_json(out, report)?,
+        ExportFormat::Html => write_html(out, report)?,
+    }
+    out.flush().map_err(write_error)
+}
+
+/// `path` with `suffix` added to the file name, e.g., `table-Elim.txt` for `table.txt`
+fn with_suffix(path: &str, suffix: &str) -> String {
+    let path = Path::new(path);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, suffix, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, suffix),
+    };
+    path.with_file_name(name).to_string_lossy().to_string()
+}
+
+fn create(path: &str) -> Result<BufWriter<File>, String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    Ok(BufWriter::new(file))
+}
+
+/// Save the report to `path` and return the paths of the written files. A TSV file holds a single
+/// table, so a run with several methods is saved as one file per method, named after the method.
+pub fn save_report(
+    path: &str,
+    format: ExportFormat,
+    report: &Report,
+) -> Result<Vec<String>, String> {
+    if format != ExportFormat::Tsv {
+        write_report(&mut create(path)?, format, report)?;
+        return Ok(vec![path.to_string()]);
+    }
+    let tables = tables(report.results, true)?;
+    let mut written = vec![];
+    for table in &tables {
+        let table_path = if tables.len() == 1 {
+            path.to_string()
+        } else {
+            with_suffix(path, &table.method)
+        };
+        let mut out = create(&table_path)?;
+        write_tsv(&mut out, table)?;
+        out.flush().map_err(write_error)?;
+        written.push(table_path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test {
+    use float_eq::float_eq;
+    use std::assert_eq;
+
+    use super::*;
+    use crate::enrichment::CalculationMethod;
+    use crate::mtc::MultipleTestingCorrection;
+
+    fn enrichment_result(term_id: &str, label: &str, population_count: usize) -> EnrichmentResult {
+        EnrichmentResult {
+            term_id: term_id.to_string(),
+            label: label.to_string(),
+            namespace: None,
+            population_total: 10,
+            population_count,
+            study_total: 3,
+            study_count: 2,
+            p_value: 0.25,
+            adjusted_p_value: 0.5,
+            lower_p_value: 0.75,
+            two_sided_p_value: 1.0,
+            odds_ratio: 2.5,
+            confidence_interval: (0.5, f64::INFINITY),
+        }
+    }
+
+    fn output() -> AnalysisOutput {
+        AnalysisOutput::Enrichment(vec![AnalysisResult {
+            method: CalculationMethod::Elim,
+            correction: MultipleTestingCorrection::Bonferroni,
+            population_total: 10,
+            study_total: 3,
+            unannotated_study_genes: vec![],
+            results: vec![
+                enrichment_result("GO:0000002", "A & B", 2),
+                enrichment_result("GO:0000003", "<C>", 5),
+            ],
+        }])
+    }
+
+    fn report(results: &AnalysisOutput) -> Report<'_> {
+        Report {
+            title: "Elim (3 genes)".to_string(),
+            parameters: vec![AnnotationStat::from_string("Study set", "study.txt")],
+            inputs: vec![InputFile {
+                path: "goa_human.gaf.gz".to_string(),
+                bytes: 42,
+                crc32: "cbf43926".to_string(),
+            }],
+            results,
+        }
+    }
+
+    #[test]
+    fn test_min_p_value() {
+        let hgeom = Hypergeometric::new(10);
+        // both genes of the term in the study set: C(2,2) C(8,1) / C(10,3) = 8 / 120
+        let p = min_p_value(&hgeom, &enrichment_result("GO:0000002", "A", 2)).unwrap();
+        assert!(float_eq!(8.0 / 120.0, p, abs <= 1e-12));
+        // all three study genes in the term: C(5,3) / C(10,3) = 10 / 120
+        let p = min_p_value(&hgeom, &enrichment_result("GO:0000003", "C", 5)).unwrap();
+        assert!(float_eq!(10.0 / 120.0, p, abs <= 1e-12));
+    }
+
+    #[test]
+    fn test_write_tsv() {
+        let results = output();
+        let mut out = vec![];
+        write_report(&mut out, ExportFormat::Tsv, &report(&results)).unwrap();
+        let tsv = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!(
+            "ID\tPop.total\tPop.term\tStudy.total\tStudy.term\tp\tp.adjusted\tp.min\tname",
+            lines[0]
+        );
+        assert!(lines[1].starts_with("GO:0000002\t10\t2\t3\t2\t2.5e-1\t5e-1\t"));
+        assert!(lines[1].ends_with("\t\"A & B\""));
+        assert_eq!(ENRICHMENT_COLUMNS.len(), lines[1].split('\t').count());
+    }
+
+    #[test]
+    fn test_write_html() {
+        let results = output();
+        let mut out = vec![];
+        write_report(&mut out, ExportFormat::Html, &report(&results)).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(html.contains("<h2>Elim, Bonferroni</h2>"));
+        assert!(html.contains("<td>A &amp; B</td>"));
+        assert!(
+            html.contains("<th>p.min</th><th>p.less</th><th>p.two.sided</th><th>odds.ratio</th>")
+        );
+        assert!(html.contains("<td class=\"number\">7.5e-1</td><td class=\"number\">1e0</td>"));
+        assert!(html.contains("<td class=\"number\">2.5</td><td class=\"number\">0.5</td><td class=\"number\">inf</td><td>A &amp; B</td>"));
+        assert!(html.contains("<td>&lt;C&gt;</td>"));
+        assert!(html.contains("<td>cbf43926</td>"));
+        assert!(html.contains("<th>Study set</th><td>study.txt</td>"));
+        assert!(html.contains("table class=\"sortable\""));
+    }
+
+    #[test]
+    fn test_write_json() {
+        let results = output();
+        let mut out = vec![];
+        write_report(&mut out, ExportFormat::Json, &report(&results)).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!("cbf43926", json["inputs"][0]["crc32"]);
+        assert_eq!("GO:0000003", json["results"][0]["results"][1]["term_id"]);
+    }
+
+    #[test]
+    fn test_with_suffix() {
+        assert_eq!("out/table-Elim.txt", with_suffix("out/table.txt", "Elim"));
+        assert_eq!("table-Weight", with_suffix("table", "Weight"));
+        assert_eq!(Ok(ExportFormat::Html), "HTML".parse());
+        assert!("xlsx".parse::<ExportFormat>().is_err());
+    }
+}
@@ -0,0 +1,357 @@
+//! Complete records of the GO Annotation File (GAF) format 2.2.
+//!
+//! See https://geneontology.org/docs/go-annotation-file-gaf-format-2.2/. A [`GafRecord`] keeps all
+//! 17 columns, so that annotations can be filtered and audited on their provenance. Formatting a record
+//! with `Display` gives back the original line. [`GafRecord::to_go_annot`] builds the compact
+//! [`GoAnnot`] that is used for the analysis.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::evidence::EvidenceCode;
+use crate::goannot::{Aspect, EviCode, GoAnnot, GoTermRelation, InputError, TermId};
+
+pub const GAF_COLUMNS: usize = 17;
+
+/// Date of an annotation (GAF: YYYYMMDD). Dates are ordered chronologically.
+///
+/// Serialized as YYYY-MM-DD, e.g., for the date range of an annotation filter.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AnnotationDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl AnnotationDate {
+    pub fn new(year: u16, month: u8, day: u8) -> Result<Self, InputError> {
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(InputError::ParsingError(format!(
+                "Invalid date: {:04}-{:02}-{:02}",
+                year, month, day
+            )));
+        }
+        Ok(AnnotationDate { year, month, day })
+    }
+
+    /// YYYY-MM-DD, as used by GPAD 2.0
+    pub fn to_iso(self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Accepts YYYYMMDD (GAF) and YYYY-MM-DD (GPAD 2.0)
+impl FromStr for AnnotationDate {
+    type Err = InputError;
+
+    fn from_str(s: &str) -> Result<Self, InputError> {
+        let digits: String = s.chars().filter(|c| *c != '-').collect();
+        let valid = s.is_ascii()
+            && (s.len() == 8 || (s.len() == 10 && &s[4..5] == "-" && &s[7..8] == "-"))
+            && digits.len() == 8
+            && digits.chars().all(|c| c.is_ascii_digit());
+        if !valid {
+            return Err(InputError::ParsingError(format!(
+                "Did not recognize '{}' as a date (YYYYMMDD).",
+                s
+            )));
+        }
+        // the slices are ASCII digits, so parsing cannot fail
+        AnnotationDate::new(
+            digits[0..4].parse().unwrap(),
+            digits[4..6].parse().unwrap(),
+            digits[6..8].parse().unwrap(),
+        )
+    }
+}
+
+impl TryFrom<String> for AnnotationDate {
+    type Error = InputError;
+
+    fn try_from(s: String) -> Result<Self, InputError> {
+        AnnotationDate::from_str(&s)
+    }
+}
+
+impl From<AnnotationDate> for String {
+    fn from(date: AnnotationDate) -> String {
+        date.to_iso()
+    }
+}
+
+impl fmt::Display for AnnotationDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}{:02}{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// The qualifier column: the relation between gene product and GO term, optionally negated with NOT
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Qualifier {
+    pub negated: bool,
+    pub relation: GoTermRelation,
+}
+
+impl FromStr for Qualifier {
+    type Err = InputError;
+
+    fn from_str(s: &str) -> Result<Self, InputError> {
+        let (negated, relation) = match s.strip_prefix("NOT|") {
+            Some(relation) => (true, relation),
+            None => (false, s),
+        };
+        Ok(Qualifier {
+            negated,
+            relation: GoTermRelation::from_str(relation)?,
+        })
+    }
+}
+
+impl fmt::Display for Qualifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            write!(f, "NOT|")?;
+        }
+        write!(f, "{}", self.relation)
+    }
+}
+
+/// One line of a GAF 2.2 file. Multi-valued columns are split at `|`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GafRecord {
+    /// e.g., UniProtKB
+    pub db: String,
+    pub db_object_id: String,
+    pub db_object_symbol: String,
+    pub qualifier: Qualifier,
+    pub go_id: TermId,
+    /// DB:Reference, e.g., PMID:2676709 or GO_REF:0000043
+    pub references: Vec<String>,
+    /// The exact evidence code, e.g., IDA
+    pub evidence_code: EvidenceCode,
+    /// With (or) From; the entries of one group are separated by commas
+    pub with_from: Vec<String>,
+    pub aspect: Aspect,
+    pub db_object_name: Option<String>,
+    pub db_object_synonyms: Vec<String>,
+    /// e.g., protein
+    pub db_object_type: String,
+    /// The taxon of the gene product, and for interactions the taxon of the other organism
+    pub taxa: Vec<TermId>,
+    pub date: AnnotationDate,
+    pub assigned_by: String,
+    /// Alternative extensions; each is a comma-separated conjunction such as part_of(CL:0000084)
+    pub annotation_extensions: Vec<String>,
+    /// e.g., UniProtKB:P12345-2 for an isoform
+    pub gene_product_form_id: Option<String>,
+    /// The ECO id of annotations from GPAD files, which may be more specific than the default ECO id
+    /// of `evidence_code`. GAF files only have the code.
+    pub eco_id: Option<String>,
+}
+
+/// Values of a multi-valued column, none for an empty column
+pub(crate) fn split_pipe(field: &str) -> Vec<String> {
+    if field.is_empty() {
+        return vec![];
+    }
+    field.split('|').map(str::to_string).collect()
+}
+
+fn optional(field: &str) -> Option<String> {
+    (!field.is_empty()).then(|| field.to_string())
+}
+
+fn required<'a>(field: &'a str, column: &str) -> Result<&'a str, InputError> {
+    if field.is_empty() {
+        return Err(InputError::ParsingError(format!(
+            "Required GAF column '{}' is empty",
+            column
+        )));
+    }
+    Ok(field)
+}
+
+impl FromStr for GafRecord {
+    type Err = InputError;
+
+    fn from_str(line: &str) -> Result<Self, InputError> {
+        let tokens: Vec<&str> = line.split('\t').collect();
+        if tokens.len() != GAF_COLUMNS {
+            return Err(InputError::MalformedLine(format!(
+                "GOA lines expected to have {} fields, but line had {} fields: {}",
+                GAF_COLUMNS,
+                tokens.len(),
+                line
+            )));
+        }
+        let taxa = required(tokens[12], "Taxon")?
+            .split('|')
+            .map(TermId::from_curie)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(GafRecord {
+            db: required(tokens[0], "DB")?.to_string(),
+            db_object_id: required(tokens[1], "DB Object ID")?.to_string(),
+            db_object_symbol: required(tokens[2], "DB Object Symbol")?.to_string(),
+            qualifier: Qualifier::from_str(tokens[3])?,
+            go_id: TermId::from_curie(tokens[4])?,
+            references: split_pipe(required(tokens[5], "DB:Reference")?),
+            evidence_code: EvidenceCode::from_str(tokens[6])?,
+            with_from: split_pipe(tokens[7]),
+            aspect: Aspect::from_str(tokens[8])?,
+            db_object_name: optional(tokens[9]),
+            db_object_synonyms: split_pipe(tokens[10]),
+            db_object_type: required(tokens[11], "DB Object Type")?.to_string(),
+            taxa,
+            date: AnnotationDate::from_str(tokens[13])?,
+            assigned_by: required(tokens[14], "Assigned By")?.to_string(),
+            annotation_extensions: split_pipe(tokens[15]),
+            gene_product_form_id: optional(tokens[16]),
+            eco_id: None,
+        })
+    }
+}
+
+impl fmt::Display for GafRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let taxa: Vec<&str> = self.taxa.iter().map(|t| t.value.as_str()).collect();
+        let columns = [
+            self.db.clone(),
+            self.db_object_id.clone(),
+            self.db_object_symbol.clone(),
+            self.qualifier.to_string(),
+            self.go_id.value.clone(),
+            self.references.join("|"),
+            self.evidence_code.to_string(),
+            self.with_from.join("|"),
+            self.aspect.to_string(),
+            self.db_object_name.clone().unwrap_or_default(),
+            self.db_object_synonyms.join("|"),
+            self.db_object_type.clone(),
+            taxa.join("|"),
+            self.date.to_string(),
+            self.assigned_by.clone(),
+            self.annotation_extensions.join("|"),
+            self.gene_product_form_id.clone().unwrap_or_default(),
+        ];
+        write!(f, "{}", columns.join("\t"))
+    }
+}
+
+impl GafRecord {
+    pub fn gene_product_id(&self) -> Result<TermId, InputError> {
+        TermId::new(&self.db, &self.db_object_id)
+    }
+
+    /// The evidence code group used for the analysis
+    pub fn evidence_group(&self) -> EviCode {
+        self.evidence_code.group()
+    }
+
+    /// The compact annotation used for the analysis. Negated annotations are returned as
+    /// [`InputError::NegatedAnnotation`].
+    pub fn to_go_annot(&self) -> Result<GoAnnot, InputError> {
+        if self.qualifier.negated {
+            return Err(InputError::NegatedAnnotation);
+        }
+        self.annotation()
+    }
+
+    /// The annotation of a negated (NOT) record, with the relation that is negated, e.g.,
+    /// `NOT|involved_in` gives an annotation with [`GoTermRelation::InvolvedIn`]. Returns None for
+    /// records that are not negated.
+    pub fn to_negated_annot(&self) -> Option<Result<GoAnnot, InputError>> {
+        self.qualifier.negated.then(|| self.annotation())
+    }
+
+    fn annotation(&self) -> Result<GoAnnot, InputError> {
+        let mut annot = GoAnnot::new(
+            self.gene_product_id()?,
+            self.db_object_symbol.as_str(),
+            self.qualifier.relation.clone(),
+            self.go_id.clone(),
+            self.evidence_code,
+            self.aspect.clone(),
+        );
+        annot.eco_id = self.eco_id.clone();
+        Ok(annot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+
+    use super::*;
+
+    const IEA_LINE: &str = "UniProtKB\tA0A024RBG1\tNUDT4B\tenables\tGO:0003723\tGO_REF:0000043\tIEA\tUniProtKB-KW:KW-0694\tF\tDiphosphoinositol polyphosphate phosphohydrolase NUDT4B\tNUDT4B\tprotein\ttaxon:9606\t20240114\tUniProt\t\t";
+
+    const NEGATED_LINE: &str = "UniProtKB\tP04637\tTP53\tNOT|involved_in\tGO:0006915\tPMID:12345|GO_REF:0000024\tIMP\tUniProtKB:Q9H3D4|UniProtKB:Q8N726\tP\tCellular tumor antigen p53\tTP53|P53\tprotein\ttaxon:9606|taxon:10090\t20201231\tMGI\toccurs_in(CL:0000057),part_of(GO:0001234)|has_input(UniProtKB:P12345)\tUniProtKB:P04637-2";
+
+    #[test]
+    fn test_round_trip() {
+        for line in [IEA_LINE, NEGATED_LINE] {
+            let record = GafRecord::from_str(line).unwrap();
+            assert_eq!(line, record.to_string());
+        }
+    }
+
+    #[test]
+    fn test_typed_fields() {
+        let record = GafRecord::from_str(NEGATED_LINE).unwrap();
+        assert!(record.qualifier.negated);
+        assert_eq!(GoTermRelation::InvolvedIn, record.qualifier.relation);
+        assert_eq!(2, record.references.len());
+        assert_eq!(vec!["TP53", "P53"], record.db_object_synonyms);
+        assert_eq!("taxon:10090", record.taxa[1].value);
+        assert_eq!(AnnotationDate::new(2020, 12, 31).unwrap(), record.date);
+        assert_eq!(2, record.annotation_extensions.len());
+        assert_eq!(
+            Some("UniProtKB:P04637-2".to_string()),
+            record.gene_product_form_id
+        );
+        let record = GafRecord::from_str(IEA_LINE).unwrap();
+        assert!(record.annotation_extensions.is_empty());
+        assert_eq!(None, record.gene_product_form_id);
+        assert_eq!("2024-01-14", record.date.to_iso());
+        let json = serde_json::to_string(&record.date).unwrap();
+        assert_eq!("\"2024-01-14\"", json);
+        assert_eq!(record.date, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn test_to_go_annot() {
+        let annot = GafRecord::from_str(IEA_LINE)
+            .unwrap()
+            .to_go_annot()
+            .unwrap();
+        assert_eq!("UniProtKB:A0A024RBG1", annot.gene_product_id.value);
+        assert_eq!(EvidenceCode::IEA, annot.evidence_code);
+        assert_eq!(EviCode::IEA, annot.evidence_group());
+        let negated = GafRecord::from_str(NEGATED_LINE).unwrap().to_go_annot();
+        assert!(matches!(negated, Err(InputError::NegatedAnnotation)));
+        let negated = GafRecord::from_str(NEGATED_LINE)
+            .unwrap()
+            .to_negated_annot()
+            .unwrap()
+            .unwrap();
+        assert_eq!(GoTermRelation::InvolvedIn, negated.relation);
+        assert_eq!("GO:0006915", negated.go_id.value);
+        assert!(GafRecord::from_str(IEA_LINE)
+            .unwrap()
+            .to_negated_annot()
+            .is_none());
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        assert!(GafRecord::from_str("UniProtKB\tP04637").is_err());
+        let bad_date = IEA_LINE.replace("20240114", "2024011");
+        assert!(GafRecord::from_str(&bad_date).is_err());
+        let no_taxon = IEA_LINE.replace("taxon:9606", "");
+        assert!(GafRecord::from_str(&no_taxon).is_err());
+        assert!(AnnotationDate::from_str("2024-13-01").is_err());
+        assert!(serde_json::from_str::<AnnotationDate>("\"2024-02-30x\"").is_err());
+    }
+}
@@ -0,0 +1,659 @@
+use flate2::bufread::MultiGzDecoder;
+use num::Integer;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::annotation_stats::{annotation_set_stats, AnnotationSummary};
+use crate::diagnostics::{DiagnosticCategory, ParseDiagnostics, ParseMode};
+use crate::evidence::EvidenceCode;
+use crate::gaf::GafRecord;
+use crate::jobs::Job;
+use crate::session::{with_id, AnnotationSet, InputFile, Session};
+
+#[derive(Debug)]
+pub enum InputError {
+    /// A NOT record was converted with [`GafRecord::to_go_annot`]; negated records are kept as
+    /// negated annotations (see [`GafRecord::to_negated_annot`])
+    NegatedAnnotation,
+    /// Wrong number of fields
+    MalformedLine(String),
+    UnknownRelation(String),
+    UnknownEvidenceCode(String),
+    InvalidCurie(String),
+    InvalidAspect(String),
+    /// Another error type
+    ParsingError(String),
+    // Add other error kinds as needed
+}
+
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            InputError::NegatedAnnotation => write!(f, "Negated annotation detected"),
+            InputError::MalformedLine(ref s) => write!(f, "Malformed line: {}", s),
+            InputError::UnknownRelation(ref s)
+            | InputError::UnknownEvidenceCode(ref s)
+            | InputError::InvalidCurie(ref s)
+            | InputError::InvalidAspect(ref s)
+            | InputError::ParsingError(ref s) => write!(f, "Parsing error: {}", s),
+        }
+    }
+}
+
+impl InputError {
+    /// The category under which a line with this error is reported
+    pub fn category(&self) -> DiagnosticCategory {
+        match self {
+            InputError::MalformedLine(_) => DiagnosticCategory::FieldCount,
+            InputError::UnknownRelation(_) => DiagnosticCategory::UnknownRelation,
+            InputError::UnknownEvidenceCode(_) => DiagnosticCategory::UnknownEvidenceCode,
+            InputError::InvalidCurie(_) => DiagnosticCategory::InvalidCurie,
+            InputError::InvalidAspect(_) => DiagnosticCategory::InvalidAspect,
+            InputError::NegatedAnnotation | InputError::ParsingError(_) => {
+                DiagnosticCategory::Other
+            }
+        }
+    }
+}
+
+/// Gene product to GO term relations
+/// enables links a gene product to a Molecular Function it executes.
+/// contributes to links a gene product to a Molecular Function executed by a macromolecular complex, in which the Molecular Function cannot be ascribed to an individual subunit of that complex. Only the complex subunits required for the Molecular Function are annotated to the Molecular Function term with ‘contributes to’.
+/// Relations between a gene product and a Biological Process:
+/// involved in links a gene product and a Biological Process in which the gene product’s Molecular Function plays an integral role.
+/// acts upstream of or within links a gene product and a Biological Process when the mechanism relating the gene product’s activity to the Biological Process is not known.
+/// Relations between a gene product and a Cellular Component:
+/// is active in links a gene product to the cellular location in which it enables its Molecular Function.
+/// located in links a gene product and the Cellular Component, specifically a cellular anatomical anatomy or virion component, in which a gene product has been detected.
+/// part of links a gene product and a protein-containing complex.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum GoTermRelation {
+    Enables,
+    ContributesTo,
+    InvolvedIn,
+    ActsUpstreamOf,
+    ActsWithin,
+    ActsUpstreamOfOrWithin,
+    ActsUpstreamOfNegativeEffect,
+    ActsUpstreamOfPositiveEffect,
+    ActsUpstreamOfOrWithinNegativeEffect,
+    ActsUpstreamOfOrWithinPositiveEffect,
+    IsActiveIn,
+    LocatedIn,
+    ColocalizesWith,
+    PartOf,
+}
+
+impl std::fmt::Display for GoTermRelation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let relation_str = match self {
+            GoTermRelation::Enables => "enables",
+            GoTermRelation::ContributesTo => "contributes_to",
+            GoTermRelation::InvolvedIn => "involved_in",
+            GoTermRelation::ActsUpstreamOf => "acts_upstream_of",
+            GoTermRelation::ActsWithin => "acts_within",
+            GoTermRelation::IsActiveIn => "is_active_in",
+            GoTermRelation::ActsUpstreamOfOrWithin => "acts_upstream_of_or_within",
+            GoTermRelation::ActsUpstreamOfNegativeEffect => "acts_upstream_of_negative_effect",
+            GoTermRelation::ActsUpstreamOfPositiveEffect => "acts_upstream_of_positive_effect",
+            GoTermRelation::ActsUpstreamOfOrWithinNegativeEffect => {
+                "acts_upstream_of_or_within_negative_effect"
+            }
+            GoTermRelation::ActsUpstreamOfOrWithinPositiveEffect => {
+                "acts_upstream_of_or_within_positive_effect"
+            }
+            GoTermRelation::LocatedIn => "located_in",
+            GoTermRelation::PartOf => "part_of",
+            GoTermRelation::ColocalizesWith => "colocalizes_with",
+        };
+        write!(f, "{}", relation_str)
+    }
+}
+
+impl FromStr for GoTermRelation {
+    type Err = InputError;
+
+    fn from_str(s: &str) -> Result<Self, InputError> {
+        if s.starts_with("NOT") {
+            return Err(InputError::NegatedAnnotation);
+        }
+        match s {
+            "enables" => Ok(GoTermRelation::Enables),
+            "contributes_to" => Ok(GoTermRelation::ContributesTo),
+            "involved_in" => Ok(GoTermRelation::InvolvedIn),
+            "located_in" => Ok(GoTermRelation::LocatedIn),
+            "acts_upstream_of" => Ok(GoTermRelation::ActsUpstreamOf),
+            "acts_within" => Ok(GoTermRelation::ActsWithin),
+            "acts_upstream_of_or_within" => Ok(GoTermRelation::ActsUpstreamOfOrWithin),
+            "acts_upstream_of_negative_effect" => Ok(GoTermRelation::ActsUpstreamOfNegativeEffect),
+            "acts_upstream_of_positive_effect" => Ok(GoTermRelation::ActsUpstreamOfPositiveEffect),
+            "acts_upstream_of_or_within_negative_effect" => {
+                Ok(GoTermRelation::ActsUpstreamOfOrWithinNegativeEffect)
+            }
+            "acts_upstream_of_or_within_positive_effect" => {
+                Ok(GoTermRelation::ActsUpstreamOfOrWithinPositiveEffect)
+            }
+            "is_active_in" => Ok(GoTermRelation::IsActiveIn),
+            "part_of" => Ok(GoTermRelation::PartOf),
+            "colocalizes_with" => Ok(GoTermRelation::ColocalizesWith),
+            _ => Err(InputError::UnknownRelation(format!(
+                "Did not recognize '{}' as a GOA relation.",
+                s
+            ))),
+        }
+    }
+}
+
+/// The groups of evidence codes, see [`EvidenceCode::group`] for the exact codes
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum EviCode {
+    EXP,           // inferred from experiment
+    HTP,           //  Inferred from High Throughput Experiment
+    PHYLO,         // Phylogenetically inferred annotations
+    COMPUTATIONAL, // computational analysis evidence codes i
+    AUTHOR,        // Author statement evidence
+    IC,            // Curator statement
+    ND,            // No biological Data available
+    IEA,           // Inferred from Electronic Annotation (IEA)
+}
+
+impl FromStr for EviCode {
+    type Err = InputError;
+
+    fn from_str(s: &str) -> Result<Self, InputError> {
+        EvidenceCode::from_str(s).map(|code| code.group())
+    }
+}
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub enum Aspect {
+    F,
+    P,
+    C,
+}
+
+impl std::fmt::Display for Aspect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let aspect_str = match self {
+            Aspect::F => "F",
+            Aspect::P => "P",
+            Aspect::C => "C",
+        };
+        write!(f, "{}", aspect_str)
+    }
+}
+
+impl FromStr for Aspect {
+    type Err = InputError;
+
+    fn from_str(s: &str) -> Result<Self, InputError> {
+        match s {
+            "F" => Ok(Aspect::F),
+            "P" => Ok(Aspect::P),
+            "C" => Ok(Aspect::C),
+            _ => Err(InputError::InvalidAspect(format!(
+                "Did not recognize '{}' as Aspect.",
+                s
+            ))),
+        }
+    }
+}
+
+/// Simple structure to represent a Gene Ontology or other Term identifier
+///
+/// We do not care much about the format of the ids, only that they are valid CURIEs. The local id may
+/// contain colons, e.g., MGI:MGI:97490 is the id MGI:97490 with the prefix MGI.
+#[derive(Serialize, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TermId {
+    pub value: String,
+}
+
+impl TermId {
+    pub fn new(prfx: &str, id: &str) -> Result<Self, InputError> {
+        if prfx.contains(":") {
+            return Err(InputError::InvalidCurie(format!(
+                "Prefix not allowed to contain colon - '{}'",
+                prfx
+            )));
+        }
+        Ok(TermId {
+            value: format!("{}:{}", prfx, id),
+        })
+    }
+
+    /// Split the CURIE at the first colon into prefix and id
+    pub fn from_curie(curie: &str) -> Result<Self, InputError> {
+        let (prfx, id) = curie.split_once(':').ok_or_else(|| {
+            InputError::InvalidCurie(format!("CURIE expected to contain a colon: {}", curie))
+        })?;
+        TermId::new(prfx, id)
+    }
+}
+
+/// A Gene Ontology Annotation, corresponding to one line of the GOA file
+///
+/// We only store a subset of the information that is important for the analysis
+#[derive(Clone)]
+pub struct GoAnnot {
+    pub gene_product_id: TermId,
+    pub gene_product_symbol: String,
+    pub relation: GoTermRelation,
+    pub go_id: TermId,
+    /// The exact evidence code, e.g., IDA
+    pub evidence_code: EvidenceCode,
+    pub aspect: Aspect,
+    /// The precise ECO id of annotations from GPAD files (see [`crate::gaf::GafRecord::eco_id`])
+    pub eco_id: Option<String>,
+}
+
+impl GoAnnot {
+    pub fn new<T: Into<String>>(
+        term: TermId,
+        symbol: T,
+        relation: GoTermRelation,
+        gene_ontology_id: TermId,
+        evicode: EvidenceCode,
+        aspect: Aspect,
+    ) -> Self {
+        GoAnnot {
+            gene_product_id: term,
+            gene_product_symbol: symbol.into(),
+            relation,
+            go_id: gene_ontology_id,
+            evidence_code: evicode,
+            aspect,
+            eco_id: None,
+        }
+    }
+
+    pub fn evidence_group(&self) -> EviCode {
+        self.evidence_code.group()
+    }
+}
+
+/// To be used for serialization to display the most interesting characteristics of the annotation as a table
+#[derive(Clone, Debug, Serialize)]
+pub struct AnnotationStat {
+    pub key: String,
+    pub value: String,
+}
+
+impl AnnotationStat {
+    pub fn from_string(item: &str, val: &str) -> Self {
+        AnnotationStat {
+            key: item.to_string(),
+            value: val.to_string(),
+        }
+    }
+
+    pub fn from_int<T: Integer + std::fmt::Display>(item: &str, val: T) -> Self {
+        AnnotationStat {
+            key: item.to_string(),
+            value: format!("{}", val),
+        }
+    }
+}
+
+/// The first two bytes of a gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Wrap `reader` in a streaming gzip decoder if its content starts with the gzip magic bytes
+pub fn decompressing_reader<R: BufRead + Send + 'static>(
+    mut reader: R,
+) -> std::io::Result<Box<dyn BufRead + Send>> {
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        // GOA downloads may consist of several gzip members
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Open a plain or gzip-compressed annotation file such as goa_human.gaf.gz.
+///
+/// Compression is detected from the content rather than the file name, and the file is decompressed
+/// while it is read, so multi-gigabyte files never need to be unpacked. The bytes read from the file
+/// are the progress of `job`, which also computes the CRC-32 of the file.
+pub fn open_annotation_file(path: &str, job: &Job) -> Result<Box<dyn BufRead + Send>, String> {
+    decompressing_reader(BufReader::new(job.open(path)?))
+        .map_err(|e| format!("Error reading file: {}", e))
+}
+
+/// Process a line in go-annotation-file-gaf-format-2.2
+///
+/// Returns the record and the annotation built from it, which is a negated annotation for NOT records.
+/// Lines that cannot be used for the analysis are errors.
+fn process_annotation_line(line: &str) -> Result<(GafRecord, GoAnnot), InputError> {
+    let record = GafRecord::from_str(line)?;
+    let annotation = match record.to_negated_annot() {
+        Some(negated) => negated?,
+        None => record.to_go_annot()?,
+    };
+    Ok((record, annotation))
+}
+
+/// Negated annotations for which the same gene product is also annotated to the same term with the
+/// same relation without NOT, e.g., because two groups came to different conclusions.
+pub fn negation_conflicts<'a>(annotations: &[GoAnnot], negated: &'a [GoAnnot]) -> Vec<&'a GoAnnot> {
+    let positive: HashSet<(&TermId, &TermId, &GoTermRelation)> = annotations
+        .iter()
+        .map(|a| (&a.gene_product_id, &a.go_id, &a.relation))
+        .collect();
+    negated
+        .iter()
+        .filter(|n| positive.contains(&(&n.gene_product_id, &n.go_id, &n.relation)))
+        .collect()
+}
+
+/// Split `records` into the annotations that are used for the analysis and the negated annotations.
+/// Records that cannot be converted are recorded in `diagnostics`.
+pub fn compact_annotations(
+    records: &[GafRecord],
+    diagnostics: &mut ParseDiagnostics,
+) -> (Vec<GoAnnot>, Vec<GoAnnot>) {
+    let mut annotations = vec![];
+    let mut negated_annotations = vec![];
+    for record in records {
+        let annotation = match record.to_negated_annot() {
+            Some(negated) => negated.map(|a| negated_annotations.push(a)),
+            None => record.to_go_annot().map(|a| annotations.push(a)),
+        };
+        if let Err(e) = annotation {
+            diagnostics.add_error(None, &record.to_string(), &e);
+        }
+    }
+    (annotations, negated_annotations)
+}
+
+/// The annotation set of `records` that were not converted when they were read, e.g., the records of
+/// a GPAD file. Records that cannot be converted are recorded in `diagnostics`, but kept in the set.
+pub fn annotation_set(
+    records: Vec<GafRecord>,
+    diagnostics: &mut ParseDiagnostics,
+) -> AnnotationSet {
+    let (annotations, negated) = compact_annotations(&records, diagnostics);
+    AnnotationSet {
+        records: Arc::new(records),
+        annotations,
+        negated,
+    }
+}
+
+/// Response of the commands that load annotations
+#[derive(Serialize)]
+struct LoadedAnnotations {
+    #[serde(flatten)]
+    summary: AnnotationSummary,
+    diagnostics: ParseDiagnostics,
+}
+
+/// Add the annotation set as a new dataset of the session and return its id and the statistics that
+/// are shown on the setup page, together with the diagnostics of the lines that were skipped.
+///
+/// All annotation formats end up here, so that the analysis does not depend on the format of the input.
+/// In strict mode, nothing is stored if too many lines could not be parsed. `inputs` are the files the
+/// records were read from.
+pub fn store_records(
+    session: &mut Session,
+    name: &str,
+    inputs: Vec<InputFile>,
+    annotation_set: AnnotationSet,
+    version: Option<&str>,
+    diagnostics: ParseDiagnostics,
+    mode: ParseMode,
+) -> Result<String, String> {
+    let mut annotation_stats: Vec<AnnotationStat> = vec![];
+    if let Some(version) = version {
+        annotation_stats.push(AnnotationStat::from_string("version", version));
+    }
+    diagnostics.check(mode)?;
+    annotation_stats.push(AnnotationStat::from_int(
+        "Skipped lines",
+        diagnostics.errors,
+    ));
+    let mut summary = annotation_set_stats(
+        &annotation_set.records,
+        &annotation_set.annotations,
+        &annotation_set.negated,
+    );
+    summary.stats.splice(0..0, annotation_stats);
+    let id = session.add_annotations(name, annotation_set, inputs);
+    let loaded = LoadedAnnotations {
+        summary,
+        diagnostics,
+    };
+    with_id(id, &loaded)
+}
+
+/// Parse the lines of a GAF file, recording the lines that cannot be used in `diagnostics`.
+///
+/// Returns the annotation set and the first `!date-generated` header. Each line is converted to an
+/// annotation while it is parsed, so that errors are reported with the line number.
+fn parse_gaf<R: BufRead>(
+    reader: R,
+    diagnostics: &mut ParseDiagnostics,
+    job: &Job,
+) -> Result<(AnnotationSet, Option<String>), String> {
+    let mut records = vec![];
+    let mut annotations = vec![];
+    let mut negated = vec![];
+    let mut version = None; // The GOA format has multiple entries for date-generated. We only want the first
+    for (i, line) in reader.lines().enumerate() {
+        let content = line.map_err(|e| format!("Error reading file: {}", e))?;
+        if let Some(header) = content.strip_prefix('!') {
+            if let Some(date) = header.strip_prefix("date-generated:") {
+                version.get_or_insert_with(|| date.trim().to_string());
+            }
+        } else if !content.trim().is_empty() {
+            diagnostics.lines += 1;
+            match process_annotation_line(&content) {
+                Ok((record, annotation)) => {
+                    if record.qualifier.negated {
+                        negated.push(annotation);
+                    } else {
+                        annotations.push(annotation);
+                    }
+                    records.push(record);
+                }
+                Err(e) => diagnostics.add_error(Some(i + 1), &content, &e),
+            }
+            job.parsed_lines(diagnostics.lines)?;
+        }
+    }
+    let annotation_set = AnnotationSet {
+        records: Arc::new(records),
+        annotations,
+        negated,
+    };
+    Ok((annotation_set, version))
+}
+
+/// Read a GAF 2.2 file, which may be gzip-compressed (e.g., goa_human.gaf.gz).
+///
+/// Returns the records with their annotations and the date the file was generated. Lines that cannot
+/// be parsed are skipped and recorded in `diagnostics`.
+pub fn read_gaf(
+    path: &str,
+    diagnostics: &mut ParseDiagnostics,
+    job: &Job,
+) -> Result<(AnnotationSet, Option<String>), String> {
+    let reader = open_annotation_file(path, job)?;
+    parse_gaf(reader, diagnostics, job)
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_valid_evidence_codes() {
+        let tests = vec![
+            ("EXP", EviCode::EXP),
+            ("IDA", EviCode::EXP),
+            ("IPI", EviCode::EXP),
+            ("IMP", EviCode::EXP),
+            ("IEP", EviCode::EXP),
+            ("HTP", EviCode::HTP),
+            ("HDA", EviCode::HTP),
+            ("HMP", EviCode::HTP),
+            ("HGI", EviCode::HTP),
+            ("HEP", EviCode::HTP),
+            ("IBA", EviCode::PHYLO),
+            ("IBD", EviCode::PHYLO),
+            ("IKR", EviCode::PHYLO),
+            ("IRD", EviCode::PHYLO),
+            ("ISS", EviCode::COMPUTATIONAL),
+            ("ISO", EviCode::COMPUTATIONAL),
+            ("ISA", EviCode::COMPUTATIONAL),
+            ("ISM", EviCode::COMPUTATIONAL),
+            ("ISS", EviCode::COMPUTATIONAL),
+            ("ISS", EviCode::COMPUTATIONAL),
+            ("TAS", EviCode::AUTHOR),
+            ("NAS", EviCode::AUTHOR),
+            ("IC", EviCode::IC),
+            ("ND", EviCode::ND),
+            ("IEA", EviCode::IEA),
+        ];
+        for test in tests {
+            let ecode = EviCode::from_str(test.0);
+            assert!(ecode.is_ok());
+            assert_eq!(ecode.unwrap(), test.1);
+        }
+    }
+
+    /// Make sure we get an error with an invalid evidence code
+    #[test]
+    fn test_invalid_evidence_code() {
+        let ecode = EviCode::from_str("something");
+        assert!(ecode.is_err());
+        match ecode {
+            Err(e) => {
+                assert_eq!(
+                    e.to_string(),
+                    "Parsing error: Did not recognize 'something' as EvidenceCode.".to_string()
+                );
+            }
+            Ok(_) => panic!("Expected an error, but got Ok."),
+        }
+    }
+
+    #[test]
+    fn test_term_id() {
+        assert_eq!(
+            "GO:0006915",
+            TermId::from_curie("GO:0006915").unwrap().value
+        );
+        let mgi = TermId::from_curie("MGI:MGI:97490").unwrap();
+        assert_eq!(TermId::new("MGI", "MGI:97490").unwrap(), mgi);
+        assert!(TermId::from_curie("GO0006915").is_err());
+        assert!(TermId::new("MGI:MGI", "97490").is_err());
+    }
+
+    #[test]
+    fn test_gzip_detection() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let content = "!gaf-version: 2.2\nline 1\nline 2\n";
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(content.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(GZIP_MAGIC, compressed[0..2]);
+        for bytes in [compressed, content.as_bytes().to_vec()] {
+            let reader = decompressing_reader(std::io::Cursor::new(bytes)).unwrap();
+            let lines: Vec<String> = reader.lines().map(Result::unwrap).collect();
+            assert_eq!(vec!["!gaf-version: 2.2", "line 1", "line 2"], lines);
+        }
+    }
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let valid = "UniProtKB\tA0A024RBG1\tNUDT4B\tenables\tGO:0003723\tGO_REF:0000043\tIEA\tUniProtKB-KW:KW-0694\tF\tDiphosphoinositol polyphosphate phosphohydrolase NUDT4B\tNUDT4B\tprotein\ttaxon:9606\t20240114\tUniProt\t\t";
+        let content = [
+            "!gaf-version: 2.2",
+            valid,
+            "UniProtKB\tA0A024RBG1",
+            &valid.replace("\tIEA\t", "\tXYZ\t"),
+            &valid.replace("\tF\t", "\tQ\t"),
+            &valid.replace("GO:0003723", "GO0003723"),
+        ]
+        .join("\n");
+        let mut diagnostics = ParseDiagnostics::new();
+        let (annotation_set, _) =
+            parse_gaf(content.as_bytes(), &mut diagnostics, &Job::default()).unwrap();
+        assert_eq!(1, annotation_set.records.len());
+        assert_eq!(1, annotation_set.annotations.len());
+        assert_eq!(5, diagnostics.lines);
+        assert_eq!(4, diagnostics.errors);
+        let categories: Vec<DiagnosticCategory> =
+            diagnostics.samples.iter().map(|d| d.category).collect();
+        assert_eq!(
+            vec![
+                DiagnosticCategory::FieldCount,
+                DiagnosticCategory::UnknownEvidenceCode,
+                DiagnosticCategory::InvalidAspect,
+                DiagnosticCategory::InvalidCurie
+            ],
+            categories
+        );
+        let line_numbers: Vec<Option<usize>> =
+            diagnostics.samples.iter().map(|d| d.line_number).collect();
+        assert_eq!(vec![Some(3), Some(4), Some(5), Some(6)], line_numbers);
+        assert!(diagnostics
+            .check(ParseMode::Strict {
+                max_error_rate: 0.5
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_date_generated() {
+        let content = "!gaf-version: 2.2\n!date-generated: 2024-06-01\n!date-generated: 2024-06-02T10:00\n!date-generated:\n";
+        let (_, version) = parse_gaf(
+            content.as_bytes(),
+            &mut ParseDiagnostics::new(),
+            &Job::default(),
+        )
+        .unwrap();
+        assert_eq!(Some("2024-06-01".to_string()), version);
+        // an empty value must not panic
+        let (_, version) = parse_gaf(
+            "!date-generated:\n".as_bytes(),
+            &mut ParseDiagnostics::new(),
+            &Job::default(),
+        )
+        .unwrap();
+        assert_eq!(Some(String::new()), version);
+    }
+
+    #[test]
+    fn test_negation_conflicts() {
+        let annot = |gene: &str, relation: GoTermRelation| {
+            GoAnnot::new(
+                TermId::new("UniProtKB", gene).unwrap(),
+                gene,
+                relation,
+                TermId::from_curie("GO:0006915").unwrap(),
+                EvidenceCode::IDA,
+                Aspect::P,
+            )
+        };
+        let annotations = vec![
+            annot("P1", GoTermRelation::InvolvedIn),
+            annot("P2", GoTermRelation::ActsUpstreamOf),
+        ];
+        let negated = vec![
+            annot("P1", GoTermRelation::InvolvedIn),
+            annot("P2", GoTermRelation::InvolvedIn),
+            annot("P3", GoTermRelation::InvolvedIn),
+        ];
+        let conflicts = negation_conflicts(&annotations, &negated);
+        assert_eq!(1, conflicts.len());
+        assert_eq!("P1", conflicts[0].gene_product_symbol);
+    }
+}
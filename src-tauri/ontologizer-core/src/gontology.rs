@@ -0,0 +1,951 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufReader, Read};
+use std::str::FromStr;
+
+use crate::goannot::{AnnotationStat, TermId};
+use crate::jobs::Job;
+use crate::obo_parser::parse_obo;
+use crate::session::{with_id, InputFile, Session};
+
+#[derive(Debug)]
+pub enum OntologyError {
+    IoError(String),
+    ParsingError(String),
+    UnknownTerm(String),
+}
+
+impl std::fmt::Display for OntologyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            OntologyError::IoError(ref s) => write!(f, "I/O error: {}", s),
+            OntologyError::ParsingError(ref s) => write!(f, "Parsing error: {}", s),
+            OntologyError::UnknownTerm(ref s) => write!(f, "Unknown term: {}", s),
+        }
+    }
+}
+
+/// The three sub-ontologies of GO
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub enum Namespace {
+    BiologicalProcess,
+    MolecularFunction,
+    CellularComponent,
+}
+
+impl std::fmt::Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ns = match self {
+            Namespace::BiologicalProcess => "biological_process",
+            Namespace::MolecularFunction => "molecular_function",
+            Namespace::CellularComponent => "cellular_component",
+        };
+        write!(f, "{}", ns)
+    }
+}
+
+impl FromStr for Namespace {
+    type Err = OntologyError;
+
+    fn from_str(s: &str) -> Result<Self, OntologyError> {
+        match s {
+            "biological_process" => Ok(Namespace::BiologicalProcess),
+            "molecular_function" => Ok(Namespace::MolecularFunction),
+            "cellular_component" => Ok(Namespace::CellularComponent),
+            _ => Err(OntologyError::ParsingError(format!(
+                "Did not recognize '{}' as GO namespace.",
+                s
+            ))),
+        }
+    }
+}
+
+/// Relations between two GO terms.
+///
+/// Only `is_a` and `part_of` obey the true path rule and are followed when computing
+/// ancestors and descendants. The other relations are kept so that they can be displayed
+/// and counted.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+pub enum TermRelation {
+    IsA,
+    PartOf,
+    Regulates,
+    NegativelyRegulates,
+    PositivelyRegulates,
+    Other(String),
+}
+
+impl TermRelation {
+    /// Map the relation ids used in OBO files (e.g., part_of) and the IRIs used in
+    /// obographs (e.g., http://purl.obolibrary.org/obo/BFO_0000050) to a relation
+    pub fn from_identifier(s: &str) -> Self {
+        match s {
+            "is_a" => TermRelation::IsA,
+            "part_of" | "BFO:0000050" | "http://purl.obolibrary.org/obo/BFO_0000050" => {
+                TermRelation::PartOf
+            }
+            "regulates" | "RO:0002211" | "http://purl.obolibrary.org/obo/RO_0002211" => {
+                TermRelation::Regulates
+            }
+            "negatively_regulates" | "RO:0002212" | "http://purl.obolibrary.org/obo/RO_0002212" => {
+                TermRelation::NegativelyRegulates
+            }
+            "positively_regulates" | "RO:0002213" | "http://purl.obolibrary.org/obo/RO_0002213" => {
+                TermRelation::PositivelyRegulates
+            }
+            other => TermRelation::Other(other.to_string()),
+        }
+    }
+
+    /// true if annotations propagate along this relation (true path rule)
+    pub fn propagates(&self) -> bool {
+        matches!(self, TermRelation::IsA | TermRelation::PartOf)
+    }
+}
+
+impl std::fmt::Display for TermRelation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TermRelation::IsA => write!(f, "is_a"),
+            TermRelation::PartOf => write!(f, "part_of"),
+            TermRelation::Regulates => write!(f, "regulates"),
+            TermRelation::NegativelyRegulates => write!(f, "negatively_regulates"),
+            TermRelation::PositivelyRegulates => write!(f, "positively_regulates"),
+            TermRelation::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum SynonymScope {
+    Exact,
+    Broad,
+    Narrow,
+    Related,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Synonym {
+    pub value: String,
+    pub scope: SynonymScope,
+}
+
+/// One GO term together with the metadata we need for display and for resolving obsolete ids
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GoTerm {
+    pub id: TermId,
+    pub label: String,
+    pub namespace: Option<Namespace>,
+    pub definition: Option<String>,
+    pub is_obsolete: bool,
+    pub alt_ids: Vec<TermId>,
+    pub replaced_by: Vec<TermId>,
+    pub consider: Vec<TermId>,
+    pub synonyms: Vec<Synonym>,
+}
+
+impl GoTerm {
+    pub fn new<T: Into<String>>(id: TermId, label: T) -> Self {
+        GoTerm {
+            id,
+            label: label.into(),
+            namespace: None,
+            definition: None,
+            is_obsolete: false,
+            alt_ids: vec![],
+            replaced_by: vec![],
+            consider: vec![],
+            synonyms: vec![],
+        }
+    }
+}
+
+/// A relation type declared in the ontology (a `[Typedef]` stanza in OBO, an object property in obographs)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Typedef {
+    /// The OBO shorthand, e.g. part_of
+    pub id: String,
+    pub name: String,
+}
+
+/// Collects terms and relations from one of the ontology parsers and resolves them into a [`GeneOntology`]
+#[derive(Default)]
+pub struct GeneOntologyBuilder {
+    terms: Vec<GoTerm>,
+    typedefs: Vec<Typedef>,
+    relations: Vec<(String, TermRelation, String)>,
+    data_version: Option<String>,
+    date: Option<String>,
+}
+
+impl GeneOntologyBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_term(&mut self, term: GoTerm) -> &mut Self {
+        self.terms.push(term);
+        self
+    }
+
+    pub fn add_typedef(&mut self, typedef: Typedef) -> &mut Self {
+        self.typedefs.push(typedef);
+        self
+    }
+
+    /// Add a relation `subject -> relation -> object`, e.g. GO:0000001 is_a GO:0048308
+    pub fn add_relation(
+        &mut self,
+        subject: &str,
+        relation: TermRelation,
+        object: &str,
+    ) -> &mut Self {
+        self.relations
+            .push((subject.to_string(), relation, object.to_string()));
+        self
+    }
+
+    pub fn data_version<T: Into<String>>(&mut self, version: T) -> &mut Self {
+        self.data_version = Some(version.into());
+        self
+    }
+
+    /// Release date in the form yyyy-mm-dd
+    pub fn date<T: Into<String>>(&mut self, date: T) -> &mut Self {
+        self.date = Some(date.into());
+        self
+    }
+
+    /// Index the terms and wire up the parent/child lists.
+    ///
+    /// Relations that point to terms outside of GO (e.g., to CHEBI or NCBITaxon) are skipped.
+    pub fn build(self) -> Result<GeneOntology, OntologyError> {
+        let mut term_index = HashMap::with_capacity(self.terms.len());
+        for (i, term) in self.terms.iter().enumerate() {
+            if term_index.insert(term.id.value.clone(), i).is_some() {
+                return Err(OntologyError::ParsingError(format!(
+                    "Duplicate term id '{}'",
+                    term.id.value
+                )));
+            }
+        }
+        let mut alt_id_index = HashMap::new();
+        for (i, term) in self.terms.iter().enumerate() {
+            for alt in &term.alt_ids {
+                if !term_index.contains_key(&alt.value) {
+                    alt_id_index.insert(alt.value.clone(), i);
+                }
+            }
+        }
+        let mut parents = vec![vec![]; self.terms.len()];
+        let mut children = vec![vec![]; self.terms.len()];
+        for (sub, rel, obj) in self.relations {
+            if let (Some(&s), Some(&o)) = (term_index.get(&sub), term_index.get(&obj)) {
+                parents[s].push((o, rel.clone()));
+                children[o].push((s, rel));
+            }
+        }
+        Ok(GeneOntology {
+            terms: self.terms,
+            typedefs: self.typedefs,
+            term_index,
+            alt_id_index,
+            parents,
+            children,
+            data_version: self.data_version,
+            date: self.date,
+        })
+    }
+}
+
+/// In-memory representation of the Gene Ontology graph.
+///
+/// Terms are stored in a vector and referred to by their index for the graph traversals
+/// that the enrichment algorithms perform many times. The public query functions accept
+/// [`TermId`]s and also resolve alternative ids (alt_id) to the primary term.
+pub struct GeneOntology {
+    terms: Vec<GoTerm>,
+    typedefs: Vec<Typedef>,
+    term_index: HashMap<String, usize>,
+    alt_id_index: HashMap<String, usize>,
+    /// For each term, its direct parents together with the relation to the parent
+    parents: Vec<Vec<(usize, TermRelation)>>,
+    children: Vec<Vec<(usize, TermRelation)>>,
+    data_version: Option<String>,
+    date: Option<String>,
+}
+
+impl GeneOntology {
+    /// Version of the release, e.g. releases/2024-01-17
+    pub fn data_version(&self) -> Option<&str> {
+        self.data_version.as_deref()
+    }
+
+    /// Release date in the form yyyy-mm-dd
+    pub fn date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+
+    pub fn typedefs(&self) -> &[Typedef] {
+        &self.typedefs
+    }
+
+    pub fn term_count(&self) -> usize {
+        self.terms.len()
+    }
+
+    pub fn terms(&self) -> impl Iterator<Item = &GoTerm> {
+        self.terms.iter()
+    }
+
+    /// Get the index of a term (primary or alternative id)
+    pub fn index_of(&self, tid: &TermId) -> Option<usize> {
+        self.term_index
+            .get(&tid.value)
+            .or_else(|| self.alt_id_index.get(&tid.value))
+            .copied()
+    }
+
+    pub fn term_at(&self, idx: usize) -> &GoTerm {
+        &self.terms[idx]
+    }
+
+    pub fn contains(&self, tid: &TermId) -> bool {
+        self.index_of(tid).is_some()
+    }
+
+    pub fn get_term(&self, tid: &TermId) -> Option<&GoTerm> {
+        self.index_of(tid).map(|i| &self.terms[i])
+    }
+
+    /// Return the primary id of a term if `tid` is an alternative id (or `tid` itself)
+    pub fn primary_id(&self, tid: &TermId) -> Option<&TermId> {
+        self.get_term(tid).map(|t| &t.id)
+    }
+
+    pub fn label(&self, tid: &TermId) -> Option<&str> {
+        self.get_term(tid).map(|t| t.label.as_str())
+    }
+
+    /// Direct parents with the relation type, including relations such as regulates that do not propagate
+    pub fn parent_relations(
+        &self,
+        tid: &TermId,
+    ) -> Result<Vec<(&TermId, &TermRelation)>, OntologyError> {
+        let idx = self.require_index(tid)?;
+        Ok(self.parents[idx]
+            .iter()
+            .map(|(p, rel)| (&self.terms[*p].id, rel))
+            .collect())
+    }
+
+    /// Direct parents along is_a and part_of
+    pub fn parents(&self, tid: &TermId) -> Result<Vec<&TermId>, OntologyError> {
+        let idx = self.require_index(tid)?;
+        Ok(self
+            .parent_indices(idx)
+            .map(|p| &self.terms[p].id)
+            .collect())
+    }
+
+    /// Direct children along is_a and part_of
+    pub fn children(&self, tid: &TermId) -> Result<Vec<&TermId>, OntologyError> {
+        let idx = self.require_index(tid)?;
+        Ok(self.child_indices(idx).map(|c| &self.terms[c].id).collect())
+    }
+
+    /// All ancestors along is_a and part_of, not including the term itself
+    pub fn ancestors(&self, tid: &TermId) -> Result<Vec<&TermId>, OntologyError> {
+        let idx = self.require_index(tid)?;
+        Ok(self
+            .ancestor_indices(idx)
+            .into_iter()
+            .map(|a| &self.terms[a].id)
+            .collect())
+    }
+
+    /// All descendants along is_a and part_of, not including the term itself
+    pub fn descendants(&self, tid: &TermId) -> Result<Vec<&TermId>, OntologyError> {
+        let idx = self.require_index(tid)?;
+        Ok(self
+            .descendant_indices(idx)
+            .into_iter()
+            .map(|d| &self.terms[d].id)
+            .collect())
+    }
+
+    pub fn is_ancestor_of(&self, ancestor: &TermId, tid: &TermId) -> Result<bool, OntologyError> {
+        let anc = self.require_index(ancestor)?;
+        let idx = self.require_index(tid)?;
+        Ok(self.ancestor_indices(idx).contains(&anc))
+    }
+
+    pub fn parent_indices(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        self.parents[idx]
+            .iter()
+            .filter(|(_, rel)| rel.propagates())
+            .map(|(p, _)| *p)
+    }
+
+    pub fn child_indices(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        self.children[idx]
+            .iter()
+            .filter(|(_, rel)| rel.propagates())
+            .map(|(c, _)| *c)
+    }
+
+    pub fn ancestor_indices(&self, idx: usize) -> HashSet<usize> {
+        self.traverse(idx, |i| self.parent_indices(i).collect())
+    }
+
+    pub fn descendant_indices(&self, idx: usize) -> HashSet<usize> {
+        self.traverse(idx, |i| self.child_indices(i).collect())
+    }
+
+    /// Breadth-first search starting from (but not including) `start`
+    fn traverse<F>(&self, start: usize, next: F) -> HashSet<usize>
+    where
+        F: Fn(usize) -> Vec<usize>,
+    {
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from(next(start));
+        while let Some(i) = queue.pop_front() {
+            if visited.insert(i) {
+                queue.extend(next(i));
+            }
+        }
+        visited
+    }
+
+    fn require_index(&self, tid: &TermId) -> Result<usize, OntologyError> {
+        self.index_of(tid)
+            .ok_or_else(|| OntologyError::UnknownTerm(tid.value.clone()))
+    }
+}
+
+const OBO_PURL: &str = "http://purl.obolibrary.org/obo/";
+const OBO_IN_OWL: &str = "http://www.geneontology.org/formats/oboInOwl#";
+const IAO_REPLACED_BY: &str = "http://purl.obolibrary.org/obo/IAO_0100001";
+const OWL_VERSION_INFO: &str = "http://www.w3.org/2002/07/owl#versionInfo";
+
+/// Convert an OBO PURL such as http://purl.obolibrary.org/obo/GO_0000001 to GO:0000001
+fn iri_to_curie(iri: &str) -> Option<String> {
+    let local = iri.strip_prefix(OBO_PURL)?;
+    let (prefix, id) = local.split_once('_')?;
+    Some(format!("{}:{}", prefix, id))
+}
+
+#[derive(Deserialize)]
+struct ObographsDocument {
+    graphs: Vec<ObographsGraph>,
+}
+
+#[derive(Deserialize)]
+struct ObographsGraph {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    meta: Option<ObographsMeta>,
+    #[serde(default)]
+    nodes: Vec<ObographsNode>,
+    #[serde(default)]
+    edges: Vec<ObographsEdge>,
+}
+
+#[derive(Deserialize)]
+struct ObographsNode {
+    id: String,
+    #[serde(default)]
+    lbl: Option<String>,
+    #[serde(rename = "type", default)]
+    node_type: Option<String>,
+    #[serde(rename = "propertyType", default)]
+    property_type: Option<String>,
+    #[serde(default)]
+    meta: Option<ObographsMeta>,
+}
+
+#[derive(Deserialize)]
+struct ObographsEdge {
+    sub: String,
+    pred: String,
+    obj: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObographsMeta {
+    #[serde(default)]
+    definition: Option<ObographsValue>,
+    #[serde(default)]
+    synonyms: Vec<ObographsSynonym>,
+    #[serde(default)]
+    basic_property_values: Vec<ObographsPropertyValue>,
+    #[serde(default)]
+    deprecated: bool,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ObographsValue {
+    val: String,
+}
+
+#[derive(Deserialize)]
+struct ObographsSynonym {
+    pred: String,
+    val: String,
+}
+
+#[derive(Deserialize)]
+struct ObographsPropertyValue {
+    pred: String,
+    val: String,
+}
+
+impl ObographsNode {
+    /// Convert object properties to typedefs, using the OBO shorthand (e.g., part_of) as id if available
+    fn to_typedef(&self) -> Option<Typedef> {
+        if self.node_type.as_deref() != Some("PROPERTY")
+            || self.property_type.as_deref().is_some_and(|t| t != "OBJECT")
+        {
+            return None;
+        }
+        let shorthand = self.meta.as_ref().and_then(|m| {
+            m.basic_property_values
+                .iter()
+                .find(|pv| pv.pred.strip_prefix(OBO_IN_OWL) == Some("shorthand"))
+                .map(|pv| pv.val.clone())
+        });
+        let id = shorthand.or_else(|| iri_to_curie(&self.id))?;
+        Some(Typedef {
+            id,
+            name: self.lbl.clone().unwrap_or_default(),
+        })
+    }
+
+    /// Convert to a term. Returns None for nodes that are not OBO classes (e.g., object properties).
+    /// The prefix is not checked, as with the OBO parser, so that other ontologies such as ECO can be loaded
+    fn into_go_term(self) -> Result<Option<GoTerm>, OntologyError> {
+        if self.node_type.as_deref() != Some("CLASS") {
+            return Ok(None);
+        }
+        let curie = match iri_to_curie(&self.id) {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let tid =
+            TermId::from_curie(&curie).map_err(|e| OntologyError::ParsingError(e.to_string()))?;
+        let mut term = GoTerm::new(tid, self.lbl.unwrap_or_default());
+        let meta = self.meta.unwrap_or_default();
+        term.is_obsolete = meta.deprecated;
+        term.definition = meta.definition.map(|d| d.val);
+        for syn in meta.synonyms {
+            let scope = match syn.pred.as_str() {
+                "hasExactSynonym" => SynonymScope::Exact,
+                "hasBroadSynonym" => SynonymScope::Broad,
+                "hasNarrowSynonym" => SynonymScope::Narrow,
+                _ => SynonymScope::Related,
+            };
+            term.synonyms.push(Synonym {
+                value: syn.val,
+                scope,
+            });
+        }
+        for pv in meta.basic_property_values {
+            let as_tid = || {
+                TermId::from_curie(&pv.val).map_err(|e| OntologyError::ParsingError(e.to_string()))
+            };
+            match pv.pred.strip_prefix(OBO_IN_OWL) {
+                // other ontologies, e.g., ECO, have their own namespaces
+                Some("hasOBONamespace") => term.namespace = Namespace::from_str(&pv.val).ok(),
+                Some("hasAlternativeId") => term.alt_ids.push(as_tid()?),
+                Some("consider") => term.consider.push(as_tid()?),
+                _ if pv.pred == IAO_REPLACED_BY => term.replaced_by.push(as_tid()?),
+                _ => {}
+            }
+        }
+        Ok(Some(term))
+    }
+}
+
+/// Load GO from an obographs JSON document (e.g., go-basic.json)
+pub fn parse_obographs<R: Read>(reader: R) -> Result<GeneOntology, OntologyError> {
+    let document: ObographsDocument = serde_json::from_reader(reader)
+        .map_err(|e| OntologyError::ParsingError(format!("Invalid obographs JSON: {}", e)))?;
+    let graph = document.graphs.into_iter().next().ok_or_else(|| {
+        OntologyError::ParsingError("obographs document has no graphs".to_string())
+    })?;
+    let mut builder = GeneOntologyBuilder::new();
+    if let Some(meta) = graph.meta {
+        // The version IRI looks like http://purl.obolibrary.org/obo/go/releases/2024-01-17/go.json
+        if let Some(version) = meta.version {
+            if let Some(start) = version.find("releases/") {
+                let release = version[start..]
+                    .split('/')
+                    .take(2)
+                    .collect::<Vec<_>>()
+                    .join("/");
+                builder.data_version(release);
+            }
+        }
+        for pv in meta.basic_property_values {
+            if pv.pred == OWL_VERSION_INFO {
+                builder.date(pv.val);
+            }
+        }
+    }
+    if graph.id.is_empty() {
+        return Err(OntologyError::ParsingError(
+            "obographs graph has no id".to_string(),
+        ));
+    }
+    for node in graph.nodes {
+        if let Some(typedef) = node.to_typedef() {
+            builder.add_typedef(typedef);
+        }
+        if let Some(term) = node.into_go_term()? {
+            builder.add_term(term);
+        }
+    }
+    for edge in graph.edges {
+        if let (Some(sub), Some(obj)) = (iri_to_curie(&edge.sub), iri_to_curie(&edge.obj)) {
+            builder.add_relation(&sub, TermRelation::from_identifier(&edge.pred), &obj);
+        }
+    }
+    builder.build()
+}
+
+/// Load the Gene Ontology from an OBO (go-basic.obo, go.obo) or obographs JSON (go.json) file.
+///
+/// The format is chosen by the file suffix; both formats result in the same graph. The bytes read are
+/// the progress of `job`, which also computes the CRC-32 of the file. If the job is cancelled, the
+/// parser fails with a read error.
+pub fn load_go(path: &str, job: &Job) -> Result<GeneOntology, OntologyError> {
+    let reader = BufReader::new(job.report_reads(job.open(path).map_err(OntologyError::IoError)?));
+    if path.ends_with(".obo") {
+        parse_obo(reader)
+    } else {
+        parse_obographs(reader)
+    }
+}
+
+/// Descriptive statistics about the GO release, shown as a table on the setup page
+pub fn ontology_descriptive_stats(go: &GeneOntology) -> Vec<AnnotationStat> {
+    let mut stats = vec![
+        AnnotationStat::from_string("data-version", go.data_version().unwrap_or("n/a")),
+        AnnotationStat::from_string("release date", go.date().unwrap_or("n/a")),
+        AnnotationStat::from_int("Total terms", go.term_count()),
+    ];
+    let namespaces = [
+        Namespace::BiologicalProcess,
+        Namespace::MolecularFunction,
+        Namespace::CellularComponent,
+    ];
+    for ns in namespaces {
+        let count = go
+            .terms()
+            .filter(|t| !t.is_obsolete && t.namespace == Some(ns))
+            .count();
+        stats.push(AnnotationStat::from_int(&ns.to_string(), count));
+    }
+    let obsolete = go.terms().filter(|t| t.is_obsolete).count();
+    stats.push(AnnotationStat::from_int("Obsolete terms", obsolete));
+    // Sort relations by name so that the table has the same order on each load
+    let mut relation_counts: HashMap<String, usize> = HashMap::new();
+    for parents in &go.parents {
+        for (_, rel) in parents {
+            *relation_counts.entry(rel.to_string()).or_insert(0) += 1;
+        }
+    }
+    let mut relation_counts: Vec<_> = relation_counts.into_iter().collect();
+    relation_counts.sort();
+    for (relation, count) in relation_counts {
+        stats.push(AnnotationStat::from_int(&relation, count));
+    }
+    stats
+}
+
+/// Response of the command that loads the ontology
+#[derive(Serialize)]
+struct LoadedOntology {
+    stats: Vec<AnnotationStat>,
+}
+
+/// Add the ontology to the session and return its id and descriptive statistics. `inputs` are the
+/// files the ontology was read from.
+pub fn store_ontology(
+    session: &mut Session,
+    name: &str,
+    inputs: Vec<InputFile>,
+    go: GeneOntology,
+) -> Result<String, String> {
+    let stats = ontology_descriptive_stats(&go);
+    let id = session.add_ontology(name, go, inputs);
+    with_id(id, &LoadedOntology { stats })
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+
+    use super::*;
+
+    /// A tiny excerpt of GO with three BP terms, one obsolete term and a regulates edge
+    const GO_JSON: &str = r#"{
+      "graphs": [{
+        "id": "http://purl.obolibrary.org/obo/go.json",
+        "meta": {
+          "version": "http://purl.obolibrary.org/obo/go/releases/2024-01-17/go.json",
+          "basicPropertyValues": [
+            {"pred": "http://www.w3.org/2002/07/owl#versionInfo", "val": "2024-01-17"}
+          ]
+        },
+        "nodes": [
+          {"id": "http://purl.obolibrary.org/obo/GO_0008150", "lbl": "biological_process", "type": "CLASS",
+           "meta": {"basicPropertyValues": [
+             {"pred": "http://www.geneontology.org/formats/oboInOwl#hasOBONamespace", "val": "biological_process"},
+             {"pred": "http://www.geneontology.org/formats/oboInOwl#hasAlternativeId", "val": "GO:0000004"}]}},
+          {"id": "http://purl.obolibrary.org/obo/GO_0009987", "lbl": "cellular process", "type": "CLASS",
+           "meta": {"basicPropertyValues": [
+             {"pred": "http://www.geneontology.org/formats/oboInOwl#hasOBONamespace", "val": "biological_process"}]}},
+          {"id": "http://purl.obolibrary.org/obo/GO_0007049", "lbl": "cell cycle", "type": "CLASS",
+           "meta": {"synonyms": [{"pred": "hasRelatedSynonym", "val": "cell-division cycle"}],
+             "basicPropertyValues": [
+             {"pred": "http://www.geneontology.org/formats/oboInOwl#hasOBONamespace", "val": "biological_process"}]}},
+          {"id": "http://purl.obolibrary.org/obo/GO_0051726", "lbl": "regulation of cell cycle", "type": "CLASS",
+           "meta": {"basicPropertyValues": [
+             {"pred": "http://www.geneontology.org/formats/oboInOwl#hasOBONamespace", "val": "biological_process"}]}},
+          {"id": "http://purl.obolibrary.org/obo/GO_0000005", "lbl": "obsolete ribosomal chaperone activity", "type": "CLASS",
+           "meta": {"deprecated": true, "basicPropertyValues": [
+             {"pred": "http://www.geneontology.org/formats/oboInOwl#hasOBONamespace", "val": "molecular_function"},
+             {"pred": "http://purl.obolibrary.org/obo/IAO_0100001", "val": "GO:0051082"}]}},
+          {"id": "http://purl.obolibrary.org/obo/BFO_0000050", "lbl": "part of", "type": "PROPERTY"}
+        ],
+        "edges": [
+          {"sub": "http://purl.obolibrary.org/obo/GO_0009987", "pred": "is_a", "obj": "http://purl.obolibrary.org/obo/GO_0008150"},
+          {"sub": "http://purl.obolibrary.org/obo/GO_0007049", "pred": "is_a", "obj": "http://purl.obolibrary.org/obo/GO_0009987"},
+          {"sub": "http://purl.obolibrary.org/obo/GO_0051726", "pred": "is_a", "obj": "http://purl.obolibrary.org/obo/GO_0008150"},
+          {"sub": "http://purl.obolibrary.org/obo/GO_0051726", "pred": "http://purl.obolibrary.org/obo/RO_0002211", "obj": "http://purl.obolibrary.org/obo/GO_0007049"}
+        ]
+      }]
+    }"#;
+
+    /// The relation kinds of GO, an edge to a term outside of GO, and an obsolete term with an alt_id
+    const RELATIONS_JSON: &str = r#"{
+      "graphs": [{
+        "id": "http://purl.obolibrary.org/obo/go.json",
+        "nodes": [
+          {"id": "http://purl.obolibrary.org/obo/GO_0008150", "lbl": "biological_process", "type": "CLASS"},
+          {"id": "http://purl.obolibrary.org/obo/GO_0007049", "lbl": "cell cycle", "type": "CLASS"},
+          {"id": "http://purl.obolibrary.org/obo/GO_0022402", "lbl": "cell cycle process", "type": "CLASS"},
+          {"id": "http://purl.obolibrary.org/obo/GO_0045786", "lbl": "negative regulation of cell cycle", "type": "CLASS"},
+          {"id": "http://purl.obolibrary.org/obo/GO_0045787", "lbl": "positive regulation of cell cycle", "type": "CLASS"},
+          {"id": "http://purl.obolibrary.org/obo/GO_0000001", "lbl": "obsolete cell cycle step", "type": "CLASS",
+           "meta": {"deprecated": true, "basicPropertyValues": [
+             {"pred": "http://www.geneontology.org/formats/oboInOwl#hasAlternativeId", "val": "GO:0000002"},
+             {"pred": "http://www.geneontology.org/formats/oboInOwl#consider", "val": "GO:0022402"}]}},
+          {"id": "http://purl.obolibrary.org/obo/BFO_0000050", "lbl": "part of", "type": "PROPERTY", "propertyType": "OBJECT",
+           "meta": {"basicPropertyValues": [
+             {"pred": "http://www.geneontology.org/formats/oboInOwl#shorthand", "val": "part_of"}]}}
+        ],
+        "edges": [
+          {"sub": "http://purl.obolibrary.org/obo/GO_0007049", "pred": "is_a", "obj": "http://purl.obolibrary.org/obo/GO_0008150"},
+          {"sub": "http://purl.obolibrary.org/obo/GO_0022402", "pred": "http://purl.obolibrary.org/obo/BFO_0000050", "obj": "http://purl.obolibrary.org/obo/GO_0007049"},
+          {"sub": "http://purl.obolibrary.org/obo/GO_0045786", "pred": "is_a", "obj": "http://purl.obolibrary.org/obo/GO_0008150"},
+          {"sub": "http://purl.obolibrary.org/obo/GO_0045786", "pred": "http://purl.obolibrary.org/obo/RO_0002212", "obj": "http://purl.obolibrary.org/obo/GO_0007049"},
+          {"sub": "http://purl.obolibrary.org/obo/GO_0045787", "pred": "is_a", "obj": "http://purl.obolibrary.org/obo/GO_0008150"},
+          {"sub": "http://purl.obolibrary.org/obo/GO_0045787", "pred": "http://purl.obolibrary.org/obo/RO_0002213", "obj": "http://purl.obolibrary.org/obo/GO_0007049"},
+          {"sub": "http://purl.obolibrary.org/obo/GO_0022402", "pred": "http://purl.obolibrary.org/obo/RO_0002091", "obj": "http://purl.obolibrary.org/obo/GO_0045787"},
+          {"sub": "http://purl.obolibrary.org/obo/GO_0007049", "pred": "http://purl.obolibrary.org/obo/RO_0002233", "obj": "http://purl.obolibrary.org/obo/CHEBI_15377"}
+        ]
+      }]
+    }"#;
+
+    fn tid(curie: &str) -> TermId {
+        TermId::from_curie(curie).unwrap()
+    }
+
+    #[test]
+    fn test_load() {
+        let go = parse_obographs(GO_JSON.as_bytes()).unwrap();
+        assert_eq!(5, go.term_count());
+        assert_eq!(Some("releases/2024-01-17"), go.data_version());
+        assert_eq!(Some("2024-01-17"), go.date());
+        assert_eq!(Some("cell cycle"), go.label(&tid("GO:0007049")));
+        let cc = go.get_term(&tid("GO:0007049")).unwrap();
+        assert_eq!(Some(Namespace::BiologicalProcess), cc.namespace);
+        assert_eq!(1, cc.synonyms.len());
+    }
+
+    #[test]
+    fn test_ancestors_and_descendants() {
+        let go = parse_obographs(GO_JSON.as_bytes()).unwrap();
+        let mut ancs: Vec<_> = go
+            .ancestors(&tid("GO:0007049"))
+            .unwrap()
+            .into_iter()
+            .cloned()
+            .collect();
+        ancs.sort_by(|a, b| a.value.cmp(&b.value));
+        assert_eq!(vec![tid("GO:0008150"), tid("GO:0009987")], ancs);
+        let desc = go.descendants(&tid("GO:0008150")).unwrap();
+        assert_eq!(3, desc.len());
+        assert!(go
+            .is_ancestor_of(&tid("GO:0008150"), &tid("GO:0007049"))
+            .unwrap());
+        assert!(!go
+            .is_ancestor_of(&tid("GO:0007049"), &tid("GO:0008150"))
+            .unwrap());
+    }
+
+    /// regulates is retained as a relation but is not followed for ancestors
+    #[test]
+    fn test_regulates_does_not_propagate() {
+        let go = parse_obographs(GO_JSON.as_bytes()).unwrap();
+        let reg = tid("GO:0051726");
+        assert_eq!(vec![&tid("GO:0008150")], go.parents(&reg).unwrap());
+        let rels = go.parent_relations(&reg).unwrap();
+        assert_eq!(2, rels.len());
+        assert!(rels.contains(&(&tid("GO:0007049"), &TermRelation::Regulates)));
+    }
+
+    #[test]
+    fn test_alt_id_and_obsolete() {
+        let go = parse_obographs(GO_JSON.as_bytes()).unwrap();
+        assert_eq!(Some(&tid("GO:0008150")), go.primary_id(&tid("GO:0000004")));
+        let obsolete = go.get_term(&tid("GO:0000005")).unwrap();
+        assert!(obsolete.is_obsolete);
+        assert_eq!(vec![tid("GO:0051082")], obsolete.replaced_by);
+    }
+
+    #[test]
+    fn test_relation_identifiers() {
+        let spellings = [
+            (
+                TermRelation::PartOf,
+                "part_of",
+                "BFO:0000050",
+                "BFO_0000050",
+            ),
+            (
+                TermRelation::Regulates,
+                "regulates",
+                "RO:0002211",
+                "RO_0002211",
+            ),
+            (
+                TermRelation::NegativelyRegulates,
+                "negatively_regulates",
+                "RO:0002212",
+                "RO_0002212",
+            ),
+            (
+                TermRelation::PositivelyRegulates,
+                "positively_regulates",
+                "RO:0002213",
+                "RO_0002213",
+            ),
+        ];
+        for (relation, shorthand, curie, local) in spellings {
+            let iri = format!("{}{}", OBO_PURL, local);
+            for identifier in [shorthand, curie, &iri] {
+                assert_eq!(relation, TermRelation::from_identifier(identifier));
+            }
+            assert_eq!(shorthand, relation.to_string());
+        }
+        assert_eq!(TermRelation::IsA, TermRelation::from_identifier("is_a"));
+        assert_eq!(
+            TermRelation::Other("RO:0002091".to_string()),
+            TermRelation::from_identifier("RO:0002091")
+        );
+    }
+
+    #[test]
+    fn test_relation_kinds() {
+        let go = parse_obographs(RELATIONS_JSON.as_bytes()).unwrap();
+        let part_of = Typedef {
+            id: "part_of".to_string(),
+            name: "part of".to_string(),
+        };
+        assert_eq!(&[part_of], go.typedefs());
+        let process = tid("GO:0022402");
+        let cell_cycle = tid("GO:0007049");
+        let positive = tid("GO:0045787");
+        let relations = go.parent_relations(&process).unwrap();
+        assert_eq!(2, relations.len());
+        assert!(relations.contains(&(&cell_cycle, &TermRelation::PartOf)));
+        let other = TermRelation::Other(format!("{}RO_0002091", OBO_PURL));
+        assert!(relations.contains(&(&positive, &other)));
+        // part_of propagates, the other relations do not
+        let mut ancestors = go.ancestors(&process).unwrap();
+        ancestors.sort_by(|a, b| a.value.cmp(&b.value));
+        assert_eq!(vec![&cell_cycle, &tid("GO:0008150")], ancestors);
+        for (term, relation) in [
+            ("GO:0045786", TermRelation::NegativelyRegulates),
+            (positive.value.as_str(), TermRelation::PositivelyRegulates),
+        ] {
+            let relations = go.parent_relations(&tid(term)).unwrap();
+            assert!(relations.contains(&(&cell_cycle, &relation)));
+            assert_eq!(vec![&tid("GO:0008150")], go.ancestors(&tid(term)).unwrap());
+        }
+        assert_eq!(vec![&process], go.children(&cell_cycle).unwrap());
+        // the edge to CHEBI is skipped, as it points outside of the ontology
+        assert_eq!(vec![&tid("GO:0008150")], go.parents(&cell_cycle).unwrap());
+    }
+
+    #[test]
+    fn test_obsolete_term() {
+        let go = parse_obographs(RELATIONS_JSON.as_bytes()).unwrap();
+        let obsolete = go.get_term(&tid("GO:0000002")).unwrap();
+        assert_eq!(tid("GO:0000001"), obsolete.id);
+        assert!(obsolete.is_obsolete);
+        assert_eq!(vec![tid("GO:0000002")], obsolete.alt_ids);
+        assert_eq!(vec![tid("GO:0022402")], obsolete.consider);
+        assert!(go.parents(&obsolete.id).unwrap().is_empty());
+        assert!(go.ancestors(&tid("GO:0000002")).unwrap().is_empty());
+        let stats = ontology_descriptive_stats(&go);
+        let obsolete_count = stats.iter().find(|s| s.key == "Obsolete terms").unwrap();
+        assert_eq!("1", obsolete_count.value);
+    }
+
+    #[test]
+    fn test_unknown_term() {
+        let go = parse_obographs(GO_JSON.as_bytes()).unwrap();
+        let result = go.parents(&tid("GO:9999999"));
+        assert!(result.is_err());
+        assert_eq!("Unknown term: GO:9999999", result.unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_descriptive_stats() {
+        let go = parse_obographs(GO_JSON.as_bytes()).unwrap();
+        let stats: Vec<(String, String)> = ontology_descriptive_stats(&go)
+            .into_iter()
+            .map(|s| (s.key, s.value))
+            .collect();
+        let expected = vec![
+            ("data-version", "releases/2024-01-17"),
+            ("release date", "2024-01-17"),
+            ("Total terms", "5"),
+            ("biological_process", "4"),
+            ("molecular_function", "0"),
+            ("cellular_component", "0"),
+            ("Obsolete terms", "1"),
+            ("is_a", "3"),
+            ("regulates", "1"),
+        ];
+        let expected: Vec<(String, String)> = expected
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(expected, stats);
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        let result = parse_obographs("{\"graphs\": 42}".as_bytes());
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,464 @@
+//! Parsers for the Gene Product Association Data (GPAD 2.0) and Gene Product Information (GPI 2.0) formats.
+//!
+//! See https://geneontology.org/docs/gene-product-association-data-gpad-format-2.0/ and
+//! https://geneontology.org/docs/gene-product-information-gpi-format-2.0/. GPAD lines only refer to gene
+//! products by id and use RO relations and ECO evidence; the symbol, name and taxon come from the GPI file.
+//! [`join`] combines both into [`GafRecord`]s, so the rest of the pipeline does not depend on the format.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use crate::diagnostics::ParseDiagnostics;
+use crate::evidence::EcoResolver;
+use crate::gaf::{split_pipe, AnnotationDate, GafRecord, Qualifier};
+use crate::goannot::{Aspect, GoTermRelation, InputError, TermId};
+use crate::gontology::{GeneOntology, Namespace};
+use crate::jobs::Job;
+
+pub const GPAD_COLUMNS: usize = 12;
+pub const GPI_COLUMNS: usize = 11;
+
+/// Relation Ontology ids of the GAF 2.2 qualifiers
+const RO_RELATIONS: [(&str, GoTermRelation); 13] = [
+    ("RO:0002327", GoTermRelation::Enables),
+    ("RO:0002326", GoTermRelation::ContributesTo),
+    ("RO:0002331", GoTermRelation::InvolvedIn),
+    ("RO:0002263", GoTermRelation::ActsUpstreamOf),
+    ("RO:0002264", GoTermRelation::ActsUpstreamOfOrWithin),
+    ("RO:0004035", GoTermRelation::ActsUpstreamOfNegativeEffect),
+    ("RO:0004034", GoTermRelation::ActsUpstreamOfPositiveEffect),
+    (
+        "RO:0004033",
+        GoTermRelation::ActsUpstreamOfOrWithinNegativeEffect,
+    ),
+    (
+        "RO:0004032",
+        GoTermRelation::ActsUpstreamOfOrWithinPositiveEffect,
+    ),
+    ("RO:0002432", GoTermRelation::IsActiveIn),
+    ("RO:0001025", GoTermRelation::LocatedIn),
+    ("RO:0002325", GoTermRelation::ColocalizesWith),
+    ("BFO:0000050", GoTermRelation::PartOf),
+];
+
+/// Sequence Ontology ids of the most common gene product types and their GAF names
+const SO_TYPES: [(&str, &str); 6] = [
+    ("SO:0000104", "protein"),
+    ("SO:0000704", "gene"),
+    ("SO:0000234", "mRNA"),
+    ("SO:0000655", "ncRNA"),
+    ("SO:0000336", "pseudogene"),
+    ("GO:0032991", "protein_complex"),
+];
+
+fn relation_from_ro(id: &str) -> Result<GoTermRelation, InputError> {
+    RO_RELATIONS
+        .iter()
+        .find(|(ro, _)| *ro == id)
+        .map(|(_, relation)| relation.clone())
+        .ok_or_else(|| {
+            InputError::UnknownRelation(format!("Did not recognize '{}' as a GPAD relation.", id))
+        })
+}
+
+/// GPAD has no aspect column, so the aspect is the namespace of the GO term in the loaded ontology.
+/// Terms that are not in the ontology cannot be assigned an aspect and are rejected.
+fn aspect_of(go: &GeneOntology, go_id: &TermId) -> Result<Aspect, InputError> {
+    match go.get_term(go_id).and_then(|term| term.namespace) {
+        Some(Namespace::MolecularFunction) => Ok(Aspect::F),
+        Some(Namespace::BiologicalProcess) => Ok(Aspect::P),
+        Some(Namespace::CellularComponent) => Ok(Aspect::C),
+        None => Err(InputError::InvalidAspect(format!(
+            "{} is not a term of the loaded Gene Ontology",
+            go_id.value
+        ))),
+    }
+}
+
+/// GPI 2.0 and GPAD 2.0 use NCBITaxon:9606 where GAF uses taxon:9606
+fn gaf_taxon(id: &str) -> Result<TermId, InputError> {
+    let taxon = id.strip_prefix("NCBITaxon:").unwrap_or(id);
+    TermId::new("taxon", taxon)
+}
+
+/// Split a CURIE at the first colon, e.g. MGI:MGI:97490 into MGI and MGI:97490
+fn split_curie(curie: &str) -> Result<(&str, &str), InputError> {
+    curie
+        .split_once(':')
+        .ok_or_else(|| InputError::InvalidCurie(format!("Expected a CURIE but got '{}'", curie)))
+}
+
+fn split_columns(line: &str, expected: usize, format: &str) -> Result<Vec<String>, InputError> {
+    let mut tokens: Vec<String> = line.split('\t').map(str::to_string).collect();
+    // trailing optional columns are sometimes left out
+    if tokens.len() < expected {
+        tokens.resize(expected, String::new());
+    }
+    if tokens.len() > expected {
+        return Err(InputError::MalformedLine(format!(
+            "{} lines expected to have {} fields, but line had {} fields: {}",
+            format,
+            expected,
+            tokens.len(),
+            line
+        )));
+    }
+    Ok(tokens)
+}
+
+/// One line of a GPI 2.0 file
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpiRecord {
+    /// e.g., UniProtKB:P04637
+    pub id: String,
+    pub symbol: String,
+    pub name: Option<String>,
+    pub synonyms: Vec<String>,
+    /// Sequence Ontology (or GO for complexes) ids, e.g., SO:0000104
+    pub types: Vec<String>,
+    /// e.g., NCBITaxon:9606
+    pub taxon: String,
+    pub encoded_by: Vec<String>,
+    pub parent_protein: Option<String>,
+    pub complex_members: Vec<String>,
+    pub xrefs: Vec<String>,
+    pub properties: Vec<String>,
+}
+
+impl FromStr for GpiRecord {
+    type Err = InputError;
+
+    fn from_str(line: &str) -> Result<Self, InputError> {
+        let tokens = split_columns(line, GPI_COLUMNS, "GPI")?;
+        if tokens[0].is_empty() || tokens[1].is_empty() || tokens[5].is_empty() {
+            return Err(InputError::ParsingError(format!(
+                "GPI lines require an id, a symbol and a taxon: {}",
+                line
+            )));
+        }
+        Ok(GpiRecord {
+            id: tokens[0].clone(),
+            symbol: tokens[1].clone(),
+            name: (!tokens[2].is_empty()).then(|| tokens[2].clone()),
+            synonyms: split_pipe(&tokens[3]),
+            types: split_pipe(&tokens[4]),
+            taxon: tokens[5].clone(),
+            encoded_by: split_pipe(&tokens[6]),
+            parent_protein: (!tokens[7].is_empty()).then(|| tokens[7].clone()),
+            complex_members: split_pipe(&tokens[8]),
+            xrefs: split_pipe(&tokens[9]),
+            properties: split_pipe(&tokens[10]),
+        })
+    }
+}
+
+impl GpiRecord {
+    /// The GAF name of the first type, or its id if there is no GAF name
+    pub fn gaf_type(&self) -> String {
+        let first = self
+            .types
+            .first()
+            .map(String::as_str)
+            .unwrap_or("gene_product");
+        SO_TYPES
+            .iter()
+            .find(|(so, _)| *so == first)
+            .map(|(_, name)| name.to_string())
+            .unwrap_or_else(|| first.to_string())
+    }
+}
+
+/// One line of a GPAD 2.0 file
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpadRecord {
+    /// e.g., UniProtKB:P04637
+    pub id: String,
+    pub negated: bool,
+    pub relation: GoTermRelation,
+    pub go_id: TermId,
+    pub references: Vec<String>,
+    /// e.g., ECO:0000314
+    pub evidence_type: String,
+    pub with_from: Vec<String>,
+    pub interacting_taxon: Option<String>,
+    pub date: AnnotationDate,
+    pub assigned_by: String,
+    pub annotation_extensions: Vec<String>,
+    pub properties: Vec<String>,
+}
+
+impl FromStr for GpadRecord {
+    type Err = InputError;
+
+    fn from_str(line: &str) -> Result<Self, InputError> {
+        let tokens = split_columns(line, GPAD_COLUMNS, "GPAD")?;
+        let negated = match tokens[1].as_str() {
+            "" => false,
+            "NOT" => true,
+            other => {
+                return Err(InputError::ParsingError(format!(
+                    "Expected NOT or an empty negation column but got '{}'",
+                    other
+                )))
+            }
+        };
+        split_curie(&tokens[0])?;
+        Ok(GpadRecord {
+            id: tokens[0].clone(),
+            negated,
+            relation: relation_from_ro(&tokens[2])?,
+            go_id: TermId::from_curie(&tokens[3])?,
+            references: split_pipe(&tokens[4]),
+            evidence_type: tokens[5].clone(),
+            with_from: split_pipe(&tokens[6]),
+            interacting_taxon: (!tokens[7].is_empty()).then(|| tokens[7].clone()),
+            date: AnnotationDate::from_str(&tokens[8])?,
+            assigned_by: tokens[9].clone(),
+            annotation_extensions: split_pipe(&tokens[10]),
+            properties: split_pipe(&tokens[11]),
+        })
+    }
+}
+
+impl GpadRecord {
+    /// The annotation in the GAF model. Symbol, name, synonyms, type and taxon are taken from the
+    /// GPI record; without one, the local id is used as symbol. The aspect is the namespace of the
+    /// term in `go`, and `codes` maps the ECO id to a GO evidence code.
+    pub fn to_gaf_record(
+        &self,
+        gpi: Option<&GpiRecord>,
+        go: &GeneOntology,
+        codes: &mut EcoResolver,
+    ) -> Result<GafRecord, InputError> {
+        let (db, db_object_id) = split_curie(&self.id)?;
+        let aspect = aspect_of(go, &self.go_id)?;
+        let mut taxa = vec![];
+        if let Some(gpi) = gpi {
+            taxa.push(gaf_taxon(&gpi.taxon)?);
+        }
+        if let Some(interacting) = &self.interacting_taxon {
+            taxa.push(gaf_taxon(interacting)?);
+        }
+        Ok(GafRecord {
+            db: db.to_string(),
+            db_object_id: db_object_id.to_string(),
+            db_object_symbol: gpi
+                .map(|g| g.symbol.clone())
+                .unwrap_or_else(|| db_object_id.to_string()),
+            qualifier: Qualifier {
+                negated: self.negated,
+                relation: self.relation.clone(),
+            },
+            go_id: self.go_id.clone(),
+            references: self.references.clone(),
+            evidence_code: codes.resolve(&self.evidence_type)?,
+            with_from: self.with_from.clone(),
+            aspect,
+            db_object_name: gpi.and_then(|g| g.name.clone()),
+            db_object_synonyms: gpi.map(|g| g.synonyms.clone()).unwrap_or_default(),
+            db_object_type: gpi
+                .map(GpiRecord::gaf_type)
+                .unwrap_or_else(|| "gene_product".to_string()),
+            taxa,
+            date: self.date,
+            assigned_by: self.assigned_by.clone(),
+            annotation_extensions: self.annotation_extensions.clone(),
+            gene_product_form_id: None,
+            eco_id: Some(self.evidence_type.clone()),
+        })
+    }
+}
+
+/// Parse the lines of a GPAD or GPI file, skipping header lines (starting with `!`).
+///
+/// Returns the records and the value of the `!date-generated` header, if any. Lines that cannot be
+/// parsed are recorded in `diagnostics`.
+fn parse_lines<R: BufRead, T: FromStr<Err = InputError>>(
+    reader: R,
+    diagnostics: &mut ParseDiagnostics,
+    job: &Job,
+) -> Result<(Vec<T>, Option<String>), String> {
+    let mut records = vec![];
+    let mut date_generated = None;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| format!("Error reading file: {}", e))?;
+        if let Some(header) = line.strip_prefix('!') {
+            if let Some(date) = header.strip_prefix("date-generated:") {
+                date_generated.get_or_insert_with(|| date.trim().to_string());
+            }
+        } else if !line.trim().is_empty() {
+            diagnostics.lines += 1;
+            match T::from_str(&line) {
+                Ok(record) => records.push(record),
+                Err(e) => diagnostics.add_error(Some(i + 1), &line, &e),
+            }
+            job.parsed_lines(diagnostics.lines)?;
+        }
+    }
+    Ok((records, date_generated))
+}
+
+pub fn parse_gpad<R: BufRead>(
+    reader: R,
+    diagnostics: &mut ParseDiagnostics,
+    job: &Job,
+) -> Result<(Vec<GpadRecord>, Option<String>), String> {
+    parse_lines(reader, diagnostics, job)
+}
+
+pub fn parse_gpi<R: BufRead>(
+    reader: R,
+    diagnostics: &mut ParseDiagnostics,
+    job: &Job,
+) -> Result<Vec<GpiRecord>, String> {
+    parse_lines(reader, diagnostics, job).map(|(records, _)| records)
+}
+
+/// Join the GPAD annotations with the gene product information of the GPI file. The aspects are taken
+/// from the Gene Ontology `go`. The Evidence and Conclusion Ontology `eco`, if loaded, maps ECO terms
+/// that are more specific than the default ids of the GO evidence codes. Annotations that cannot be
+/// converted are recorded in `diagnostics` without a line number.
+pub fn join(
+    gpad: &[GpadRecord],
+    gpi: &[GpiRecord],
+    go: &GeneOntology,
+    eco: Option<&GeneOntology>,
+    diagnostics: &mut ParseDiagnostics,
+) -> Vec<GafRecord> {
+    let gene_products: HashMap<&str, &GpiRecord> = gpi.iter().map(|g| (g.id.as_str(), g)).collect();
+    let mut codes = EcoResolver::new(eco);
+    gpad.iter()
+        .filter_map(|a| {
+            match a.to_gaf_record(gene_products.get(a.id.as_str()).copied(), go, &mut codes) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    diagnostics.add_error(None, &a.id, &e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+
+    use super::*;
+    use crate::diagnostics::DiagnosticCategory;
+    use crate::evidence::EvidenceCode;
+    use crate::goannot::EviCode;
+    use crate::gontology::{GeneOntologyBuilder, GoTerm};
+
+    const GPAD: &str = "!gpad-version: 2.0
+!date-generated: 2024-06-01
+UniProtKB:P04637\t\tRO:0002331\tGO:0006915\tPMID:12345\tECO:0000315\t\t\t2020-12-31\tMGI\t\t
+UniProtKB:P04637\tNOT\tRO:0002327\tGO:0003700\tPMID:12345\tECO:0000314\t\t\t2021-01-05\tUniProt\t\t
+MGI:MGI:97490\t\tRO:0002432\tGO:0005634\tGO_REF:0000043\tECO:0000501\t\t\t2024-01-14\tMGI\t\t
+";
+
+    const GPI: &str = "!gpi-version: 2.0
+UniProtKB:P04637\tTP53\tCellular tumor antigen p53\tP53|LFS1\tSO:0000104\tNCBITaxon:9606\t\t\t\t\t
+";
+
+    /// The GO terms of the GPAD lines, one in each namespace
+    fn go() -> GeneOntology {
+        let mut builder = GeneOntologyBuilder::new();
+        for (id, label, namespace) in [
+            (
+                "GO:0006915",
+                "apoptotic process",
+                Namespace::BiologicalProcess,
+            ),
+            (
+                "GO:0003700",
+                "DNA-binding transcription factor activity",
+                Namespace::MolecularFunction,
+            ),
+            ("GO:0005634", "nucleus", Namespace::CellularComponent),
+        ] {
+            let mut term = GoTerm::new(TermId::from_curie(id).unwrap(), label);
+            term.namespace = Some(namespace);
+            builder.add_term(term);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_parse_gpad() {
+        let mut diagnostics = ParseDiagnostics::new();
+        let (records, version) =
+            parse_gpad(GPAD.as_bytes(), &mut diagnostics, &Job::default()).unwrap();
+        assert_eq!(3, diagnostics.lines);
+        assert_eq!(0, diagnostics.errors);
+        assert_eq!(3, records.len());
+        assert_eq!(Some("2024-06-01".to_string()), version);
+        assert_eq!(GoTermRelation::InvolvedIn, records[0].relation);
+        assert!(records[1].negated);
+        assert_eq!(AnnotationDate::new(2024, 1, 14).unwrap(), records[2].date);
+    }
+
+    #[test]
+    fn test_join() {
+        let mut diagnostics = ParseDiagnostics::new();
+        let (gpad, _) = parse_gpad(GPAD.as_bytes(), &mut diagnostics, &Job::default()).unwrap();
+        let gpi = parse_gpi(GPI.as_bytes(), &mut diagnostics, &Job::default()).unwrap();
+        assert_eq!("protein", gpi[0].gaf_type());
+        let records = join(&gpad, &gpi, &go(), None, &mut diagnostics);
+        assert_eq!(0, diagnostics.errors);
+        assert_eq!(3, records.len());
+        let tp53 = &records[0];
+        assert_eq!("TP53", tp53.db_object_symbol);
+        assert_eq!(EvidenceCode::IMP, tp53.evidence_code);
+        assert_eq!(Some("ECO:0000315"), tp53.eco_id.as_deref());
+        assert_eq!(Aspect::P, tp53.aspect);
+        assert_eq!("taxon:9606", tp53.taxa[0].value);
+        let annot = tp53.to_go_annot().unwrap();
+        assert_eq!("UniProtKB:P04637", annot.gene_product_id.value);
+        assert_eq!(EvidenceCode::IMP, annot.evidence_code);
+        assert_eq!(EviCode::EXP, annot.evidence_group());
+        assert!(matches!(
+            records[1].to_go_annot(),
+            Err(InputError::NegatedAnnotation)
+        ));
+        // no GPI entry: the local id is used as symbol
+        assert_eq!("MGI", records[2].db);
+        assert_eq!("MGI:97490", records[2].db_object_symbol);
+        assert_eq!(Aspect::C, records[2].aspect);
+        assert_eq!(EvidenceCode::IEA, records[2].evidence_code);
+        let annot = records[2].to_go_annot().unwrap();
+        assert_eq!("MGI:MGI:97490", annot.gene_product_id.value);
+    }
+
+    #[test]
+    fn test_aspect_from_ontology() {
+        let mut diagnostics = ParseDiagnostics::new();
+        // enables a cellular component: the relation does not decide the aspect
+        let gpad = format!(
+            "{}UniProtKB:P04637\t\tRO:0002327\tGO:0005634\tPMID:1\tECO:0000314\t\t\t2020-12-31\tMGI\t\t
+UniProtKB:P04637\t\tRO:0002331\tGO:0099999\tPMID:1\tECO:0000315\t\t\t2020-12-31\tMGI\t\t\n",
+            GPAD
+        );
+        let (gpad, _) = parse_gpad(gpad.as_bytes(), &mut diagnostics, &Job::default()).unwrap();
+        let records = join(&gpad, &[], &go(), None, &mut diagnostics);
+        assert_eq!(4, records.len());
+        assert_eq!(Aspect::F, records[1].aspect);
+        assert_eq!(Aspect::C, records[3].aspect);
+        // GO:0099999 is not in the ontology
+        assert_eq!(1, diagnostics.errors);
+        assert_eq!(1, diagnostics.counts[&DiagnosticCategory::InvalidAspect]);
+    }
+
+    #[test]
+    fn test_unknown_relation() {
+        let line = "UniProtKB:P04637\t\tRO:9999999\tGO:0006915\tPMID:1\tECO:0000315\t\t\t2020-12-31\tMGI\t\t";
+        assert!(GpadRecord::from_str(line).is_err());
+        let gpad = format!("{}{}\n", GPAD, line);
+        let mut diagnostics = ParseDiagnostics::new();
+        let (records, _) = parse_gpad(gpad.as_bytes(), &mut diagnostics, &Job::default()).unwrap();
+        assert_eq!(3, records.len());
+        assert_eq!(1, diagnostics.counts[&DiagnosticCategory::UnknownRelation]);
+        assert_eq!(Some(6), diagnostics.samples[0].line_number);
+    }
+}
@@ -0,0 +1,353 @@
+//! Progress and cancellation of long-running commands.
+//!
+//! Loading a large annotation file or running a resampling-based correction can take minutes. The
+//! frontend passes a job id to such a command, receives [`JobProgress`] events under that id while the
+//! command runs, and can stop it with [`JobState::cancel`]. A cancelled command returns [`CANCELLED`] as
+//! error.
+
+use flate2::Crc;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::session::InputFile;
+
+/// Error message of a cancelled job
+pub const CANCELLED: &str = "Cancelled";
+
+/// Number of lines between two progress reports while parsing
+const PROGRESS_LINES: usize = 10_000;
+/// Number of bytes between two progress reports of [`Job::report_reads`]
+const PROGRESS_BYTES: u64 = 1 << 20;
+
+/// Called with the stage, the completed and the total amount of work. Returns an error if the job was
+/// cancelled. It may be called from worker threads, so the completed counts may arrive out of order.
+pub type Progress<'a> = &'a (dyn Fn(ProgressStage, usize, usize) -> Result<(), String> + Sync);
+
+/// Receives the progress of a job, e.g., to send it to the frontend as an event
+pub type ProgressSink = Box<dyn Fn(JobProgress) + Send + Sync>;
+
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum ProgressStage {
+    /// Bytes of the (possibly compressed) input files
+    BytesRead,
+    LinesParsed,
+    TermsTested,
+    Resampling,
+    /// MCMC steps of all chains of MGSA
+    McmcSteps,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JobProgress {
+    pub job: String,
+    pub stage: ProgressStage,
+    pub done: u64,
+    /// Unknown, e.g., for the number of lines of a file
+    pub total: Option<u64>,
+}
+
+/// The cancellation tokens of the running jobs
+#[derive(Default)]
+pub struct JobState(Arc<Mutex<HashMap<String, CancellationToken>>>);
+
+impl JobState {
+    /// Register a job, whose progress is passed to `sink`. The job is unregistered when the returned
+    /// [`Job`] is dropped.
+    pub fn start<F>(&self, id: &str, sink: F) -> Result<Job, String>
+    where
+        F: Fn(JobProgress) + Send + Sync + 'static,
+    {
+        let mut jobs = self.0.lock().map_err(|e| e.to_string())?;
+        if jobs.contains_key(id) {
+            return Err(format!("Job {} is already running", id));
+        }
+        let token = CancellationToken::default();
+        jobs.insert(id.to_string(), token.clone());
+        Ok(Job {
+            id: id.to_string(),
+            token,
+            sink: Some(Box::new(sink)),
+            jobs: Some(Arc::clone(&self.0)),
+            bytes_read: Arc::default(),
+            bytes_total: AtomicU64::new(0),
+            files: Mutex::default(),
+        })
+    }
+
+    /// Stop the job `id`. It returns an error the next time it checks for cancellation.
+    pub fn cancel(&self, id: &str) -> Result<(), String> {
+        let jobs = self.0.lock().map_err(|e| e.to_string())?;
+        match jobs.get(id) {
+            Some(token) => {
+                token.cancel();
+                Ok(())
+            }
+            None => Err(format!("Job {} is not running", id)),
+        }
+    }
+}
+
+/// A running job, which reports its progress to a sink. The default job is not registered, so it
+/// reports nothing and cannot be cancelled with [`JobState::cancel`], e.g., for tests and the
+/// command-line binary.
+#[derive(Default)]
+pub struct Job {
+    id: String,
+    token: CancellationToken,
+    sink: Option<ProgressSink>,
+    jobs: Option<Arc<Mutex<HashMap<String, CancellationToken>>>>,
+    bytes_read: Arc<AtomicU64>,
+    bytes_total: AtomicU64,
+    /// The files read by the job, in the order they were opened
+    files: Mutex<Vec<Arc<Mutex<FileDigest>>>>,
+}
+
+/// Size and CRC-32 of the bytes read from one file
+struct FileDigest {
+    path: String,
+    bytes: u64,
+    crc: Crc,
+}
+
+impl Job {
+    /// An error if the job was cancelled
+    pub fn check(&self) -> Result<(), String> {
+        if self.token.is_cancelled() {
+            Err(CANCELLED.to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Emit a progress event, or an error if the job was cancelled
+    pub fn progress(
+        &self,
+        stage: ProgressStage,
+        done: u64,
+        total: Option<u64>,
+    ) -> Result<(), String> {
+        self.check()?;
+        if let Some(sink) = &self.sink {
+            sink(JobProgress {
+                job: self.id.clone(),
+                stage,
+                done,
+                total,
+            });
+        }
+        Ok(())
+    }
+
+    /// Report the number of parsed lines and the bytes read so far, every few thousand lines
+    pub fn parsed_lines(&self, lines: usize) -> Result<(), String> {
+        if !lines.is_multiple_of(PROGRESS_LINES) {
+            return Ok(());
+        }
+        let total = self.bytes_total.load(Ordering::Relaxed);
+        let read = self.bytes_read.load(Ordering::Relaxed);
+        self.progress(ProgressStage::BytesRead, read, Some(total))?;
+        self.progress(ProgressStage::LinesParsed, lines as u64, None)
+    }
+
+    /// Count the bytes read from `reader`, which has `size` bytes, as the progress of the job, and
+    /// compute the CRC-32 of the file `path` it reads from
+    pub fn track_reads<R: Read>(&self, path: &str, reader: R, size: u64) -> CountingReader<R> {
+        self.bytes_total.fetch_add(size, Ordering::Relaxed);
+        let digest = Arc::new(Mutex::new(FileDigest {
+            path: path.to_string(),
+            bytes: 0,
+            crc: Crc::new(),
+        }));
+        if let Ok(mut files) = self.files.lock() {
+            files.push(Arc::clone(&digest));
+        }
+        CountingReader {
+            inner: reader,
+            count: Arc::clone(&self.bytes_read),
+            digest,
+        }
+    }
+
+    /// Open the file `path`, whose reads are tracked with [`Job::track_reads`]
+    pub fn open(&self, path: &str) -> Result<CountingReader<File>, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(self.track_reads(path, file, size))
+    }
+
+    /// Report the bytes read so far while `reader` is read, for parsers that do not report the lines
+    /// they parse (e.g., of JSON files). Reading fails once the job is cancelled.
+    pub fn report_reads<R: Read>(&self, reader: R) -> ProgressReader<'_, R> {
+        ProgressReader {
+            inner: reader,
+            job: self,
+            reported: 0,
+        }
+    }
+
+    /// The files read by the job with their size and CRC-32, which were computed while they were
+    /// parsed, so they are complete once the files have been read to the end
+    pub fn input_files(&self) -> Result<Vec<InputFile>, String> {
+        let files = self.files.lock().map_err(|e| e.to_string())?;
+        files
+            .iter()
+            .map(|digest| {
+                let digest = digest.lock().map_err(|e| e.to_string())?;
+                Ok(InputFile {
+                    path: digest.path.clone(),
+                    bytes: digest.bytes,
+                    crc32: format!("{:08x}", digest.crc.sum()),
+                })
+            })
+            .collect()
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        if let Some(jobs) = &self.jobs {
+            if let Ok(mut jobs) = jobs.lock() {
+                jobs.remove(&self.id);
+            }
+        }
+    }
+}
+
+pub struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+    digest: Arc<Mutex<FileDigest>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        if let Ok(mut digest) = self.digest.lock() {
+            digest.bytes += n as u64;
+            digest.crc.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    job: &'a Job,
+    reported: u64,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let read = self.job.bytes_read.load(Ordering::Relaxed);
+        if n == 0 || read >= self.reported + PROGRESS_BYTES {
+            self.reported = read;
+            let total = self.job.bytes_total.load(Ordering::Relaxed);
+            self.job
+                .progress(ProgressStage::BytesRead, read, Some(total))
+                .map_err(io::Error::other)?;
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_cancel() {
+        let job = Job::default();
+        assert!(job.progress(ProgressStage::TermsTested, 1, Some(2)).is_ok());
+        job.token.cancel();
+        assert_eq!(Err(CANCELLED.to_string()), job.check());
+        assert!(job.parsed_lines(PROGRESS_LINES).is_err());
+        // only checked every few thousand lines
+        assert!(job.parsed_lines(PROGRESS_LINES + 1).is_ok());
+    }
+
+    #[test]
+    fn test_job_state() {
+        let jobs = JobState::default();
+        let received = Arc::new(Mutex::new(vec![]));
+        let sink = Arc::clone(&received);
+        let job = jobs
+            .start("load", move |p| sink.lock().unwrap().push((p.job, p.done)))
+            .unwrap();
+        assert!(jobs.start("load", |_| {}).is_err());
+        job.progress(ProgressStage::LinesParsed, 7, None).unwrap();
+        assert_eq!(vec![("load".to_string(), 7)], *received.lock().unwrap());
+        jobs.cancel("load").unwrap();
+        assert!(job.progress(ProgressStage::LinesParsed, 8, None).is_err());
+        assert_eq!(1, received.lock().unwrap().len());
+        drop(job);
+        // the job is unregistered when it is dropped
+        assert!(jobs.cancel("load").is_err());
+    }
+
+    #[test]
+    fn test_counting_reader() {
+        let job = Job::default();
+        let mut content = String::new();
+        job.track_reads("go.obo", "GO:0008150\n".as_bytes(), 11)
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(11, job.bytes_read.load(Ordering::Relaxed));
+        assert_eq!(11, job.bytes_total.load(Ordering::Relaxed));
+        let mut check = String::new();
+        job.track_reads("check.txt", "123456789".as_bytes(), 9)
+            .read_to_string(&mut check)
+            .unwrap();
+        let files = job.input_files().unwrap();
+        assert_eq!(2, files.len());
+        assert_eq!("go.obo", files[0].path);
+        assert_eq!(11, files[0].bytes);
+        assert_eq!(9, files[1].bytes);
+        // the check value of CRC-32
+        assert_eq!("cbf43926", files[1].crc32);
+        assert!(job.open("does/not/exist.gaf").is_err());
+    }
+
+    #[test]
+    fn test_progress_reader() {
+        let jobs = JobState::default();
+        let received = Arc::new(Mutex::new(vec![]));
+        let sink = Arc::clone(&received);
+        let job = jobs
+            .start("load", move |p| {
+                sink.lock().unwrap().push((p.done, p.total))
+            })
+            .unwrap();
+        let content = vec![b'x'; 3 << 20];
+        let mut reader = job.report_reads(job.track_reads("go.json", content.as_slice(), 3 << 20));
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        let received = received.lock().unwrap().clone();
+        // at least every PROGRESS_BYTES bytes and at the end
+        assert!(received.len() >= 3);
+        assert_eq!(Some(&(3 << 20, Some(3 << 20))), received.last());
+        jobs.cancel("load").unwrap();
+        let mut reader = job.report_reads("GO:0008150\n".as_bytes());
+        let error = reader.read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(CANCELLED, error.to_string());
+    }
+}
@@ -0,0 +1,28 @@
+//! The analysis core of the Ontologizer.
+//!
+//! The modules are shared by the desktop application (the `Ontologizer` crate, which wraps them in
+//! Tauri commands) and the command-line binary (`bin/ontologizer-cli.rs`), so both compute the same
+//! results. Nothing in this crate depends on Tauri, so the command-line binary builds and runs on
+//! machines without a display.
+
+pub mod annotation_filter;
+pub mod annotation_index;
+pub mod annotation_stats;
+pub mod diagnostics;
+pub mod enrichment;
+pub mod evidence;
+pub mod exact_fisher;
+pub mod export;
+pub mod gaf;
+pub mod goannot;
+pub mod gontology;
+pub mod gpad;
+pub mod jobs;
+pub mod mgsa;
+pub mod mtc;
+pub mod obo_parser;
+pub mod resampling;
+pub mod session;
+#[cfg(test)]
+pub(crate) mod test_fixtures;
+pub mod topgo;
@@ -0,0 +1,626 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::annotation_index::AnnotationIndex;
+use crate::enrichment::TermGenes;
+use crate::gontology::{GeneOntology, Namespace};
+use crate::jobs::{Progress, ProgressStage};
+
+/// Values of alpha and beta that are sampled if they are not fixed by the user. Error rates above 0.5
+/// are excluded, since they would allow a degenerate model in which the study set is unrelated to the
+/// active terms.
+const ALPHA_BETA_GRID: [f64; 10] = [0.01, 0.05, 0.1, 0.15, 0.2, 0.25, 0.3, 0.35, 0.4, 0.45];
+/// Number of grid points for p, which are spread evenly between 1/#terms and 0.5
+const P_GRID_SIZE: usize = 20;
+/// Probability that an MCMC step updates one of the free parameters instead of toggling a term
+const PARAMETER_UPDATE_PROBABILITY: f64 = 0.1;
+/// Number of MCMC steps between two progress reports
+const PROGRESS_STEPS: usize = 1_000;
+
+/// Settings for Model-based Gene Set Analysis (Bauer et al., 2010).
+///
+/// `alpha` is the false-positive rate (a gene is in the study set although none of its terms is active),
+/// `beta` the false-negative rate, and `p` the prior probability of a term being active. Parameters that
+/// are `None` are integrated over a grid of values by the sampler.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MgsaConfig {
+    pub alpha: Option<f64>,
+    pub beta: Option<f64>,
+    pub p: Option<f64>,
+    pub steps: usize,
+    pub burn_in: usize,
+    pub chains: usize,
+    pub seed: u64,
+}
+
+impl Default for MgsaConfig {
+    fn default() -> Self {
+        MgsaConfig {
+            alpha: None,
+            beta: None,
+            p: None,
+            steps: 500_000,
+            burn_in: 100_000,
+            chains: 2,
+            seed: 42,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MgsaTermResult {
+    pub term_id: String,
+    pub label: String,
+    pub namespace: Option<Namespace>,
+    pub population_count: usize,
+    pub study_count: usize,
+    /// Marginal posterior probability that the term is active
+    pub marginal: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MgsaDiagnostics {
+    pub chains: usize,
+    pub steps: usize,
+    pub burn_in: usize,
+    pub acceptance_rates: Vec<f64>,
+    /// Gelman-Rubin potential scale reduction factor of the log posterior; values close to 1 indicate
+    /// convergence. It needs at least two chains with two samples each.
+    pub r_hat: Option<f64>,
+    /// Largest difference between the marginals that the individual chains estimated for a term
+    pub max_marginal_difference: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MgsaResult {
+    pub population_total: usize,
+    pub study_total: usize,
+    pub alpha_mean: f64,
+    pub beta_mean: f64,
+    pub p_mean: f64,
+    pub results: Vec<MgsaTermResult>,
+    pub diagnostics: MgsaDiagnostics,
+}
+
+/// A model parameter that is either fixed or sampled from a grid (stored as index into the grid)
+struct Parameter {
+    grid: Vec<f64>,
+    idx: usize,
+}
+
+impl Parameter {
+    fn new(fixed: Option<f64>, grid: &[f64]) -> Result<Self, String> {
+        match fixed {
+            Some(v) if !(v > 0.0 && v < 1.0) => Err(format!(
+                "MGSA parameters must be between 0 and 1 but got {}",
+                v
+            )),
+            Some(v) => Ok(Parameter {
+                grid: vec![v],
+                idx: 0,
+            }),
+            None => Ok(Parameter {
+                grid: grid.to_vec(),
+                idx: grid.len() / 2,
+            }),
+        }
+    }
+
+    fn value(&self) -> f64 {
+        self.grid[self.idx]
+    }
+
+    fn is_free(&self) -> bool {
+        self.grid.len() > 1
+    }
+}
+
+/// An accepted proposal of a Metropolis step
+enum Move {
+    Parameter,
+    /// The term was switched on or off
+    Toggle(usize),
+}
+
+/// The Bayesian network: terms (hidden, on/off) -> genes (hidden) -> observed study set membership
+struct MgsaModel {
+    term_genes: Vec<Vec<usize>>,
+    observed: Vec<bool>,
+}
+
+/// State of one Markov chain
+struct Chain<'a> {
+    model: &'a MgsaModel,
+    active: Vec<bool>,
+    active_count: usize,
+    /// Number of active terms annotated to each gene. A gene is hidden-on if this is positive.
+    cover: Vec<usize>,
+    /// Counts of genes by (hidden state, observed state)
+    n00: usize,
+    n01: usize,
+    n10: usize,
+    n11: usize,
+    params: [Parameter; 3],
+}
+
+impl<'a> Chain<'a> {
+    fn new(model: &'a MgsaModel, config: &MgsaConfig) -> Result<Self, String> {
+        let term_count = model.term_genes.len();
+        let lowest_p = 1.0 / term_count.max(2) as f64;
+        let p_grid: Vec<f64> = (0..P_GRID_SIZE)
+            .map(|i| lowest_p + (0.5 - lowest_p) * i as f64 / (P_GRID_SIZE - 1) as f64)
+            .collect();
+        let n01 = model.observed.iter().filter(|&&o| o).count();
+        Ok(Chain {
+            model,
+            active: vec![false; term_count],
+            active_count: 0,
+            cover: vec![0; model.observed.len()],
+            n00: model.observed.len() - n01,
+            n01,
+            n10: 0,
+            n11: 0,
+            params: [
+                Parameter::new(config.alpha, &ALPHA_BETA_GRID)?,
+                Parameter::new(config.beta, &ALPHA_BETA_GRID)?,
+                Parameter::new(config.p, &p_grid)?,
+            ],
+        })
+    }
+
+    fn log_posterior(&self) -> f64 {
+        let alpha = self.params[0].value();
+        let beta = self.params[1].value();
+        let p = self.params[2].value();
+        let inactive = self.active.len() - self.active_count;
+        self.n01 as f64 * alpha.ln()
+            + self.n00 as f64 * (1.0 - alpha).ln()
+            + self.n10 as f64 * beta.ln()
+            + self.n11 as f64 * (1.0 - beta).ln()
+            + self.active_count as f64 * p.ln()
+            + inactive as f64 * (1.0 - p).ln()
+    }
+
+    fn toggle(&mut self, term: usize) {
+        let activate = !self.active[term];
+        self.active[term] = activate;
+        if activate {
+            self.active_count += 1;
+        } else {
+            self.active_count -= 1;
+        }
+        for &g in &self.model.term_genes[term] {
+            let observed = self.model.observed[g];
+            if activate {
+                self.cover[g] += 1;
+                if self.cover[g] == 1 {
+                    self.move_gene(observed, true);
+                }
+            } else {
+                self.cover[g] -= 1;
+                if self.cover[g] == 0 {
+                    self.move_gene(observed, false);
+                }
+            }
+        }
+    }
+
+    /// Update the counts when the hidden state of a gene changes
+    fn move_gene(&mut self, observed: bool, hidden_on: bool) {
+        match (observed, hidden_on) {
+            (false, true) => {
+                self.n00 -= 1;
+                self.n10 += 1;
+            }
+            (true, true) => {
+                self.n01 -= 1;
+                self.n11 += 1;
+            }
+            (false, false) => {
+                self.n10 -= 1;
+                self.n00 += 1;
+            }
+            (true, false) => {
+                self.n11 -= 1;
+                self.n01 += 1;
+            }
+        }
+    }
+
+    /// One Metropolis step: toggle a random term or move one of the free parameters to another grid value.
+    /// Both proposals are symmetric. Returns the proposal if it was accepted.
+    fn step(&mut self, rng: &mut StdRng) -> Option<Move> {
+        let current = self.log_posterior();
+        let free: Vec<usize> = (0..3).filter(|&i| self.params[i].is_free()).collect();
+        if !free.is_empty() && rng.gen::<f64>() < PARAMETER_UPDATE_PROBABILITY {
+            let param = free[rng.gen_range(0..free.len())];
+            let old_idx = self.params[param].idx;
+            self.params[param].idx = rng.gen_range(0..self.params[param].grid.len());
+            if accept(rng, self.log_posterior() - current) {
+                return Some(Move::Parameter);
+            }
+            self.params[param].idx = old_idx;
+            None
+        } else {
+            let term = rng.gen_range(0..self.active.len());
+            self.toggle(term);
+            if accept(rng, self.log_posterior() - current) {
+                return Some(Move::Toggle(term));
+            }
+            self.toggle(term);
+            None
+        }
+    }
+}
+
+fn accept(rng: &mut StdRng, log_ratio: f64) -> bool {
+    log_ratio >= 0.0 || rng.gen::<f64>().ln() < log_ratio
+}
+
+/// The summary of one chain after burn-in
+struct ChainSummary {
+    active_frequency: Vec<f64>,
+    param_means: [f64; 3],
+    acceptance_rate: f64,
+    log_posterior_mean: f64,
+    log_posterior_variance: f64,
+}
+
+/// Run one chain, passing the number of completed steps to `progress` every [`PROGRESS_STEPS`] steps
+fn run_chain(
+    model: &MgsaModel,
+    config: &MgsaConfig,
+    seed: u64,
+    progress: &dyn Fn(usize) -> Result<(), String>,
+) -> Result<ChainSummary, String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut chain = Chain::new(model, config)?;
+    // the number of samples in which each term was active is only updated when the term is switched
+    // off, from the sample at which it was switched on, so that a step does not visit all terms
+    let mut active_counts = vec![0usize; model.term_genes.len()];
+    let mut active_since = vec![0usize; model.term_genes.len()];
+    let mut param_sums = [0.0; 3];
+    let mut accepted = 0;
+    // Welford's online algorithm for the mean and variance of the log posterior
+    let mut mean = 0.0;
+    let mut m2 = 0.0;
+    let mut samples = 0;
+    for i in 0..config.steps {
+        let step = chain.step(&mut rng);
+        if step.is_some() {
+            accepted += 1;
+        }
+        if (i + 1) % PROGRESS_STEPS == 0 || i + 1 == config.steps {
+            progress(i + 1)?;
+        }
+        if i < config.burn_in {
+            continue;
+        }
+        // the terms that are active in the first sample count from it, as active_since is zero
+        if let Some(Move::Toggle(t)) = step.filter(|_| samples > 0) {
+            if chain.active[t] {
+                active_since[t] = samples;
+            } else {
+                active_counts[t] += samples - active_since[t];
+            }
+        }
+        samples += 1;
+        for (sum, param) in param_sums.iter_mut().zip(chain.params.iter()) {
+            *sum += param.value();
+        }
+        let lp = chain.log_posterior();
+        let delta = lp - mean;
+        mean += delta / samples as f64;
+        m2 += delta * (lp - mean);
+    }
+    for (t, &a) in chain.active.iter().enumerate() {
+        if a {
+            active_counts[t] += samples - active_since[t];
+        }
+    }
+    let n = samples.max(1) as f64;
+    Ok(ChainSummary {
+        active_frequency: active_counts.iter().map(|&c| c as f64 / n).collect(),
+        param_means: param_sums.map(|s| s / n),
+        acceptance_rate: accepted as f64 / config.steps.max(1) as f64,
+        log_posterior_mean: mean,
+        log_posterior_variance: if samples > 1 { m2 / (n - 1.0) } else { 0.0 },
+    })
+}
+
+/// Gelman-Rubin statistic for chains of length `n` from their means and variances, None for fewer
+/// than two chains or samples
+fn gelman_rubin(summaries: &[ChainSummary], n: usize) -> Option<f64> {
+    let m = summaries.len() as f64;
+    if summaries.len() < 2 || n < 2 {
+        return None;
+    }
+    let n = n as f64;
+    let within = summaries
+        .iter()
+        .map(|s| s.log_posterior_variance)
+        .sum::<f64>()
+        / m;
+    let grand_mean = summaries.iter().map(|s| s.log_posterior_mean).sum::<f64>() / m;
+    let between_over_n = summaries
+        .iter()
+        .map(|s| (s.log_posterior_mean - grand_mean).powi(2))
+        .sum::<f64>()
+        / (m - 1.0);
+    if within == 0.0 {
+        return Some(1.0);
+    }
+    let var_hat = (n - 1.0) / n * within + between_over_n;
+    Some((var_hat / within).sqrt())
+}
+
+/// Model-based Gene Set Analysis.
+///
+/// All terms annotated to at least one population gene take part in the model. The result is sorted
+/// by decreasing marginal posterior probability. The steps of all chains are reported to `progress`,
+/// which stops the sampler by returning an error.
+pub fn mgsa(
+    go: &GeneOntology,
+    annotations: &AnnotationIndex,
+    study: &[String],
+    population: &[String],
+    config: &MgsaConfig,
+    progress: Progress,
+) -> Result<MgsaResult, String> {
+    if config.burn_in >= config.steps {
+        return Err(format!(
+            "MGSA burn-in ({}) must be smaller than the number of steps ({})",
+            config.burn_in, config.steps
+        ));
+    }
+    let genes = TermGenes::new(annotations, study, population);
+    // position of each population gene in the model
+    let gene_index: HashMap<usize, usize> = genes
+        .population
+        .iter()
+        .enumerate()
+        .map(|(i, g)| (g, i))
+        .collect();
+    let mut terms: Vec<usize> = genes.population_genes.keys().copied().collect();
+    terms.sort();
+    if terms.is_empty() {
+        return Err("None of the population genes is annotated".to_string());
+    }
+    let model = MgsaModel {
+        term_genes: terms
+            .iter()
+            .map(|t| {
+                genes.population_genes[t]
+                    .iter()
+                    .map(|g| gene_index[&g])
+                    .collect()
+            })
+            .collect(),
+        observed: genes
+            .population
+            .iter()
+            .map(|g| genes.study.contains(g))
+            .collect(),
+    };
+    let chains = config.chains.max(1);
+    let total_steps = chains * config.steps;
+    let summaries = (0..chains)
+        .map(|c| {
+            run_chain(
+                &model,
+                config,
+                config.seed.wrapping_add(c as u64),
+                &|done| {
+                    progress(
+                        ProgressStage::McmcSteps,
+                        c * config.steps + done,
+                        total_steps,
+                    )
+                },
+            )
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut max_marginal_difference: f64 = 0.0;
+    let mut results = vec![];
+    for (i, &term) in terms.iter().enumerate() {
+        let freqs: Vec<f64> = summaries.iter().map(|s| s.active_frequency[i]).collect();
+        let lowest = freqs.iter().cloned().fold(f64::INFINITY, f64::min);
+        let highest = freqs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        max_marginal_difference = max_marginal_difference.max(highest - lowest);
+        let go_term = go.term_at(term);
+        results.push(MgsaTermResult {
+            term_id: go_term.id.value.clone(),
+            label: go_term.label.clone(),
+            namespace: go_term.namespace,
+            population_count: genes.population_genes[&term].len(),
+            study_count: genes.study_genes.get(&term).map_or(0, |g| g.len()),
+            marginal: freqs.iter().sum::<f64>() / chains as f64,
+        });
+    }
+    results.sort_by(|a, b| {
+        b.marginal
+            .total_cmp(&a.marginal)
+            .then_with(|| a.term_id.cmp(&b.term_id))
+    });
+    let param_mean =
+        |i: usize| summaries.iter().map(|s| s.param_means[i]).sum::<f64>() / chains as f64;
+    Ok(MgsaResult {
+        population_total: genes.population.len(),
+        study_total: genes.study.len(),
+        alpha_mean: param_mean(0),
+        beta_mean: param_mean(1),
+        p_mean: param_mean(2),
+        results,
+        diagnostics: MgsaDiagnostics {
+            chains,
+            steps: config.steps,
+            burn_in: config.burn_in,
+            acceptance_rates: summaries.iter().map(|s| s.acceptance_rate).collect(),
+            r_hat: gelman_rubin(&summaries, config.steps - config.burn_in),
+            max_marginal_difference,
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use float_eq::float_eq;
+    use std::assert_eq;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::gontology::TermRelation;
+    use crate::jobs::CANCELLED;
+    use crate::test_fixtures::{annotate, ontology};
+
+    /// root <- A, root <- B, root <- C. Each of A, B and C has ten genes of its own, g1..g10 are
+    /// annotated to A, g11..g20 to B and g21..g30 to C.
+    fn fixture() -> (GeneOntology, AnnotationIndex) {
+        let go = ontology(
+            &[
+                ("GO:0000001", "root"),
+                ("GO:0000002", "A"),
+                ("GO:0000003", "B"),
+                ("GO:0000004", "C"),
+            ],
+            &[
+                ("GO:0000002", TermRelation::IsA, "GO:0000001"),
+                ("GO:0000003", TermRelation::IsA, "GO:0000001"),
+                ("GO:0000004", TermRelation::IsA, "GO:0000001"),
+            ],
+        );
+        let annots = annotate(&[("GO:0000002", 10), ("GO:0000003", 10), ("GO:0000004", 10)]);
+        let annotations = AnnotationIndex::new(&go, &annots);
+        (go, annotations)
+    }
+
+    fn config() -> MgsaConfig {
+        MgsaConfig {
+            steps: 20_000,
+            burn_in: 5_000,
+            ..MgsaConfig::default()
+        }
+    }
+
+    /// The study set consists of 9 of the 10 genes of B and one gene of C
+    fn study() -> Vec<String> {
+        (11..20).chain(26..27).map(|i| format!("g{}", i)).collect()
+    }
+
+    #[test]
+    fn test_mgsa_finds_active_term() {
+        let (go, annotations) = fixture();
+        let result = mgsa(&go, &annotations, &study(), &[], &config(), &|_, _, _| {
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(30, result.population_total);
+        assert_eq!(10, result.study_total);
+        assert_eq!("B", result.results[0].label);
+        assert!(result.results[0].marginal > 0.9);
+        for other in &result.results[1..] {
+            assert!(other.marginal < 0.5, "{} {}", other.label, other.marginal);
+        }
+        assert!(result.diagnostics.r_hat.unwrap() < 1.1);
+        assert_eq!(2, result.diagnostics.acceptance_rates.len());
+    }
+
+    #[test]
+    fn test_mgsa_is_reproducible() {
+        let (go, annotations) = fixture();
+        let first = mgsa(&go, &annotations, &study(), &[], &config(), &|_, _, _| {
+            Ok(())
+        })
+        .unwrap();
+        let second = mgsa(&go, &annotations, &study(), &[], &config(), &|_, _, _| {
+            Ok(())
+        })
+        .unwrap();
+        let marginals = |r: &MgsaResult| r.results.iter().map(|t| t.marginal).collect::<Vec<_>>();
+        assert_eq!(marginals(&first), marginals(&second));
+    }
+
+    #[test]
+    fn test_progress_and_cancellation() {
+        let (go, annotations) = fixture();
+        let reports = Mutex::new(vec![]);
+        mgsa(
+            &go,
+            &annotations,
+            &study(),
+            &[],
+            &config(),
+            &|stage, done, total| {
+                assert_eq!(ProgressStage::McmcSteps, stage);
+                reports.lock().unwrap().push((done, total));
+                Ok(())
+            },
+        )
+        .unwrap();
+        let reports = reports.into_inner().unwrap();
+        // both chains, every PROGRESS_STEPS steps
+        assert_eq!(2 * 20_000 / PROGRESS_STEPS, reports.len());
+        assert_eq!(Some(&(40_000, 40_000)), reports.last());
+        let cancelled = mgsa(
+            &go,
+            &annotations,
+            &study(),
+            &[],
+            &config(),
+            &|_, done, _| {
+                if done > 5_000 {
+                    Err(CANCELLED.to_string())
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert_eq!(CANCELLED, cancelled.unwrap_err());
+    }
+
+    #[test]
+    fn test_single_chain() {
+        let (go, annotations) = fixture();
+        let config = MgsaConfig {
+            chains: 1,
+            ..config()
+        };
+        let result = mgsa(&go, &annotations, &study(), &[], &config, &|_, _, _| Ok(())).unwrap();
+        assert_eq!("B", result.results[0].label);
+        assert_eq!(None, result.diagnostics.r_hat);
+        assert_eq!(0.0, result.diagnostics.max_marginal_difference);
+        let json = serde_json::to_value(&result.diagnostics).unwrap();
+        assert!(json["r_hat"].is_null());
+    }
+
+    #[test]
+    fn test_fixed_parameters() {
+        let (go, annotations) = fixture();
+        let config = MgsaConfig {
+            alpha: Some(0.1),
+            beta: Some(0.2),
+            p: Some(0.25),
+            ..config()
+        };
+        let result = mgsa(&go, &annotations, &study(), &[], &config, &|_, _, _| Ok(())).unwrap();
+        assert!(float_eq!(0.1, result.alpha_mean, rmax <= 1e-9));
+        assert!(float_eq!(0.2, result.beta_mean, rmax <= 1e-9));
+        assert!(float_eq!(0.25, result.p_mean, rmax <= 1e-9));
+        let invalid = MgsaConfig {
+            alpha: Some(1.5),
+            ..config
+        };
+        assert!(mgsa(
+            &go,
+            &annotations,
+            &study(),
+            &[],
+            &invalid,
+            &|_, _, _| Ok(())
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use serde::{Deserialize, Serialize};
+
+use crate::resampling::ResamplingConfig;
+
+/// A procedure that adjusts the p-values of all terms tested in one analysis for multiple testing
+pub trait PValueAdjustment {
+    /// Return the adjusted p-values in the same order as `p_values`
+    fn adjust(&self, p_values: &[f64]) -> Vec<f64>;
+}
+
+/// Indices of `p_values` in ascending order of the p-values
+pub(crate) fn ascending_order(p_values: &[f64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..p_values.len()).collect();
+    order.sort_by(|&a, &b| p_values[a].total_cmp(&p_values[b]));
+    order
+}
+
+pub struct Bonferroni;
+
+impl PValueAdjustment for Bonferroni {
+    fn adjust(&self, p_values: &[f64]) -> Vec<f64> {
+        let m = p_values.len() as f64;
+        p_values.iter().map(|p| (p * m).min(1.0)).collect()
+    }
+}
+
+/// Holm's step-down version of Bonferroni: the i-th smallest p-value is multiplied by m - i + 1
+pub struct Holm;
+
+impl PValueAdjustment for Holm {
+    fn adjust(&self, p_values: &[f64]) -> Vec<f64> {
+        let m = p_values.len();
+        let mut adjusted = vec![0.0; m];
+        let mut running_max: f64 = 0.0;
+        for (rank, i) in ascending_order(p_values).into_iter().enumerate() {
+            running_max = running_max.max(((m - rank) as f64 * p_values[i]).min(1.0));
+            adjusted[i] = running_max;
+        }
+        adjusted
+    }
+}
+
+/// Step-up control of the false discovery rate (Benjamini and Hochberg, 1995).
+///
+/// `dependency_factor` is 1 for the original procedure and sum(1/i) for Benjamini and Yekutieli (2001),
+/// which is valid under arbitrary dependency between the tests.
+fn step_up(p_values: &[f64], dependency_factor: f64) -> Vec<f64> {
+    let m = p_values.len();
+    let mut adjusted = vec![0.0; m];
+    let mut running_min: f64 = 1.0;
+    for (rank, i) in ascending_order(p_values).into_iter().enumerate().rev() {
+        let p = dependency_factor * m as f64 * p_values[i] / (rank + 1) as f64;
+        running_min = running_min.min(p.min(1.0));
+        adjusted[i] = running_min;
+    }
+    adjusted
+}
+
+pub struct BenjaminiHochberg;
+
+impl PValueAdjustment for BenjaminiHochberg {
+    fn adjust(&self, p_values: &[f64]) -> Vec<f64> {
+        step_up(p_values, 1.0)
+    }
+}
+
+pub struct BenjaminiYekutieli;
+
+impl PValueAdjustment for BenjaminiYekutieli {
+    fn adjust(&self, p_values: &[f64]) -> Vec<f64> {
+        let harmonic: f64 = (1..=p_values.len()).map(|i| 1.0 / i as f64).sum();
+        step_up(p_values, harmonic)
+    }
+}
+
+/// Storey's q-values: the Benjamini-Hochberg adjustment scaled by the estimated proportion of true null
+/// hypotheses pi0 = (#{p > lambda} + 1) / (m (1 - lambda)) (Storey, Taylor and Siegmund, 2004)
+pub struct StoreyQValue {
+    pub lambda: f64,
+}
+
+impl StoreyQValue {
+    pub fn pi0(&self, p_values: &[f64]) -> f64 {
+        let above = p_values.iter().filter(|&&p| p > self.lambda).count();
+        let m = p_values.len().max(1) as f64;
+        ((above + 1) as f64 / (m * (1.0 - self.lambda))).min(1.0)
+    }
+}
+
+impl PValueAdjustment for StoreyQValue {
+    fn adjust(&self, p_values: &[f64]) -> Vec<f64> {
+        let pi0 = self.pi0(p_values);
+        step_up(p_values, 1.0)
+            .into_iter()
+            .map(|q| q * pi0)
+            .collect()
+    }
+}
+
+pub struct NoCorrection;
+
+impl PValueAdjustment for NoCorrection {
+    fn adjust(&self, p_values: &[f64]) -> Vec<f64> {
+        p_values.to_vec()
+    }
+}
+
+/// The corrections that can be selected for an analysis
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum MultipleTestingCorrection {
+    #[default]
+    None,
+    Bonferroni,
+    /// Holm's method is also known as Bonferroni-Holm step-down
+    #[serde(alias = "BonferroniHolm")]
+    Holm,
+    BenjaminiHochberg,
+    BenjaminiYekutieli,
+    StoreyQValue,
+    /// Westfall-Young single-step min-P, which accounts for the dependency between the terms
+    WestfallYoungSingleStep(ResamplingConfig),
+    /// Westfall-Young step-down min-P, which is uniformly more powerful than the single-step version
+    WestfallYoungStepDown(ResamplingConfig),
+}
+
+impl MultipleTestingCorrection {
+    /// The adjustment procedure, or None for the Westfall-Young corrections, which need to rerun the
+    /// analysis on random study sets (see [`crate::resampling::westfall_young`])
+    pub fn procedure(&self) -> Option<Box<dyn PValueAdjustment>> {
+        let procedure: Box<dyn PValueAdjustment> = match self {
+            MultipleTestingCorrection::None => Box::new(NoCorrection),
+            MultipleTestingCorrection::Bonferroni => Box::new(Bonferroni),
+            MultipleTestingCorrection::Holm => Box::new(Holm),
+            MultipleTestingCorrection::BenjaminiHochberg => Box::new(BenjaminiHochberg),
+            MultipleTestingCorrection::BenjaminiYekutieli => Box::new(BenjaminiYekutieli),
+            MultipleTestingCorrection::StoreyQValue => Box::new(StoreyQValue { lambda: 0.5 }),
+            MultipleTestingCorrection::WestfallYoungSingleStep(_)
+            | MultipleTestingCorrection::WestfallYoungStepDown(_) => return None,
+        };
+        Some(procedure)
+    }
+
+    pub fn adjust(&self, p_values: &[f64]) -> Option<Vec<f64>> {
+        self.procedure().map(|procedure| procedure.adjust(p_values))
+    }
+
+    /// The correction with the name of the variant, ignoring case, dashes and underscores (e.g.,
+    /// `benjamini-hochberg`). `config` is used for the Westfall-Young corrections.
+    pub fn from_name(name: &str, config: ResamplingConfig) -> Result<Self, String> {
+        let correction = match name.replace(['-', '_'], "").to_lowercase().as_str() {
+            "none" => MultipleTestingCorrection::None,
+            "bonferroni" => MultipleTestingCorrection::Bonferroni,
+            "holm" | "bonferroniholm" => MultipleTestingCorrection::Holm,
+            "benjaminihochberg" => MultipleTestingCorrection::BenjaminiHochberg,
+            "benjaminiyekutieli" => MultipleTestingCorrection::BenjaminiYekutieli,
+            "storeyqvalue" => MultipleTestingCorrection::StoreyQValue,
+            "westfallyoungsinglestep" => MultipleTestingCorrection::WestfallYoungSingleStep(config),
+            "westfallyoungstepdown" => MultipleTestingCorrection::WestfallYoungStepDown(config),
+            _ => return Err(format!("Unknown multiple testing correction: {}", name)),
+        };
+        Ok(correction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use float_eq::float_eq;
+
+    use super::*;
+
+    const P_VALUES: [f64; 6] = [0.01, 0.04, 0.03, 0.005, 0.2, 0.6];
+
+    /// Compare with the values of p.adjust(c(0.01, 0.04, 0.03, 0.005, 0.2, 0.6), method) in R
+    fn check(correction: MultipleTestingCorrection, expected: [f64; 6]) {
+        let adjusted = correction.adjust(&P_VALUES).unwrap();
+        for (e, a) in expected.iter().zip(adjusted.iter()) {
+            assert!(float_eq!(*e, *a, abs <= 1e-9));
+        }
+    }
+
+    #[test]
+    fn test_bonferroni() {
+        check(
+            MultipleTestingCorrection::Bonferroni,
+            [0.06, 0.24, 0.18, 0.03, 1.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn test_holm() {
+        check(
+            MultipleTestingCorrection::Holm,
+            [0.05, 0.12, 0.12, 0.03, 0.4, 0.6],
+        );
+    }
+
+    #[test]
+    fn test_benjamini_hochberg() {
+        check(
+            MultipleTestingCorrection::BenjaminiHochberg,
+            [0.03, 0.06, 0.06, 0.03, 0.24, 0.6],
+        );
+    }
+
+    #[test]
+    fn test_benjamini_yekutieli() {
+        check(
+            MultipleTestingCorrection::BenjaminiYekutieli,
+            [0.0735, 0.147, 0.147, 0.0735, 0.588, 1.0],
+        );
+    }
+
+    #[test]
+    fn test_storey_q_value() {
+        // one p-value above lambda = 0.5, so pi0 = 2 / 3
+        let storey = StoreyQValue { lambda: 0.5 };
+        assert!(float_eq!(2.0 / 3.0, storey.pi0(&P_VALUES), abs <= 1e-9));
+        check(
+            MultipleTestingCorrection::StoreyQValue,
+            [0.02, 0.04, 0.04, 0.02, 0.16, 0.4],
+        );
+    }
+
+    #[test]
+    fn test_bonferroni_holm_alias() {
+        let mtc: MultipleTestingCorrection = serde_json::from_str("\"BonferroniHolm\"").unwrap();
+        assert_eq!(MultipleTestingCorrection::Holm, mtc);
+    }
+
+    #[test]
+    fn test_westfall_young_config() {
+        let mtc: MultipleTestingCorrection =
+            serde_json::from_str(r#"{"WestfallYoungStepDown": {"seed": 7}}"#).unwrap();
+        let expected = ResamplingConfig {
+            resamplings: 1000,
+            seed: 7,
+        };
+        assert_eq!(
+            MultipleTestingCorrection::WestfallYoungStepDown(expected),
+            mtc
+        );
+        assert!(mtc.adjust(&P_VALUES).is_none());
+    }
+
+    #[test]
+    fn test_from_name() {
+        let config = ResamplingConfig::default();
+        let from_name = |name| MultipleTestingCorrection::from_name(name, config);
+        assert_eq!(
+            Ok(MultipleTestingCorrection::Holm),
+            from_name("Bonferroni-Holm")
+        );
+        assert_eq!(
+            Ok(MultipleTestingCorrection::BenjaminiHochberg),
+            from_name("benjamini_hochberg")
+        );
+        assert_eq!(
+            Ok(MultipleTestingCorrection::WestfallYoungStepDown(config)),
+            from_name("WestfallYoungStepDown")
+        );
+        assert!(from_name("fdr").is_err());
+    }
+}
@@ -0,0 +1,413 @@
+//! Parser for the OBO 1.4 flat file format used for go.obo and go-basic.obo.
+//!
+//! See https://owlcollab.github.io/oboformat/doc/obo-syntax.html. We only interpret the tags
+//! needed to build the [`GeneOntology`]; other tags (xref, subset, intersection_of, ...) are skipped.
+
+use std::io::BufRead;
+use std::str::FromStr;
+
+use crate::goannot::TermId;
+use crate::gontology::{
+    GeneOntology, GeneOntologyBuilder, GoTerm, Namespace, OntologyError, Synonym, SynonymScope,
+    TermRelation, Typedef,
+};
+
+enum Stanza {
+    Header,
+    Term(TermStanza),
+    Typedef(TypedefStanza),
+    /// e.g., [Instance], which does not occur in GO
+    Other,
+}
+
+#[derive(Default)]
+struct TermStanza {
+    id: Option<TermId>,
+    name: String,
+    namespace: Option<Namespace>,
+    definition: Option<String>,
+    is_obsolete: bool,
+    alt_ids: Vec<TermId>,
+    replaced_by: Vec<TermId>,
+    consider: Vec<TermId>,
+    synonyms: Vec<Synonym>,
+    relations: Vec<(TermRelation, String)>,
+}
+
+#[derive(Default)]
+struct TypedefStanza {
+    id: Option<String>,
+    name: String,
+}
+
+struct OboParser {
+    builder: GeneOntologyBuilder,
+    default_namespace: Option<Namespace>,
+    line_number: usize,
+}
+
+impl OboParser {
+    fn error(&self, msg: &str) -> OntologyError {
+        OntologyError::ParsingError(format!("line {}: {}", self.line_number, msg))
+    }
+
+    fn term_id(&self, value: &str) -> Result<TermId, OntologyError> {
+        TermId::from_curie(value).map_err(|e| self.error(&e.to_string()))
+    }
+
+    fn process_header_tag(&mut self, tag: &str, value: &str) -> Result<(), OntologyError> {
+        match tag {
+            "data-version" => {
+                self.builder.data_version(value);
+            }
+            "date" => {
+                self.builder.date(obo_date_to_iso(value));
+            }
+            // go.obo declares gene_ontology, which is not one of the three namespaces
+            "default-namespace" => self.default_namespace = Namespace::from_str(value).ok(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn process_term_tag(
+        &self,
+        term: &mut TermStanza,
+        tag: &str,
+        value: &str,
+    ) -> Result<(), OntologyError> {
+        match tag {
+            "id" => term.id = Some(self.term_id(value)?),
+            "name" => term.name = value.to_string(),
+            // other ontologies loaded with this parser, e.g., ECO, have their own namespaces
+            "namespace" => term.namespace = Namespace::from_str(value).ok(),
+            "def" => term.definition = Some(parse_quoted(value).map_err(|e| self.error(&e))?.0),
+            "alt_id" => term.alt_ids.push(self.term_id(value)?),
+            "is_obsolete" => term.is_obsolete = value == "true",
+            "replaced_by" => term.replaced_by.push(self.term_id(value)?),
+            "consider" => term.consider.push(self.term_id(value)?),
+            "synonym" => term.synonyms.push(self.parse_synonym(value)?),
+            "is_a" => term.relations.push((TermRelation::IsA, value.to_string())),
+            "relationship" => {
+                let (rel, target) = value
+                    .split_once(' ')
+                    .ok_or_else(|| self.error(&format!("Malformed relationship '{}'", value)))?;
+                term.relations.push((
+                    TermRelation::from_identifier(rel),
+                    target.trim().to_string(),
+                ));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// synonym: "cell-division cycle" RELATED [GOC:mah]
+    fn parse_synonym(&self, value: &str) -> Result<Synonym, OntologyError> {
+        let (text, rest) = parse_quoted(value).map_err(|e| self.error(&e))?;
+        let scope = match rest.split_whitespace().next() {
+            Some("EXACT") => SynonymScope::Exact,
+            Some("BROAD") => SynonymScope::Broad,
+            Some("NARROW") => SynonymScope::Narrow,
+            Some("RELATED") => SynonymScope::Related,
+            other => {
+                return Err(self.error(&format!(
+                    "Did not recognize synonym scope '{}'",
+                    other.unwrap_or_default()
+                )))
+            }
+        };
+        Ok(Synonym { value: text, scope })
+    }
+
+    fn finish_stanza(&mut self, stanza: Stanza) -> Result<(), OntologyError> {
+        match stanza {
+            Stanza::Term(term) => {
+                let id = term
+                    .id
+                    .ok_or_else(|| self.error("[Term] stanza without id"))?;
+                for (rel, target) in &term.relations {
+                    self.builder.add_relation(&id.value, rel.clone(), target);
+                }
+                let mut go_term = GoTerm::new(id, term.name);
+                go_term.namespace = term.namespace.or(self.default_namespace);
+                go_term.definition = term.definition;
+                go_term.is_obsolete = term.is_obsolete;
+                go_term.alt_ids = term.alt_ids;
+                go_term.replaced_by = term.replaced_by;
+                go_term.consider = term.consider;
+                go_term.synonyms = term.synonyms;
+                self.builder.add_term(go_term);
+            }
+            Stanza::Typedef(typedef) => {
+                let id = typedef
+                    .id
+                    .ok_or_else(|| self.error("[Typedef] stanza without id"))?;
+                self.builder.add_typedef(Typedef {
+                    id,
+                    name: typedef.name,
+                });
+            }
+            Stanza::Header | Stanza::Other => {}
+        }
+        Ok(())
+    }
+}
+
+/// Convert the OBO header date (dd:MM:yyyy HH:mm) to yyyy-mm-dd
+fn obo_date_to_iso(date: &str) -> String {
+    let day_month_year: Vec<&str> = date
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .split(':')
+        .collect();
+    match day_month_year.as_slice() {
+        [d, m, y] => format!("{}-{}-{}", y, m, d),
+        _ => date.to_string(),
+    }
+}
+
+/// Remove a trailing `! comment`, ignoring exclamation marks within quotes or escaped by a backslash
+fn strip_comment(value: &str) -> &str {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '!' if !in_quotes => return value[..i].trim_end(),
+            _ => {}
+        }
+    }
+    value.trim_end()
+}
+
+/// Remove trailing qualifiers such as `{source="GOC:mah"}` from an unquoted value
+fn strip_trailing_modifier(value: &str) -> &str {
+    if value.ends_with('}') && !value.starts_with('"') {
+        if let Some(start) = value.rfind('{') {
+            return value[..start].trim_end();
+        }
+    }
+    value
+}
+
+/// Parse a quoted string such as the text of a def or synonym.
+///
+/// Returns the unescaped text and the remainder of the value after the closing quote.
+fn parse_quoted(value: &str) -> Result<(String, &str), String> {
+    let rest = value
+        .strip_prefix('"')
+        .ok_or_else(|| format!("Expected quoted string but got '{}'", value))?;
+    let mut text = String::new();
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        if escaped {
+            text.push(match c {
+                'n' => '\n',
+                't' => '\t',
+                other => other,
+            });
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            return Ok((text, rest[i + 1..].trim_start()));
+        } else {
+            text.push(c);
+        }
+    }
+    Err(format!("Unterminated quoted string '{}'", value))
+}
+
+/// Parse an OBO 1.4 document into a [`GeneOntology`]
+pub fn parse_obo<R: BufRead>(reader: R) -> Result<GeneOntology, OntologyError> {
+    let mut parser = OboParser {
+        builder: GeneOntologyBuilder::new(),
+        default_namespace: None,
+        line_number: 0,
+    };
+    let mut stanza = Stanza::Header;
+    for line in reader.lines() {
+        parser.line_number += 1;
+        let line =
+            line.map_err(|e| OntologyError::IoError(format!("Error reading file: {}", e)))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('!') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            let next = match line {
+                "[Term]" => Stanza::Term(TermStanza::default()),
+                "[Typedef]" => Stanza::Typedef(TypedefStanza::default()),
+                _ => Stanza::Other,
+            };
+            let finished = std::mem::replace(&mut stanza, next);
+            parser.finish_stanza(finished)?;
+            continue;
+        }
+        let (tag, value) = line
+            .split_once(':')
+            .ok_or_else(|| parser.error(&format!("Expected tag-value pair but got '{}'", line)))?;
+        let value = strip_comment(value.trim());
+        match &mut stanza {
+            Stanza::Header => parser.process_header_tag(tag, value)?,
+            Stanza::Term(term) => {
+                let value = if value.starts_with('"') {
+                    value
+                } else {
+                    strip_trailing_modifier(value)
+                };
+                parser.process_term_tag(term, tag, value)?
+            }
+            Stanza::Typedef(typedef) => match tag {
+                "id" => typedef.id = Some(value.to_string()),
+                "name" => typedef.name = value.to_string(),
+                _ => {}
+            },
+            Stanza::Other => {}
+        }
+    }
+    parser.finish_stanza(stanza)?;
+    parser.builder.build()
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+
+    use super::*;
+
+    const GO_OBO: &str = r#"format-version: 1.2
+data-version: releases/2024-01-17
+date: 17:01:2024 10:55
+default-namespace: gene_ontology
+ontology: go
+
+[Term]
+id: GO:0008150
+name: biological_process
+namespace: biological_process
+alt_id: GO:0000004
+def: "A biological process is the execution of a genetically-encoded biological module or program." [GOC:pdt]
+
+[Term]
+id: GO:0009987
+name: cellular process
+namespace: biological_process
+is_a: GO:0008150 ! biological_process
+
+[Term]
+id: GO:0007049
+name: cell cycle
+namespace: biological_process
+def: "The progression of biochemical and morphological phases and events that occur in a cell during successive cell replication or nuclear replication events. Canonically, the cell cycle comprises the replication and segregation of genetic material followed by the division of the cell, but in endocycles or syncytial cells nuclear replication or nuclear division may not be followed by cell division." [GOC:go_curators, GOC:mtg_cell_cycle]
+synonym: "cell-division cycle" RELATED []
+is_a: GO:0009987 {source="GOC:mah"} ! cellular process
+
+[Term]
+id: GO:0051726
+name: regulation of cell cycle
+namespace: biological_process
+synonym: "cell cycle regulator" RELATED []
+is_a: GO:0008150 ! biological_process
+relationship: regulates GO:0007049 ! cell cycle
+
+[Term]
+id: GO:0000005
+name: obsolete ribosomal chaperone activity
+namespace: molecular_function
+def: "OBSOLETE. Assists in the correct assembly of \"ribosomes\"! or ribosomal subunits in vivo." []
+is_obsolete: true
+replaced_by: GO:0051082
+
+[Typedef]
+id: part_of
+name: part of
+xref: BFO:0000050
+is_transitive: true
+
+[Typedef]
+id: regulates
+name: regulates
+xref: RO:0002211
+"#;
+
+    fn tid(curie: &str) -> TermId {
+        TermId::from_curie(curie).unwrap()
+    }
+
+    #[test]
+    fn test_parse_obo() {
+        let go = parse_obo(GO_OBO.as_bytes()).unwrap();
+        assert_eq!(5, go.term_count());
+        assert_eq!(Some("releases/2024-01-17"), go.data_version());
+        assert_eq!(Some("2024-01-17"), go.date());
+        assert_eq!(Some("cell cycle"), go.label(&tid("GO:0007049")));
+        assert_eq!(2, go.typedefs().len());
+        assert_eq!("part of", go.typedefs()[0].name);
+    }
+
+    #[test]
+    fn test_relations() {
+        let go = parse_obo(GO_OBO.as_bytes()).unwrap();
+        // the trailing modifier {source=...} must not end up in the target id
+        assert_eq!(
+            vec![&tid("GO:0009987")],
+            go.parents(&tid("GO:0007049")).unwrap()
+        );
+        let reg = tid("GO:0051726");
+        assert_eq!(vec![&tid("GO:0008150")], go.parents(&reg).unwrap());
+        let rels = go.parent_relations(&reg).unwrap();
+        assert!(rels.contains(&(&tid("GO:0007049"), &TermRelation::Regulates)));
+        assert_eq!(3, go.descendants(&tid("GO:0008150")).unwrap().len());
+    }
+
+    #[test]
+    fn test_obsolete_and_alt_id() {
+        let go = parse_obo(GO_OBO.as_bytes()).unwrap();
+        assert_eq!(Some(&tid("GO:0008150")), go.primary_id(&tid("GO:0000004")));
+        let obsolete = go.get_term(&tid("GO:0000005")).unwrap();
+        assert!(obsolete.is_obsolete);
+        assert_eq!(Some(Namespace::MolecularFunction), obsolete.namespace);
+        assert_eq!(vec![tid("GO:0051082")], obsolete.replaced_by);
+        assert_eq!(
+            Some("OBSOLETE. Assists in the correct assembly of \"ribosomes\"! or ribosomal subunits in vivo."),
+            obsolete.definition.as_deref()
+        );
+    }
+
+    #[test]
+    fn test_synonyms() {
+        let go = parse_obo(GO_OBO.as_bytes()).unwrap();
+        let term = go.get_term(&tid("GO:0051726")).unwrap();
+        assert_eq!(
+            vec![Synonym {
+                value: "cell cycle regulator".to_string(),
+                scope: SynonymScope::Related
+            }],
+            term.synonyms
+        );
+    }
+
+    #[test]
+    fn test_strip_comment() {
+        assert_eq!(
+            "GO:0008150",
+            strip_comment("GO:0008150 ! biological_process")
+        );
+        assert_eq!("\"a ! b\" []", strip_comment("\"a ! b\" [] ! comment"));
+    }
+
+    #[test]
+    fn test_malformed_line() {
+        let result = parse_obo("[Term]\nid: GO:0008150\nno tag here\n".as_bytes());
+        assert!(result.is_err());
+        assert_eq!(
+            "Parsing error: line 3: Expected tag-value pair but got 'no tag here'",
+            result.err().unwrap().to_string()
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use rand::rngs::StdRng;
+use rand::seq::index;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::annotation_index::{AnnotationIndex, GeneSet};
+use crate::enrichment::{term_scores, CalculationMethod, TermGenes, TermScore};
+use crate::exact_fisher::Hypergeometric;
+use crate::gontology::GeneOntology;
+use crate::jobs::{Progress, ProgressStage};
+use crate::mtc::{ascending_order, MultipleTestingCorrection, PValueAdjustment};
+
+/// Settings for the resampling-based Westfall-Young corrections.
+///
+/// Each resampling uses its own generator, seeded with `seed` plus the index of the resampling, so
+/// the adjusted p-values do not depend on the number of threads.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct ResamplingConfig {
+    pub resamplings: usize,
+    pub seed: u64,
+}
+
+impl Default for ResamplingConfig {
+    fn default() -> Self {
+        ResamplingConfig {
+            resamplings: 1000,
+            seed: 42,
+        }
+    }
+}
+
+/// Westfall-Young min-P adjustment (Westfall and Young, 1993) given the p-values of the same terms for
+/// random study sets, one row per resampling.
+///
+/// Single-step: the adjusted p-value of a term is the fraction of resamplings in which the smallest
+/// p-value of all terms is at most the observed p-value. Step-down: terms are processed from the most to
+/// the least significant, and only the terms that are not more significant than the current one take
+/// part in the minimum. Step-down p-values are made monotone in the order of the observed p-values.
+pub struct WestfallYoung {
+    pub null_p_values: Vec<Vec<f64>>,
+    pub step_down: bool,
+}
+
+impl PValueAdjustment for WestfallYoung {
+    fn adjust(&self, p_values: &[f64]) -> Vec<f64> {
+        let m = p_values.len();
+        if self.null_p_values.is_empty() {
+            return vec![1.0; m];
+        }
+        let resamplings = self.null_p_values.len() as f64;
+        let mut counts = vec![0usize; m];
+        if self.step_down {
+            let order = ascending_order(p_values);
+            for sample in &self.null_p_values {
+                let mut successive_min: f64 = 1.0;
+                for &i in order.iter().rev() {
+                    successive_min = successive_min.min(sample[i]);
+                    if successive_min <= p_values[i] {
+                        counts[i] += 1;
+                    }
+                }
+            }
+            let mut adjusted = vec![0.0; m];
+            let mut running_max: f64 = 0.0;
+            for i in order {
+                running_max = running_max.max(counts[i] as f64 / resamplings);
+                adjusted[i] = running_max;
+            }
+            adjusted
+        } else {
+            for sample in &self.null_p_values {
+                let min_p = sample.iter().copied().fold(1.0, f64::min);
+                for (count, p) in counts.iter_mut().zip(p_values) {
+                    if min_p <= *p {
+                        *count += 1;
+                    }
+                }
+            }
+            counts.into_iter().map(|c| c as f64 / resamplings).collect()
+        }
+    }
+}
+
+/// p-values of `terms` for random study sets of the same size as the observed study set, drawn from the
+/// population without replacement. Terms that are not annotated to any gene of a random study set get p = 1.
+///
+/// The resamplings are distributed over all cores, which share one [`Hypergeometric`] calculator. The
+/// resampling stops with the error of `progress`, e.g., if the job was cancelled.
+pub fn null_p_values(
+    go: &GeneOntology,
+    annotations: &AnnotationIndex,
+    genes: &TermGenes,
+    method: CalculationMethod,
+    terms: &[usize],
+    config: &ResamplingConfig,
+    progress: Progress,
+) -> Result<Vec<Vec<f64>>, String> {
+    if config.resamplings == 0 {
+        return Err("At least one resampling is required".to_string());
+    }
+    let population: Vec<usize> = genes.population.iter().collect();
+    let study_total = genes.study.len();
+    let hgeom = Hypergeometric::new(population.len());
+    let done = AtomicUsize::new(0);
+    (0..config.resamplings)
+        .into_par_iter()
+        .map(|i| {
+            let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(i as u64));
+            let study: GeneSet = index::sample(&mut rng, population.len(), study_total)
+                .into_iter()
+                .map(|g| population[g])
+                .collect();
+            let random_genes = genes.with_study(annotations, study);
+            let scores: HashMap<usize, f64> = term_scores(go, &random_genes, method, &hgeom)?
+                .into_iter()
+                .map(|s| (s.term, s.p_value))
+                .collect();
+            let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+            progress(ProgressStage::Resampling, done, config.resamplings)?;
+            Ok(terms
+                .iter()
+                .map(|t| scores.get(t).copied().unwrap_or(1.0))
+                .collect())
+        })
+        .collect()
+}
+
+/// Adjust the p-values of `scores` with the Westfall-Young correction selected in `correction`.
+pub fn westfall_young(
+    go: &GeneOntology,
+    annotations: &AnnotationIndex,
+    genes: &TermGenes,
+    method: CalculationMethod,
+    scores: &[TermScore],
+    correction: MultipleTestingCorrection,
+    progress: Progress,
+) -> Result<Vec<f64>, String> {
+    let (config, step_down) = match correction {
+        MultipleTestingCorrection::WestfallYoungSingleStep(config) => (config, false),
+        MultipleTestingCorrection::WestfallYoungStepDown(config) => (config, true),
+        _ => {
+            return Err(format!(
+                "{:?} is not a resampling-based correction",
+                correction
+            ))
+        }
+    };
+    let terms: Vec<usize> = scores.iter().map(|s| s.term).collect();
+    let p_values: Vec<f64> = scores.iter().map(|s| s.p_value).collect();
+    let null_p_values = null_p_values(go, annotations, genes, method, &terms, &config, progress)?;
+    Ok(WestfallYoung {
+        null_p_values,
+        step_down,
+    }
+    .adjust(&p_values))
+}
+
+#[cfg(test)]
+mod test {
+    use float_eq::float_eq;
+    use std::assert_eq;
+
+    use super::*;
+    use crate::enrichment::analyze_methods;
+    use crate::jobs::CANCELLED;
+    use crate::test_fixtures::chain_fixture;
+
+    const OBSERVED: [f64; 3] = [0.01, 0.04, 0.5];
+
+    fn null_fixture() -> Vec<Vec<f64>> {
+        vec![
+            vec![0.2, 0.03, 0.9],
+            vec![0.005, 0.6, 0.7],
+            vec![0.5, 0.5, 0.3],
+            vec![0.02, 0.01, 0.6],
+        ]
+    }
+
+    #[test]
+    fn test_single_step() {
+        let wy = WestfallYoung {
+            null_p_values: null_fixture(),
+            step_down: false,
+        };
+        // minima of the samples are 0.03, 0.005, 0.3 and 0.01
+        let adjusted = wy.adjust(&OBSERVED);
+        for (e, a) in [0.5, 0.75, 1.0].iter().zip(adjusted.iter()) {
+            assert!(float_eq!(*e, *a, abs <= 1e-12));
+        }
+    }
+
+    #[test]
+    fn test_step_down() {
+        let wy = WestfallYoung {
+            null_p_values: null_fixture(),
+            step_down: true,
+        };
+        // the third term only exceeds its successive minimum in one sample (0.25), but adjusted
+        // p-values may not decrease with the observed p-values
+        let adjusted = wy.adjust(&OBSERVED);
+        for (e, a) in [0.5, 0.5, 0.5].iter().zip(adjusted.iter()) {
+            assert!(float_eq!(*e, *a, abs <= 1e-12));
+        }
+    }
+
+    #[test]
+    fn test_westfall_young_analysis() {
+        // root <- A <- B with 40 genes: g1..g4 are annotated to B, g5..g10 to A and the others to the root
+        let (go, annotations) = chain_fixture(4, 6, 40);
+        let study: Vec<String> = ["g1", "g2", "g5", "g11"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = ResamplingConfig {
+            resamplings: 200,
+            seed: 3,
+        };
+        let calls = AtomicUsize::new(0);
+        let run = |correction| {
+            analyze_methods(
+                &go,
+                &annotations,
+                &study,
+                &[],
+                &[CalculationMethod::TermForTerm],
+                correction,
+                &|stage, _, total| {
+                    if stage == ProgressStage::Resampling {
+                        assert_eq!(200, total);
+                        calls.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap()
+            .remove(0)
+            .results
+        };
+        let cancelled = analyze_methods(
+            &go,
+            &annotations,
+            &study,
+            &[],
+            &[CalculationMethod::TermForTerm],
+            MultipleTestingCorrection::WestfallYoungStepDown(config),
+            &|stage, _, _| match stage {
+                ProgressStage::Resampling => Err(CANCELLED.to_string()),
+                _ => Ok(()),
+            },
+        );
+        assert_eq!(CANCELLED, cancelled.unwrap_err());
+        let single_step = run(MultipleTestingCorrection::WestfallYoungSingleStep(config));
+        let step_down = run(MultipleTestingCorrection::WestfallYoungStepDown(config));
+        assert_eq!(400, calls.load(Ordering::Relaxed));
+        // the same seed gives the same random study sets, independent of the scheduling of the threads
+        let again = run(MultipleTestingCorrection::WestfallYoungStepDown(config));
+        assert_eq!(3, step_down.len());
+        for ((s, d), a) in single_step.iter().zip(&step_down).zip(&again) {
+            assert_eq!(s.term_id, d.term_id);
+            assert_eq!(d.adjusted_p_value, a.adjusted_p_value);
+            assert!(d.adjusted_p_value <= s.adjusted_p_value);
+            assert!((0.0..=1.0).contains(&s.adjusted_p_value));
+        }
+        // every random study set is annotated to the root, whose p-value is always 1
+        let root = step_down.iter().find(|r| r.label == "root").unwrap();
+        assert!(float_eq!(1.0, root.adjusted_p_value, abs <= 1e-12));
+    }
+}
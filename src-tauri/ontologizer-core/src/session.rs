@@ -0,0 +1,550 @@
+//! The datasets of a session.
+//!
+//! Every ontology, annotation set, gene list and analysis result that is loaded or computed gets an
+//! id that the frontend uses to refer to it. Several datasets of each kind can be held at once, e.g.
+//! two releases of the annotations of a species, or the annotations of two species. Commands that
+//! take an optional id use the most recently added dataset of that kind.
+//!
+//! The datasets are shared via [`Arc`], so that a long analysis does not keep the session locked.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::annotation_index::AnnotationIndex;
+use crate::enrichment::AnalysisResult;
+use crate::evidence::{AllowedEvidence, EvidenceFilter};
+use crate::gaf::GafRecord;
+use crate::goannot::{AnnotationStat, GoAnnot};
+use crate::gontology::GeneOntology;
+use crate::mgsa::MgsaResult;
+
+pub type DatasetId = u32;
+
+/// The loaded records of an annotation file and the annotations that are used for the analysis
+pub struct AnnotationSet {
+    /// All columns of the loaded annotations, including negated annotations
+    pub records: Arc<Vec<GafRecord>>,
+    /// Built from `records`, possibly after filtering
+    pub annotations: Vec<GoAnnot>,
+    /// Negated (NOT) annotations, i.e., a gene product explicitly does not have the relation to the term
+    pub negated: Vec<GoAnnot>,
+}
+
+impl AnnotationSet {
+    /// The index of the annotations whose evidence passes `evidence`, which all analysis methods use,
+    /// and the evidence that passes. With `exclude_negated`, the NOT annotations that pass the filter
+    /// remove their genes from the term and its descendants.
+    pub fn index(
+        &self,
+        go: &GeneOntology,
+        evidence: &EvidenceFilter,
+        eco: Option<&GeneOntology>,
+        exclude_negated: bool,
+    ) -> Result<(AnnotationIndex, AllowedEvidence), String> {
+        let eco_ids = self
+            .annotations
+            .iter()
+            .chain(&self.negated)
+            .filter_map(|a| Some((a.evidence_code, a.eco_id.as_deref()?)));
+        let allowed = evidence.allowed(eco, eco_ids)?;
+        let passes = |annotations: &[GoAnnot]| -> Vec<GoAnnot> {
+            annotations
+                .iter()
+                .filter(|a| allowed.contains(a.evidence_code, a.eco_id.as_deref()))
+                .cloned()
+                .collect()
+        };
+        let mut index = AnnotationIndex::new(go, &passes(&self.annotations));
+        if exclude_negated {
+            index.exclude_negated(go, &passes(&self.negated));
+        }
+        Ok((index, allowed))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum GeneListKind {
+    StudySet,
+    Population,
+}
+
+pub struct GeneList {
+    pub kind: GeneListKind,
+    pub genes: Vec<String>,
+}
+
+/// A gene list that is either given directly or refers to a gene list of the session
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum GeneListInput {
+    Id(DatasetId),
+    Genes(Vec<String>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum AnalysisOutput {
+    Enrichment(Vec<AnalysisResult>),
+    Mgsa(MgsaResult),
+}
+
+/// The result of an analysis together with the datasets it was computed from
+#[derive(Debug, Serialize)]
+pub struct AnalysisRun {
+    pub ontology: DatasetId,
+    pub annotations: DatasetId,
+    pub study: DatasetId,
+    pub population: DatasetId,
+    /// Settings of the analysis that are not part of the results, e.g., the evidence filter
+    pub parameters: Vec<AnnotationStat>,
+    pub results: AnalysisOutput,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum DatasetKind {
+    Ontology,
+    Annotations,
+    StudySet,
+    Population,
+    Result,
+}
+
+/// A file that a dataset was loaded from, with a checksum that identifies the exact release in reports
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct InputFile {
+    pub path: String,
+    pub bytes: u64,
+    /// CRC-32 of the file as stored on disk, i.e., of the compressed file for `.gz` files
+    pub crc32: String,
+}
+
+impl InputFile {
+    /// The description of the file `path` with the content `content`, e.g., of a gene list that was
+    /// read at once. Files that are parsed while they are read are described by [`Job::input_files`].
+    ///
+    /// [`Job::input_files`]: crate::jobs::Job::input_files
+    pub fn from_bytes(path: &str, content: &[u8]) -> Self {
+        let mut crc = flate2::Crc::new();
+        crc.update(content);
+        InputFile {
+            path: path.to_string(),
+            bytes: content.len() as u64,
+            crc32: format!("{:08x}", crc.sum()),
+        }
+    }
+}
+
+/// One row of the dataset list of the frontend
+#[derive(Clone, Debug, Serialize)]
+pub struct DatasetInfo {
+    pub id: DatasetId,
+    pub kind: DatasetKind,
+    pub name: String,
+    /// Empty for results
+    pub inputs: Vec<InputFile>,
+}
+
+struct Named<T> {
+    name: String,
+    data: Arc<T>,
+    inputs: Vec<InputFile>,
+}
+
+/// The last dataset of `datasets`, or the one with `id`
+fn lookup<T>(
+    datasets: &BTreeMap<DatasetId, Named<T>>,
+    id: Option<DatasetId>,
+    kind: &str,
+) -> Result<(DatasetId, Arc<T>), String> {
+    let entry = match id {
+        Some(id) => datasets.get_key_value(&id),
+        None => datasets.last_key_value(),
+    };
+    match (entry, id) {
+        (Some((id, named)), _) => Ok((*id, Arc::clone(&named.data))),
+        (None, Some(id)) => Err(format!("No {} with id {}", kind, id)),
+        (None, None) => Err(format!("{} has not been loaded", kind)),
+    }
+}
+
+#[derive(Default)]
+pub struct Session {
+    next_id: DatasetId,
+    ontologies: BTreeMap<DatasetId, Named<GeneOntology>>,
+    annotations: BTreeMap<DatasetId, Named<AnnotationSet>>,
+    gene_lists: BTreeMap<DatasetId, Named<GeneList>>,
+    results: BTreeMap<DatasetId, Named<AnalysisRun>>,
+}
+
+impl Session {
+    fn next_id(&mut self) -> DatasetId {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    pub fn add_ontology(
+        &mut self,
+        name: &str,
+        go: GeneOntology,
+        inputs: Vec<InputFile>,
+    ) -> DatasetId {
+        let id = self.next_id();
+        let data = Arc::new(go);
+        self.ontologies.insert(
+            id,
+            Named {
+                name: name.to_string(),
+                data,
+                inputs,
+            },
+        );
+        id
+    }
+
+    pub fn ontology(
+        &self,
+        id: Option<DatasetId>,
+    ) -> Result<(DatasetId, Arc<GeneOntology>), String> {
+        lookup(&self.ontologies, id, "Gene Ontology")
+    }
+
+    pub fn add_annotations(
+        &mut self,
+        name: &str,
+        annotations: AnnotationSet,
+        inputs: Vec<InputFile>,
+    ) -> DatasetId {
+        let id = self.next_id();
+        let data = Arc::new(annotations);
+        self.annotations.insert(
+            id,
+            Named {
+                name: name.to_string(),
+                data,
+                inputs,
+            },
+        );
+        id
+    }
+
+    pub fn annotations(
+        &self,
+        id: Option<DatasetId>,
+    ) -> Result<(DatasetId, Arc<AnnotationSet>), String> {
+        lookup(&self.annotations, id, "GO annotations")
+    }
+
+    /// Add `annotations` that were filtered from the annotation set `id` as a new dataset with the same
+    /// input files. The annotation set `id` is kept, so results that were computed from it still refer
+    /// to the annotations they used.
+    pub fn add_filtered_annotations(
+        &mut self,
+        id: DatasetId,
+        annotations: AnnotationSet,
+    ) -> Result<DatasetId, String> {
+        let source = self
+            .annotations
+            .get(&id)
+            .ok_or_else(|| format!("No GO annotations with id {}", id))?;
+        let name = format!("{} (filtered)", source.name);
+        let inputs = source.inputs.clone();
+        Ok(self.add_annotations(&name, annotations, inputs))
+    }
+
+    /// Store a gene list that is given directly, or look up the one that is referenced, which must be
+    /// of the same kind.
+    ///
+    /// A gene list that is given directly is described in reports by the CRC-32 of its genes, one per
+    /// line, as they are not read from a file.
+    pub fn gene_list(
+        &mut self,
+        input: GeneListInput,
+        kind: GeneListKind,
+    ) -> Result<(DatasetId, Arc<GeneList>), String> {
+        let kind_name = |kind| match kind {
+            GeneListKind::StudySet => "Study set",
+            GeneListKind::Population => "Population",
+        };
+        match input {
+            GeneListInput::Id(id) => {
+                let (id, list) = lookup(&self.gene_lists, Some(id), "gene list")?;
+                if list.kind != kind {
+                    return Err(format!(
+                        "{} expected, but gene list {} is a {}",
+                        kind_name(kind),
+                        id,
+                        kind_name(list.kind).to_lowercase()
+                    ));
+                }
+                Ok((id, list))
+            }
+            GeneListInput::Genes(genes) => {
+                let id = self.next_id();
+                let name = format!("{} ({} genes)", kind_name(kind), genes.len());
+                let content: String = genes.iter().map(|g| format!("{}\n", g)).collect();
+                let inputs = vec![InputFile::from_bytes(&name, content.as_bytes())];
+                let data = Arc::new(GeneList { kind, genes });
+                self.gene_lists.insert(
+                    id,
+                    Named {
+                        name,
+                        data: Arc::clone(&data),
+                        inputs,
+                    },
+                );
+                Ok((id, data))
+            }
+        }
+    }
+
+    pub fn add_result(&mut self, name: &str, run: AnalysisRun) -> (DatasetId, Arc<AnalysisRun>) {
+        let id = self.next_id();
+        let data = Arc::new(run);
+        self.results.insert(
+            id,
+            Named {
+                name: name.to_string(),
+                data: Arc::clone(&data),
+                inputs: vec![],
+            },
+        );
+        (id, data)
+    }
+
+    pub fn result(&self, id: Option<DatasetId>) -> Result<(DatasetId, Arc<AnalysisRun>), String> {
+        lookup(&self.results, id, "analysis result")
+    }
+
+    /// Remove a dataset of any kind. Results that were computed from it are kept.
+    pub fn remove(&mut self, id: DatasetId) -> Result<(), String> {
+        let removed = self.ontologies.remove(&id).is_some()
+            || self.annotations.remove(&id).is_some()
+            || self.gene_lists.remove(&id).is_some()
+            || self.results.remove(&id).is_some();
+        if removed {
+            Ok(())
+        } else {
+            Err(format!("No dataset with id {}", id))
+        }
+    }
+
+    /// All datasets, ordered by id
+    pub fn datasets(&self) -> Vec<DatasetInfo> {
+        fn info<T>(kind: DatasetKind, id: &DatasetId, named: &Named<T>) -> DatasetInfo {
+            DatasetInfo {
+                id: *id,
+                kind,
+                name: named.name.clone(),
+                inputs: named.inputs.clone(),
+            }
+        }
+        let mut datasets = vec![];
+        for (id, named) in &self.ontologies {
+            datasets.push(info(DatasetKind::Ontology, id, named));
+        }
+        for (id, named) in &self.annotations {
+            datasets.push(info(DatasetKind::Annotations, id, named));
+        }
+        for (id, named) in &self.gene_lists {
+            let kind = match named.data.kind {
+                GeneListKind::StudySet => DatasetKind::StudySet,
+                GeneListKind::Population => DatasetKind::Population,
+            };
+            datasets.push(info(kind, id, named));
+        }
+        for (id, named) in &self.results {
+            datasets.push(info(DatasetKind::Result, id, named));
+        }
+        datasets.sort_by_key(|d| d.id);
+        datasets
+    }
+
+    /// The dataset `id`, or None if it was removed
+    pub fn dataset(&self, id: DatasetId) -> Option<DatasetInfo> {
+        self.datasets().into_iter().find(|d| d.id == id)
+    }
+}
+
+/// The session of the application, managed by Tauri
+#[derive(Default)]
+pub struct SessionState(pub Mutex<Session>);
+
+/// The name of a dataset loaded from `path`
+pub fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Serialize a dataset together with its id, e.g., `{"id": 3, "stats": [...]}`
+pub fn with_id<T: Serialize>(id: DatasetId, data: &T) -> Result<String, String> {
+    #[derive(Serialize)]
+    struct WithId<'a, T> {
+        id: DatasetId,
+        #[serde(flatten)]
+        data: &'a T,
+    }
+    serde_json::to_string(&WithId { id, data }).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use std::assert_eq;
+
+    use super::*;
+    use crate::evidence::{EvidenceCode, EvidenceSelector};
+    use crate::goannot::{Aspect, GoTermRelation, TermId};
+    use crate::gontology::{GeneOntologyBuilder, GoTerm, TermRelation};
+
+    fn annotation_set() -> AnnotationSet {
+        AnnotationSet {
+            records: Arc::new(vec![]),
+            annotations: vec![],
+            negated: vec![],
+        }
+    }
+
+    #[test]
+    fn test_datasets() {
+        let mut session = Session::default();
+        assert_eq!(
+            "Gene Ontology has not been loaded",
+            session.ontology(None).err().unwrap()
+        );
+        let go = session.add_ontology(
+            "go.json",
+            GeneOntologyBuilder::new().build().unwrap(),
+            vec![],
+        );
+        let human = session.add_annotations("goa_human.gaf", annotation_set(), vec![]);
+        let mouse = session.add_annotations("mgi.gaf", annotation_set(), vec![]);
+        assert_eq!(go, session.ontology(None).unwrap().0);
+        // the most recent annotations are used unless another set is selected
+        assert_eq!(mouse, session.annotations(None).unwrap().0);
+        assert_eq!(human, session.annotations(Some(human)).unwrap().0);
+        assert_eq!(
+            "No GO annotations with id 1",
+            session.annotations(Some(go)).err().unwrap()
+        );
+        let genes = GeneListInput::Genes(vec!["TP53".to_string()]);
+        let (study, _) = session.gene_list(genes, GeneListKind::StudySet).unwrap();
+        let (same, list) = session
+            .gene_list(GeneListInput::Id(study), GeneListKind::StudySet)
+            .unwrap();
+        assert_eq!(study, same);
+        assert_eq!(vec!["TP53"], list.genes);
+        assert_eq!(
+            "Population expected, but gene list 4 is a study set",
+            session
+                .gene_list(GeneListInput::Id(study), GeneListKind::Population)
+                .err()
+                .unwrap()
+        );
+        // the genes of a gene list, one per line, are its input
+        assert_eq!(
+            vec![InputFile::from_bytes("Study set (1 genes)", b"TP53\n")],
+            session.dataset(study).unwrap().inputs
+        );
+        session.remove(human).unwrap();
+        assert!(session.remove(human).is_err());
+        let kinds: Vec<(DatasetId, DatasetKind)> =
+            session.datasets().iter().map(|d| (d.id, d.kind)).collect();
+        assert_eq!(
+            vec![
+                (go, DatasetKind::Ontology),
+                (mouse, DatasetKind::Annotations),
+                (study, DatasetKind::StudySet)
+            ],
+            kinds
+        );
+    }
+
+    #[test]
+    fn test_filtered_annotations() {
+        let mut session = Session::default();
+        let input = InputFile::from_bytes("mgi.gaf", b"!gaf-version: 2.2");
+        let loaded = session.add_annotations("mgi.gaf", annotation_set(), vec![input.clone()]);
+        let filtered = session
+            .add_filtered_annotations(loaded, annotation_set())
+            .unwrap();
+        assert_ne!(loaded, filtered);
+        // the analyses use the filtered annotations, but the loaded ones are kept
+        assert_eq!(filtered, session.annotations(None).unwrap().0);
+        assert_eq!(loaded, session.annotations(Some(loaded)).unwrap().0);
+        let info = session.dataset(filtered).unwrap();
+        assert_eq!("mgi.gaf (filtered)", info.name);
+        assert_eq!(vec![input], info.inputs);
+        assert!(session
+            .add_filtered_annotations(42, annotation_set())
+            .is_err());
+    }
+
+    #[test]
+    fn test_gene_list_input() {
+        let id: GeneListInput = serde_json::from_str("4").unwrap();
+        assert!(matches!(id, GeneListInput::Id(4)));
+        let genes: GeneListInput = serde_json::from_str(r#"["TP53", "MDM2"]"#).unwrap();
+        assert!(matches!(genes, GeneListInput::Genes(g) if g.len() == 2));
+    }
+
+    #[test]
+    fn test_input_file() {
+        let input = InputFile::from_bytes("study.txt", b"123456789");
+        assert_eq!("study.txt", input.path);
+        assert_eq!(9, input.bytes);
+        // the check value of CRC-32
+        assert_eq!("cbf43926", input.crc32);
+    }
+
+    #[test]
+    fn test_index() {
+        let tid = |id: &str| TermId::from_curie(id).unwrap();
+        let mut builder = GeneOntologyBuilder::new();
+        builder.add_term(GoTerm::new(tid("GO:0000001"), "root"));
+        builder.add_term(GoTerm::new(tid("GO:0000002"), "A"));
+        builder.add_relation("GO:0000002", TermRelation::IsA, "GO:0000001");
+        let go = builder.build().unwrap();
+        let annot = |gene: &str, evidence_code| {
+            GoAnnot::new(
+                TermId::new("UniProtKB", &format!("P{}", gene)).unwrap(),
+                gene,
+                GoTermRelation::InvolvedIn,
+                tid("GO:0000002"),
+                evidence_code,
+                Aspect::P,
+            )
+        };
+        let annotations = AnnotationSet {
+            records: Arc::new(vec![]),
+            annotations: vec![
+                annot("g1", EvidenceCode::EXP),
+                annot("g2", EvidenceCode::IEA),
+            ],
+            negated: vec![annot("g1", EvidenceCode::IEA)],
+        };
+        let a = go.index_of(&tid("GO:0000002")).unwrap();
+        let (index, _) = annotations
+            .index(&go, &EvidenceFilter::default(), None, true)
+            .unwrap();
+        // the NOT annotation removes g1 from A
+        assert_eq!(vec!["g2"], genes(&index, a));
+        let no_iea = EvidenceFilter {
+            include: vec![],
+            exclude: vec![EvidenceSelector::Code(EvidenceCode::IEA)],
+        };
+        let (index, allowed) = annotations.index(&go, &no_iea, None, true).unwrap();
+        // neither the annotation of g2 nor the NOT annotation of g1 pass the filter
+        assert_eq!(vec!["g1"], genes(&index, a));
+        assert!(!allowed.codes.contains(&EvidenceCode::IEA));
+    }
+
+    fn genes(index: &AnnotationIndex, term: usize) -> Vec<&str> {
+        index
+            .genes(term)
+            .map(|g| g.iter().map(|g| index.symbol(g)).collect())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,94 @@
+//! Small ontologies and annotations shared by the tests of the analysis modules.
+//!
+//! All terms are biological processes and all annotations have the evidence code EXP. The gene
+//! product of the gene `g1` is `UniProtKB:Pg1`.
+
+use crate::annotation_index::AnnotationIndex;
+use crate::evidence::EvidenceCode;
+use crate::goannot::{Aspect, GoAnnot, GoTermRelation, TermId};
+use crate::gontology::{GeneOntology, GeneOntologyBuilder, GoTerm, TermRelation};
+
+pub(crate) fn tid(curie: &str) -> TermId {
+    TermId::from_curie(curie).unwrap()
+}
+
+/// Build an ontology of the `(id, label)` terms and the `(child, relation, parent)` relations
+pub(crate) fn ontology(
+    terms: &[(&str, &str)],
+    relations: &[(&str, TermRelation, &str)],
+) -> GeneOntology {
+    let mut builder = GeneOntologyBuilder::new();
+    for (id, label) in terms {
+        builder.add_term(GoTerm::new(tid(id), *label));
+    }
+    for (child, relation, parent) in relations {
+        builder.add_relation(child, relation.clone(), parent);
+    }
+    builder.build().unwrap()
+}
+
+pub(crate) fn annot(gene: &str, go_id: &str) -> GoAnnot {
+    GoAnnot::new(
+        TermId::new("UniProtKB", &format!("P{}", gene)).unwrap(),
+        gene,
+        GoTermRelation::InvolvedIn,
+        tid(go_id),
+        EvidenceCode::EXP,
+        Aspect::P,
+    )
+}
+
+/// Annotate the genes g1, g2, ... to the terms in order, `count` genes to each term
+pub(crate) fn annotate(terms: &[(&str, usize)]) -> Vec<GoAnnot> {
+    terms
+        .iter()
+        .flat_map(|(go_id, count)| std::iter::repeat_n(*go_id, *count))
+        .enumerate()
+        .map(|(i, go_id)| annot(&format!("g{}", i + 1), go_id))
+        .collect()
+}
+
+/// root <- A <- B (part_of), root <- C
+pub(crate) fn toy_ontology() -> GeneOntology {
+    ontology(
+        &[
+            ("GO:0000001", "root"),
+            ("GO:0000002", "A"),
+            ("GO:0000003", "B"),
+            ("GO:0000004", "C"),
+        ],
+        &[
+            ("GO:0000002", TermRelation::IsA, "GO:0000001"),
+            ("GO:0000003", TermRelation::PartOf, "GO:0000002"),
+            ("GO:0000004", TermRelation::IsA, "GO:0000001"),
+        ],
+    )
+}
+
+/// Genes g1..g4 are annotated to B, g5..g10 to C
+pub(crate) fn toy_annotations() -> Vec<GoAnnot> {
+    annotate(&[("GO:0000003", 4), ("GO:0000004", 6)])
+}
+
+/// root <- A <- B with `genes` genes: the first `b` are annotated to B, the next `a` to A and the others
+/// to the root
+pub(crate) fn chain_fixture(b: usize, a: usize, genes: usize) -> (GeneOntology, AnnotationIndex) {
+    let go = ontology(
+        &[
+            ("GO:0000001", "root"),
+            ("GO:0000002", "A"),
+            ("GO:0000003", "B"),
+        ],
+        &[
+            ("GO:0000002", TermRelation::IsA, "GO:0000001"),
+            ("GO:0000003", TermRelation::IsA, "GO:0000002"),
+        ],
+    );
+    let annots = annotate(&[
+        ("GO:0000003", b),
+        ("GO:0000002", a),
+        ("GO:0000001", genes - a - b),
+    ]);
+    let annotations = AnnotationIndex::new(&go, &annots);
+    (go, annotations)
+}
@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::annotation_index::GeneSet;
+use crate::enrichment::{upper_tail, TermGenes, TermScore};
+use crate::exact_fisher::Hypergeometric;
+use crate::gontology::GeneOntology;
+
+/// Terms with a p-value below this cutoff remove their genes from their ancestors (topGO default)
+pub const ELIM_CUTOFF: f64 = 0.01;
+
+/// p-values below this are considered to be equal when comparing a term with its child
+const SIG_RATIO_TOLERANCE: f64 = 1e-50;
+
+/// Order the tested terms so that every term comes after all of its descendants.
+///
+/// Since annotations are propagated, the ancestors of a tested term are also tested and the
+/// ordering covers the entire subgraph that is induced by the population genes.
+fn bottom_up_order(go: &GeneOntology, terms: &HashSet<usize>) -> Vec<usize> {
+    let mut remaining_children: HashMap<usize, usize> = terms
+        .iter()
+        .map(|&t| (t, go.child_indices(t).filter(|c| terms.contains(c)).count()))
+        .collect();
+    let mut leaves: Vec<usize> = remaining_children
+        .iter()
+        .filter(|(_, &n)| n == 0)
+        .map(|(&t, _)| t)
+        .collect();
+    // sort so that the order (and thereby the weights) does not depend on HashMap iteration order
+    leaves.sort();
+    let mut queue = VecDeque::from(leaves);
+    let mut order = Vec::with_capacity(terms.len());
+    while let Some(t) = queue.pop_front() {
+        order.push(t);
+        let mut parents: Vec<usize> = go.parent_indices(t).filter(|p| terms.contains(p)).collect();
+        parents.sort();
+        parents.dedup();
+        for p in parents {
+            let n = remaining_children.get_mut(&p).unwrap();
+            *n -= 1;
+            if *n == 0 {
+                queue.push_back(p);
+            }
+        }
+    }
+    order
+}
+
+/// The elim algorithm of Alexa et al. (2006).
+///
+/// Terms are processed bottom-up. If a term is significant at `cutoff`, its genes are removed from all
+/// of its ancestors, so that a more general term is only reported if it is enriched in genes that are
+/// not already explained by one of its significant descendants. The totals are not changed.
+pub fn elim(
+    go: &GeneOntology,
+    genes: &TermGenes,
+    hgeom: &Hypergeometric,
+    cutoff: f64,
+) -> Result<Vec<TermScore>, String> {
+    let tested: HashSet<usize> = genes.population_genes.keys().copied().collect();
+    let population_total = genes.population.len();
+    let study_total = genes.study.len();
+    let mut removed: HashMap<usize, GeneSet> = HashMap::new();
+    let mut scores = Vec::with_capacity(tested.len());
+    for term in bottom_up_order(go, &tested) {
+        let Some(study_genes) = genes.study_genes.get(&term) else {
+            // a term without study genes is not significant and, as its descendants have none
+            // either, keeps all of its genes
+            scores.push(TermScore {
+                term,
+                population_total,
+                population_count: genes.population_genes[&term].len(),
+                study_total,
+                study_count: 0,
+                p_value: 1.0,
+            });
+            continue;
+        };
+        let mut population_genes = genes.population_genes[&term].clone();
+        let mut study_genes = study_genes.clone();
+        if let Some(r) = removed.get(&term) {
+            population_genes.difference_with(r);
+            study_genes.difference_with(r);
+        }
+        let study_count = study_genes.len();
+        let p_value = upper_tail(
+            hgeom,
+            study_count,
+            population_total,
+            population_genes.len(),
+            study_total,
+        )?;
+        if p_value < cutoff {
+            for ancestor in go.ancestor_indices(term) {
+                if tested.contains(&ancestor) {
+                    removed
+                        .entry(ancestor)
+                        .or_default()
+                        .union_with(&population_genes);
+                }
+            }
+        }
+        scores.push(TermScore {
+            term,
+            population_total,
+            population_count: population_genes.len(),
+            study_total,
+            study_count,
+            p_value,
+        });
+    }
+    Ok(scores)
+}
+
+/// The weight algorithm of Alexa et al. (2006).
+///
+/// Terms are processed bottom-up and each term is compared with its children. If a child is more
+/// significant than the term, the genes of the child are down-weighted in the term by the ratio of the
+/// p-values. Otherwise, the genes of the child are down-weighted in the child, whose score is
+/// recomputed. Weighted counts are rounded to the nearest integer for the hypergeometric test.
+pub fn weight(
+    go: &GeneOntology,
+    genes: &TermGenes,
+    hgeom: &Hypergeometric,
+) -> Result<Vec<TermScore>, String> {
+    let tested: HashSet<usize> = genes.population_genes.keys().copied().collect();
+    let mut weights: HashMap<usize, HashMap<usize, f64>> = HashMap::new();
+    let mut scores: HashMap<usize, TermScore> = HashMap::new();
+    for term in bottom_up_order(go, &tested) {
+        if !genes.study_genes.contains_key(&term) {
+            // the term and its children have no study genes, so they all have p = 1 and no weights change
+            scores.insert(term, weighted_score(hgeom, genes, &weights, term)?);
+            continue;
+        }
+        let p_term = weighted_score(hgeom, genes, &weights, term)?.p_value;
+        let mut children: Vec<usize> = go
+            .child_indices(term)
+            .filter(|c| tested.contains(c))
+            .collect();
+        children.sort();
+        children.dedup();
+        for child in children {
+            let p_child = scores[&child].p_value;
+            let ratio = sig_ratio(p_term, p_child);
+            let (target, factor) = if ratio < 1.0 {
+                (term, ratio)
+            } else {
+                (child, 1.0 / ratio)
+            };
+            let target_weights = weights.entry(target).or_default();
+            for gene in genes.population_genes[&child].iter() {
+                *target_weights.entry(gene).or_insert(1.0) *= factor;
+            }
+            if target == child {
+                scores.insert(child, weighted_score(hgeom, genes, &weights, child)?);
+            }
+        }
+        scores.insert(term, weighted_score(hgeom, genes, &weights, term)?);
+    }
+    Ok(scores.into_values().collect())
+}
+
+/// Ratio of the p-values of the child and the parent. Values below one mean that the child is more significant.
+fn sig_ratio(p_parent: f64, p_child: f64) -> f64 {
+    if p_parent < SIG_RATIO_TOLERANCE && p_child < SIG_RATIO_TOLERANCE {
+        return 1.0;
+    }
+    p_child / p_parent
+}
+
+fn weighted_score(
+    hgeom: &Hypergeometric,
+    genes: &TermGenes,
+    weights: &HashMap<usize, HashMap<usize, f64>>,
+    term: usize,
+) -> Result<TermScore, String> {
+    let term_weights = weights.get(&term);
+    let weighted_count = |term_genes: &GeneSet| -> usize {
+        let sum: f64 = term_genes
+            .iter()
+            .map(|g| term_weights.and_then(|w| w.get(&g)).copied().unwrap_or(1.0))
+            .sum();
+        sum.round() as usize
+    };
+    let population_count = weighted_count(&genes.population_genes[&term]);
+    let study_count = genes.study_genes.get(&term).map_or(0, weighted_count);
+    let population_total = genes.population.len();
+    let study_total = genes.study.len();
+    let p_value = upper_tail(
+        hgeom,
+        study_count,
+        population_total,
+        population_count,
+        study_total,
+    )?;
+    Ok(TermScore {
+        term,
+        population_total,
+        population_count,
+        study_total,
+        study_count,
+        p_value,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use float_eq::float_eq;
+    use std::assert_eq;
+
+    use super::*;
+    use crate::annotation_index::AnnotationIndex;
+    use crate::enrichment::{analyze, CalculationMethod};
+    use crate::mtc::MultipleTestingCorrection;
+    use crate::test_fixtures::chain_fixture;
+
+    /// p-value of the term `label` for the study set g1..g3, which are the genes of B in
+    /// `chain_fixture(3, 3, 20)`
+    fn p_value(
+        go: &GeneOntology,
+        annotations: &AnnotationIndex,
+        method: CalculationMethod,
+        label: &str,
+    ) -> f64 {
+        let study: Vec<String> = ["g1", "g2", "g3"].iter().map(|s| s.to_string()).collect();
+        let result = analyze(
+            go,
+            annotations,
+            &study,
+            &[],
+            method,
+            MultipleTestingCorrection::None,
+        )
+        .unwrap();
+        result
+            .results
+            .iter()
+            .find(|r| r.label == label)
+            .unwrap()
+            .p_value
+    }
+
+    #[test]
+    fn test_bottom_up_order() {
+        let (go, _) = chain_fixture(3, 3, 20);
+        let terms: HashSet<usize> = (0..3).collect();
+        let order: Vec<&str> = bottom_up_order(&go, &terms)
+            .into_iter()
+            .map(|t| go.term_at(t).label.as_str())
+            .collect();
+        assert_eq!(vec!["B", "A", "root"], order);
+    }
+
+    #[test]
+    fn test_elim() {
+        let (go, annotations) = chain_fixture(3, 3, 20);
+        // B: 1/choose(20,3) = 0.000877193
+        let p_b = p_value(&go, &annotations, CalculationMethod::Elim, "B");
+        assert!(float_eq!(0.000877193, p_b, rmax <= 1e-6));
+        // term-for-term A: phyper(2, 6, 14, 3, lower.tail=FALSE) = 0.01754386 in R
+        let p_a = p_value(&go, &annotations, CalculationMethod::TermForTerm, "A");
+        assert!(float_eq!(0.01754386, p_a, rmax <= 1e-6));
+        // elim removes the genes of B from A, leaving no study genes
+        let p_a = p_value(&go, &annotations, CalculationMethod::Elim, "A");
+        assert!(float_eq!(1.0, p_a, rmax <= 1e-9));
+    }
+
+    #[test]
+    fn test_weight() {
+        let (go, annotations) = chain_fixture(3, 3, 20);
+        let p_b = p_value(&go, &annotations, CalculationMethod::Weight, "B");
+        assert!(float_eq!(0.000877193, p_b, rmax <= 1e-6));
+        // B is more significant than A, so the genes of B are down-weighted in A
+        let p_a = p_value(&go, &annotations, CalculationMethod::Weight, "A");
+        assert!(float_eq!(1.0, p_a, rmax <= 1e-9));
+        assert_eq!(1.0, sig_ratio(1e-60, 1e-70));
+    }
+}
@@ -0,0 +1,411 @@
+//! The Tauri commands of the desktop application.
+//!
+//! The commands only look up the datasets of the session, call the analysis core
+//! ([`ontologizer_core`]) and serialize the result for the frontend. Long-running commands take a job
+//! id and send their progress to the frontend as [`PROGRESS_EVENT`] events.
+
+use std::sync::Arc;
+use tauri::{command, AppHandle, Emitter, State};
+
+use ontologizer_core::annotation_filter::AnnotationFilter;
+use ontologizer_core::annotation_index::AnnotationIndex;
+use ontologizer_core::annotation_stats::annotation_set_stats;
+use ontologizer_core::diagnostics::{ParseDiagnostics, ParseMode};
+use ontologizer_core::enrichment::{analyze_methods, CalculationMethod};
+use ontologizer_core::evidence::{EvidenceFilter, EvidenceOntologyState};
+use ontologizer_core::export::{save_report, ExportFormat, Report};
+use ontologizer_core::gaf::GafRecord;
+use ontologizer_core::goannot::{
+    annotation_set, compact_annotations, open_annotation_file, read_gaf, store_records,
+    AnnotationStat,
+};
+use ontologizer_core::gontology::{load_go, store_ontology, GeneOntology};
+use ontologizer_core::gpad::{join, parse_gpad, parse_gpi};
+use ontologizer_core::jobs::{Job, JobState};
+use ontologizer_core::mgsa::{mgsa, MgsaConfig};
+use ontologizer_core::mtc::MultipleTestingCorrection;
+use ontologizer_core::session::{
+    file_name, with_id, AnalysisOutput, AnalysisRun, AnnotationSet, DatasetId, GeneListInput,
+    GeneListKind, SessionState,
+};
+
+/// Name of the event that reports the progress of a job to the frontend
+pub const PROGRESS_EVENT: &str = "job-progress";
+
+/// Register the job `id`, whose progress is emitted as events
+fn start_job(jobs: &JobState, id: &str, app: AppHandle) -> Result<Job, String> {
+    jobs.start(id, move |progress| {
+        let _ = app.emit(PROGRESS_EVENT, progress);
+    })
+}
+
+/// Load a GAF 2.2 file, which may be gzip-compressed (e.g., goa_human.gaf.gz).
+/// Lines that cannot be parsed are skipped and reported, unless `mode` is strict and there are too many.
+/// The progress is reported under the id `job`, which can be used to cancel loading.
+#[command(async)]
+pub fn process_file(
+    path: String,
+    mode: Option<ParseMode>,
+    job: String,
+    app: AppHandle,
+    state: State<'_, SessionState>,
+    jobs: State<'_, JobState>,
+) -> Result<String, String> {
+    let job = start_job(&jobs, &job, app)?;
+    let mut diagnostics = ParseDiagnostics::new();
+    let (annotations, version) = read_gaf(&path, &mut diagnostics, &job)?;
+    let inputs = job.input_files()?;
+    let mut session = state.0.lock().map_err(|e| e.to_string())?;
+    store_records(
+        &mut session,
+        &file_name(&path),
+        inputs,
+        annotations,
+        version.as_deref(),
+        diagnostics,
+        mode.unwrap_or_default(),
+    )
+}
+
+/// Load a GPAD 2.0 file and the matching GPI 2.0 file, if there is one, in place of a GAF file.
+/// Both may be gzip-compressed. The progress is reported under the id `job`. The aspects of the
+/// annotations are taken from the ontology `ontology` (by default the most recently loaded one), so
+/// it must be loaded first. ECO terms that are more specific than the ids of the GO evidence codes
+/// are mapped if the Evidence and Conclusion Ontology was loaded before.
+#[command(async)]
+#[allow(clippy::too_many_arguments)]
+pub fn process_gpad_file(
+    gpad_path: String,
+    gpi_path: Option<String>,
+    ontology: Option<DatasetId>,
+    mode: Option<ParseMode>,
+    job: String,
+    app: AppHandle,
+    state: State<'_, SessionState>,
+    eco_state: State<'_, EvidenceOntologyState>,
+    jobs: State<'_, JobState>,
+) -> Result<String, String> {
+    let (_, go) = state
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .ontology(ontology)?;
+    let job = start_job(&jobs, &job, app)?;
+    // open both files first, so that the progress refers to the size of both
+    let gpad_reader = open_annotation_file(&gpad_path, &job)?;
+    let gpi_reader = match &gpi_path {
+        Some(path) => Some(open_annotation_file(path, &job)?),
+        None => None,
+    };
+    let mut diagnostics = ParseDiagnostics::new();
+    let (gpad, version) = parse_gpad(gpad_reader, &mut diagnostics, &job)?;
+    let gpi = match gpi_reader {
+        Some(reader) => parse_gpi(reader, &mut diagnostics, &job)?,
+        None => vec![],
+    };
+    let eco = eco_state.0.lock().map_err(|e| e.to_string())?;
+    let records = join(&gpad, &gpi, &go, eco.as_ref(), &mut diagnostics);
+    drop(eco);
+    let annotations = annotation_set(records, &mut diagnostics);
+    let inputs = job.input_files()?;
+    let mut session = state.0.lock().map_err(|e| e.to_string())?;
+    store_records(
+        &mut session,
+        &file_name(&gpad_path),
+        inputs,
+        annotations,
+        version.as_deref(),
+        diagnostics,
+        mode.unwrap_or_default(),
+    )
+}
+
+/// Add the ontology to the session and return its id and descriptive statistics.
+/// The bytes read are reported under the id `job`, which can be used to cancel loading.
+#[command(async)]
+pub fn load_ontology(
+    path: String,
+    job: String,
+    app: AppHandle,
+    state: State<'_, SessionState>,
+    jobs: State<'_, JobState>,
+) -> Result<String, String> {
+    let job = start_job(&jobs, &job, app)?;
+    // the parsers see a cancellation as a read error
+    let go =
+        load_go(&path, &job).map_err(|e| job.check().err().unwrap_or_else(|| e.to_string()))?;
+    let inputs = job.input_files()?;
+    let mut session = state.0.lock().map_err(|e| e.to_string())?;
+    store_ontology(&mut session, &file_name(&path), inputs, go)
+}
+
+/// Load the Evidence and Conclusion Ontology (eco.obo or eco.json), which is needed to filter
+/// annotations by ECO terms
+#[command(async)]
+pub fn load_evidence_ontology(
+    path: String,
+    state: State<'_, EvidenceOntologyState>,
+) -> Result<String, String> {
+    let eco = load_go(&path, &Job::default()).map_err(|e| e.to_string())?;
+    let stats = vec![
+        AnnotationStat::from_string("data-version", eco.data_version().unwrap_or("n/a")),
+        AnnotationStat::from_int("Total terms", eco.term_count()),
+    ];
+    *state.0.lock().map_err(|e| e.to_string())? = Some(eco);
+    serde_json::to_string(&stats).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Add the loaded records of the annotation set `annotations` (by default the most recently loaded
+/// one) that pass `filter` as a new annotation set, which the analyses use unless another one is selected.
+///
+/// The filter always starts from all loaded records, so an empty filter restores all annotations.
+/// Returns the id of the new set and the statistics and histograms of the filtered annotations, as
+/// shown on the setup page.
+#[command]
+pub fn apply_annotation_filter(
+    annotations: Option<DatasetId>,
+    filter: AnnotationFilter,
+    state: State<'_, SessionState>,
+    eco_state: State<'_, EvidenceOntologyState>,
+) -> Result<String, String> {
+    let eco = eco_state.0.lock().map_err(|e| e.to_string())?;
+    let mut session = state.0.lock().map_err(|e| e.to_string())?;
+    let (id, annotation_set) = session.annotations(annotations)?;
+    let records = &annotation_set.records;
+    let compiled = filter.compile(eco.as_ref(), records)?;
+    let retained: Vec<GafRecord> = compiled.apply(records).into_iter().cloned().collect();
+    // the records were checked when they were loaded
+    let (annotations, negated) = compact_annotations(&retained, &mut ParseDiagnostics::new());
+    let mut summary = annotation_set_stats(&retained, &annotations, &negated);
+    let filter_stats = [
+        AnnotationStat::from_int("Loaded records", records.len()),
+        AnnotationStat::from_int("Records after filtering", retained.len()),
+    ];
+    summary.stats.splice(0..0, filter_stats);
+    let filtered = AnnotationSet {
+        records: Arc::clone(records),
+        annotations,
+        negated,
+    };
+    let id = session.add_filtered_annotations(id, filtered)?;
+    with_id(id, &summary)
+}
+
+/// Run an enrichment analysis with the ontology and annotations that were loaded on the setup page.
+///
+/// Returns one result table for each of the requested calculation methods. The number of tested terms
+/// and the progress of a Westfall-Young correction are reported under the id `job`, which can be used
+/// to cancel the analysis. With `exclude_negated`, genes with a negated (NOT) annotation to a term are
+/// not counted for the term and its descendants. Only annotations with evidence that passes the `evidence` filter are used.
+#[allow(clippy::too_many_arguments)]
+#[command(async)]
+pub fn run_analysis(
+    ontology: Option<DatasetId>,
+    annotations: Option<DatasetId>,
+    study: GeneListInput,
+    population: GeneListInput,
+    methods: Vec<CalculationMethod>,
+    correction: MultipleTestingCorrection,
+    exclude_negated: Option<bool>,
+    evidence: Option<EvidenceFilter>,
+    job: String,
+    app: AppHandle,
+    state: State<'_, SessionState>,
+    eco_state: State<'_, EvidenceOntologyState>,
+    jobs: State<'_, JobState>,
+) -> Result<String, String> {
+    let job = start_job(&jobs, &job, app)?;
+    let mut session = state.0.lock().map_err(|e| e.to_string())?;
+    let (ontology, go) = session.ontology(ontology)?;
+    let (annotations, annotation_set) = session.annotations(annotations)?;
+    let (study, study_genes) = session.gene_list(study, GeneListKind::StudySet)?;
+    let (population, population_genes) = session.gene_list(population, GeneListKind::Population)?;
+    // the datasets are shared, so other commands can use the session during the analysis
+    drop(session);
+    let (gene_annotations, parameters) =
+        annotation_index(&go, &annotation_set, evidence, exclude_negated, &eco_state)?;
+    let results = analyze_methods(
+        &go,
+        &gene_annotations,
+        &study_genes.genes,
+        &population_genes.genes,
+        &methods,
+        correction,
+        &|stage, done, total| {
+            job.check()?;
+            // about one hundred updates are enough for a progress bar
+            if done == total || done % (total / 100).max(1) == 0 {
+                job.progress(stage, done as u64, Some(total as u64))?;
+            }
+            Ok(())
+        },
+    )?;
+    let name = format!(
+        "{} ({} genes)",
+        methods
+            .iter()
+            .map(|m| format!("{:?}", m))
+            .collect::<Vec<_>>()
+            .join(", "),
+        study_genes.genes.len()
+    );
+    let run = AnalysisRun {
+        ontology,
+        annotations,
+        study,
+        population,
+        parameters,
+        results: AnalysisOutput::Enrichment(results),
+    };
+    let (id, run) = state
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .add_result(&name, run);
+    with_id(id, &*run)
+}
+
+/// The index of the annotations that pass the evidence filter, which all analyses use, and the
+/// parameters that describe the selection
+fn annotation_index(
+    go: &GeneOntology,
+    annotation_set: &AnnotationSet,
+    evidence: Option<EvidenceFilter>,
+    exclude_negated: Option<bool>,
+    eco_state: &EvidenceOntologyState,
+) -> Result<(AnnotationIndex, Vec<AnnotationStat>), String> {
+    let exclude_negated = exclude_negated.unwrap_or(false);
+    let (index, allowed) = annotation_set.index(
+        go,
+        &evidence.unwrap_or_default(),
+        eco_state.0.lock().map_err(|e| e.to_string())?.as_ref(),
+        exclude_negated,
+    )?;
+    let codes: Vec<String> = allowed.codes.iter().map(|c| c.to_string()).collect();
+    let parameters = vec![
+        AnnotationStat::from_string("Evidence codes", &codes.join(", ")),
+        AnnotationStat::from_string("Exclude NOT annotations", &exclude_negated.to_string()),
+    ];
+    Ok((index, parameters))
+}
+
+/// Run MGSA on the given datasets (by default the most recently loaded ones) and store the result
+/// in the session. The MCMC steps are reported under the id `job`, which can be used to cancel the
+/// analysis. The annotations are selected by `evidence` and `exclude_negated` as for [`run_analysis`].
+#[allow(clippy::too_many_arguments)]
+#[command(async)]
+pub fn run_mgsa(
+    ontology: Option<DatasetId>,
+    annotations: Option<DatasetId>,
+    study: GeneListInput,
+    population: GeneListInput,
+    config: MgsaConfig,
+    exclude_negated: Option<bool>,
+    evidence: Option<EvidenceFilter>,
+    job: String,
+    app: AppHandle,
+    state: State<'_, SessionState>,
+    eco_state: State<'_, EvidenceOntologyState>,
+    jobs: State<'_, JobState>,
+) -> Result<String, String> {
+    let job = start_job(&jobs, &job, app)?;
+    let mut session = state.0.lock().map_err(|e| e.to_string())?;
+    let (ontology, go) = session.ontology(ontology)?;
+    let (annotations, annotation_set) = session.annotations(annotations)?;
+    let (study, study_genes) = session.gene_list(study, GeneListKind::StudySet)?;
+    let (population, population_genes) = session.gene_list(population, GeneListKind::Population)?;
+    drop(session);
+    let (gene_annotations, mut parameters) =
+        annotation_index(&go, &annotation_set, evidence, exclude_negated, &eco_state)?;
+    let result = mgsa(
+        &go,
+        &gene_annotations,
+        &study_genes.genes,
+        &population_genes.genes,
+        &config,
+        &|stage, done, total| job.progress(stage, done as u64, Some(total as u64)),
+    )?;
+    let fixed = |value: Option<f64>| value.map_or("sampled".to_string(), |v| v.to_string());
+    parameters.extend([
+        AnnotationStat::from_string("alpha", &fixed(config.alpha)),
+        AnnotationStat::from_string("beta", &fixed(config.beta)),
+        AnnotationStat::from_string("p", &fixed(config.p)),
+        AnnotationStat::from_int("Seed", config.seed),
+    ]);
+    let run = AnalysisRun {
+        ontology,
+        annotations,
+        study,
+        population,
+        parameters,
+        results: AnalysisOutput::Mgsa(result),
+    };
+    let name = format!("MGSA ({} genes)", study_genes.genes.len());
+    let (id, run) = state
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .add_result(&name, run);
+    with_id(id, &*run)
+}
+
+#[command]
+pub fn list_datasets(state: State<'_, SessionState>) -> Result<String, String> {
+    let session = state.0.lock().map_err(|e| e.to_string())?;
+    serde_json::to_string(&session.datasets()).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[command]
+pub fn remove_dataset(id: DatasetId, state: State<'_, SessionState>) -> Result<(), String> {
+    state.0.lock().map_err(|e| e.to_string())?.remove(id)
+}
+
+/// Stop the job `job`. It returns an error the next time it checks for cancellation.
+#[command]
+pub fn cancel_job(job: String, state: State<'_, JobState>) -> Result<(), String> {
+    state.cancel(&job)
+}
+
+/// Save the analysis result `result` (by default the most recent one) to `path` and return the paths of
+/// the written files
+#[command]
+pub fn save_results(
+    result: Option<DatasetId>,
+    path: String,
+    format: ExportFormat,
+    state: State<'_, SessionState>,
+) -> Result<String, String> {
+    let session = state.0.lock().map_err(|e| e.to_string())?;
+    let (id, run) = session.result(result)?;
+    let mut parameters = vec![];
+    let mut inputs = vec![];
+    let datasets = [
+        ("Ontology", run.ontology),
+        ("Annotations", run.annotations),
+        ("Study set", run.study),
+        ("Population", run.population),
+    ];
+    for (key, dataset) in datasets {
+        match session.dataset(dataset) {
+            Some(info) => {
+                parameters.push(AnnotationStat::from_string(key, &info.name));
+                inputs.extend(info.inputs);
+            }
+            None => parameters.push(AnnotationStat::from_string(
+                key,
+                &format!("removed dataset {}", dataset),
+            )),
+        }
+    }
+    let title = session.dataset(id).map(|d| d.name).unwrap_or_default();
+    drop(session);
+    parameters.extend(run.parameters.iter().cloned());
+    let report = Report {
+        title,
+        parameters,
+        inputs,
+        results: &run.results,
+    };
+    let written = save_report(&path, format, &report)?;
+    serde_json::to_string(&written).map_err(|e| format!("Serialization error: {}", e))
+}
@@ -0,0 +1,39 @@
+//! The Tauri application of the Ontologizer.
+//!
+//! The analysis itself is done by the `ontologizer-core` crate, which the command-line binary
+//! `ontologizer-cli` shares; this crate only wraps it in Tauri commands (see [`commands`]).
+
+mod commands;
+mod window_handler;
+use commands::{
+    apply_annotation_filter, cancel_job, list_datasets, load_evidence_ontology, load_ontology,
+    process_file, process_gpad_file, remove_dataset, run_analysis, run_mgsa, save_results,
+};
+use ontologizer_core::evidence::EvidenceOntologyState;
+use ontologizer_core::jobs::JobState;
+use ontologizer_core::session::SessionState;
+
+/// Start the desktop application
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_fs::init())
+        .manage(SessionState::default())
+        .manage(JobState::default())
+        .manage(EvidenceOntologyState::default())
+        .invoke_handler(tauri::generate_handler![
+            process_file,
+            process_gpad_file,
+            load_ontology,
+            load_evidence_ontology,
+            apply_annotation_filter,
+            run_analysis,
+            run_mgsa,
+            list_datasets,
+            remove_dataset,
+            cancel_job,
+            save_results
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -1,20 +1,10 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod goannot;
-mod window_handler;
-mod gontology;
-mod exact_fisher;
 use std::env;
-use goannot::process_file;
 
 fn main() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_dialog::init())
-        .plugin(tauri_plugin_fs::init())
-        .invoke_handler(tauri::generate_handler![process_file])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+    ontologizer_lib::run();
 }
 
 #[tauri::command]